@@ -13,10 +13,24 @@
 
 use crate::{
 	GetAccountInformation,
-	api::error::{Error, Result},
-	rpc::Request,
+	api::{
+		builder::ApiBuilder,
+		error::{Error, Result},
+		rpc_capabilities::{RpcCapabilities, RpcMethods},
+	},
+	rpc::{Request, Subscribe},
 	runtime_api::RuntimeApiClient,
 };
+#[cfg(feature = "std")]
+use crate::{
+	api::{
+		UnexpectedTxStatus,
+		metadata_cache::MetadataCache,
+		nonce_manager::NonceManager,
+		shared_runtime::{RuntimeUpdater, SharedRuntime},
+	},
+	rpc::TransactionPoolError,
+};
 use ac_compose_macros::rpc_params;
 use ac_node_api::metadata::Metadata;
 use ac_primitives::{Config, ExtrinsicParams, SignExtrinsic};
@@ -26,7 +40,7 @@ use alloc::sync::Arc;
 use codec::Decode;
 use core::convert::TryFrom;
 use frame_metadata::RuntimeMetadataPrefixed;
-use log::{debug, info};
+use log::{debug, info, warn};
 use sp_core::Bytes;
 use sp_version::RuntimeVersion;
 
@@ -39,16 +53,24 @@ pub const KSM_V14_METADATA_PATH: &str = "./../ksm_metadata_v14.bin";
 pub struct Api<T: Config, Client> {
 	signer: Option<T::ExtrinsicSigner>,
 	genesis_hash: T::Hash,
-	metadata: Metadata,
+	metadata: Arc<Metadata>,
 	runtime_version: RuntimeVersion,
 	client: Arc<Client>,
 	runtime_api: RuntimeApiClient<T, Client>,
 	additional_extrinsic_params:
 		Option<<T::ExtrinsicParams as ExtrinsicParams<T::Index, T::Hash>>::AdditionalParams>,
+	rpc_capabilities: RpcCapabilities,
+	#[cfg(feature = "std")]
+	metadata_cache: Option<MetadataCache>,
+	#[cfg(feature = "std")]
+	shared_runtime: Option<SharedRuntime>,
+	#[cfg(feature = "std")]
+	nonce_manager: NonceManager<T::AccountId, T::Index>,
 }
 
 impl<T: Config, Client> Api<T, Client> {
 	/// Create a new api instance without any node interaction.
+	/// The rpc methods offered by the node are unknown, so the legacy methods are used.
 	pub fn new_offline(
 		genesis_hash: T::Hash,
 		metadata: Metadata,
@@ -60,14 +82,27 @@ impl<T: Config, Client> Api<T, Client> {
 		Self {
 			signer: None,
 			genesis_hash,
-			metadata,
+			metadata: Arc::new(metadata),
 			runtime_version,
 			client,
 			runtime_api,
 			additional_extrinsic_params: None,
+			rpc_capabilities: RpcCapabilities::unknown(),
+			#[cfg(feature = "std")]
+			metadata_cache: None,
+			#[cfg(feature = "std")]
+			shared_runtime: None,
+			#[cfg(feature = "std")]
+			nonce_manager: NonceManager::new(),
 		}
 	}
 
+	/// Create a builder for an api using the given `client`, which only fetches the items
+	/// from the node that have not been set.
+	pub fn builder(client: Client) -> ApiBuilder<T, Client> {
+		ApiBuilder::new(client)
+	}
+
 	/// Set the api signer account.
 	pub fn set_signer(&mut self, signer: T::ExtrinsicSigner) {
 		self.signer = Some(signer);
@@ -84,18 +119,42 @@ impl<T: Config, Client> Api<T, Client> {
 	}
 
 	/// Get the cached metadata of the substrate node.
+	/// In shared runtime mode, this is the metadata the api has been created or last updated
+	/// with. Use [`Self::metadata_snapshot`] to get the current metadata of the shared runtime.
 	pub fn metadata(&self) -> &Metadata {
 		&self.metadata
 	}
 
 	/// Get the cached runtime version of the substrate node.
+	/// In shared runtime mode, this is the runtime version the api has been created or last
+	/// updated with. Use [`Self::runtime_version_snapshot`] to get the current one.
 	pub fn runtime_version(&self) -> &RuntimeVersion {
 		&self.runtime_version
 	}
 
-	/// Get the cached spec version of the substrate node.
+	/// Get the current metadata. In shared runtime mode, this is the metadata of the shared
+	/// runtime, which is kept up to date upon runtime upgrades, see [`Self::runtime_updater`].
+	pub fn metadata_snapshot(&self) -> Arc<Metadata> {
+		#[cfg(feature = "std")]
+		if let Some(shared_runtime) = &self.shared_runtime {
+			return shared_runtime.metadata()
+		}
+		self.metadata.clone()
+	}
+
+	/// Get the current runtime version. In shared runtime mode, this is the runtime version of
+	/// the shared runtime, see [`Self::metadata_snapshot`].
+	pub fn runtime_version_snapshot(&self) -> RuntimeVersion {
+		#[cfg(feature = "std")]
+		if let Some(shared_runtime) = &self.shared_runtime {
+			return shared_runtime.runtime_version()
+		}
+		self.runtime_version.clone()
+	}
+
+	/// Get the current spec version of the substrate node.
 	pub fn spec_version(&self) -> u32 {
-		self.runtime_version.spec_version
+		self.runtime_version_snapshot().spec_version
 	}
 
 	/// Get the rpc client.
@@ -111,6 +170,82 @@ impl<T: Config, Client> Api<T, Client> {
 		self.additional_extrinsic_params = Some(add_params);
 	}
 
+	/// Get the additional params, if any have been set.
+	pub fn additional_params(
+		&self,
+	) -> Option<&<T::ExtrinsicParams as ExtrinsicParams<T::Index, T::Hash>>::AdditionalParams> {
+		self.additional_extrinsic_params.as_ref()
+	}
+
+	/// Get the rpc methods offered by the node, as reported upon creation of the api.
+	pub fn rpc_capabilities(&self) -> &RpcCapabilities {
+		&self.rpc_capabilities
+	}
+
+	/// Set the rpc methods offered by the node. They decide whether the legacy methods or the
+	/// ones of the new json-rpc specification are used.
+	pub fn set_rpc_capabilities(&mut self, rpc_capabilities: RpcCapabilities) {
+		self.rpc_capabilities = rpc_capabilities;
+	}
+
+	/// Get the cache the metadata is loaded from and stored to upon runtime updates.
+	#[cfg(feature = "std")]
+	pub fn metadata_cache(&self) -> Option<&MetadataCache> {
+		self.metadata_cache.as_ref()
+	}
+
+	/// Set the cache the metadata is loaded from and stored to upon runtime updates.
+	#[cfg(feature = "std")]
+	pub fn set_metadata_cache(&mut self, metadata_cache: MetadataCache) {
+		self.metadata_cache = Some(metadata_cache);
+	}
+
+	/// Get the runtime shared with the clones of the api, if the shared runtime mode is enabled.
+	#[cfg(feature = "std")]
+	pub fn shared_runtime(&self) -> Option<&SharedRuntime> {
+		self.shared_runtime.as_ref()
+	}
+
+	/// Hold the metadata and runtime version behind shared interior mutability. Clones of
+	/// the api created afterwards, and the event subscriptions created from them, share the
+	/// runtime, so an update of one of them is seen by all others.
+	#[cfg(feature = "std")]
+	pub fn enable_shared_runtime(&mut self) -> &SharedRuntime {
+		let (metadata, runtime_version) = (self.metadata.clone(), self.runtime_version.clone());
+		self.shared_runtime
+			.get_or_insert_with(|| SharedRuntime::new(metadata, runtime_version))
+	}
+
+	/// Enable the shared runtime mode and create the task keeping the shared runtime up to
+	/// date upon runtime upgrades. The task needs to be run in the background, see
+	/// [`RuntimeUpdater`].
+	#[cfg(feature = "std")]
+	pub fn runtime_updater(&mut self) -> RuntimeUpdater<T, Client> {
+		let shared_runtime = self.enable_shared_runtime().clone();
+		RuntimeUpdater::new(
+			self.client.clone(),
+			shared_runtime,
+			self.genesis_hash,
+			self.metadata_cache.clone(),
+		)
+	}
+
+	/// Set the metadata and runtime version, in all api instances sharing the runtime.
+	fn set_runtime(&mut self, metadata: Metadata, runtime_version: RuntimeVersion) {
+		self.metadata = Arc::new(metadata);
+		self.runtime_version = runtime_version;
+		#[cfg(feature = "std")]
+		if let Some(shared_runtime) = &self.shared_runtime {
+			shared_runtime.set(self.metadata.clone(), self.runtime_version.clone());
+		}
+	}
+
+	/// The nonce manager of the api, shared by all its clones.
+	#[cfg(feature = "std")]
+	pub fn nonce_manager(&self) -> &NonceManager<T::AccountId, T::Index> {
+		&self.nonce_manager
+	}
+
 	/// Access the RuntimeApi.
 	pub fn runtime_api(&self) -> &RuntimeApiClient<T, Client> {
 		&self.runtime_api
@@ -121,9 +256,22 @@ impl<T: Config, Client> Api<T, Client> {
 	pub fn extrinsic_params(&self, nonce: T::Index) -> T::ExtrinsicParams {
 		let additional_extrinsic_params =
 			self.additional_extrinsic_params.clone().unwrap_or_default();
+		self.extrinsic_params_with(nonce, additional_extrinsic_params)
+	}
+
+	/// Get the extrinsic params with the given additional params instead of the set ones.
+	pub fn extrinsic_params_with(
+		&self,
+		nonce: T::Index,
+		additional_extrinsic_params: <T::ExtrinsicParams as ExtrinsicParams<
+			T::Index,
+			T::Hash,
+		>>::AdditionalParams,
+	) -> T::ExtrinsicParams {
+		let runtime_version = self.runtime_version_snapshot();
 		T::ExtrinsicParams::new(
-			self.runtime_version.spec_version,
-			self.runtime_version.transaction_version,
+			runtime_version.spec_version,
+			runtime_version.transaction_version,
 			nonce,
 			self.genesis_hash,
 			additional_extrinsic_params,
@@ -137,48 +285,23 @@ where
 	Client: Request,
 {
 	/// Create a new Api client with call to the node to retrieve metadata.
-	#[maybe_async::async_impl]
+	/// Use the [`ApiBuilder`] to avoid fetching items that are already known.
+	#[maybe_async::maybe_async(?Send)]
 	pub async fn new(client: Client) -> Result<Self> {
-		let genesis_hash_future = Self::get_genesis_hash(&client);
-		let metadata_future = Self::get_metadata(&client);
-		let runtime_version_future = Self::get_runtime_version(&client);
-
-		let (genesis_hash, metadata, runtime_version) = futures_util::future::try_join3(
-			genesis_hash_future,
-			metadata_future,
-			runtime_version_future,
-		)
-		.await?;
-		info!("Got genesis hash: {genesis_hash:?}");
-		debug!("Metadata: {metadata:?}");
-		info!("Runtime Version: {runtime_version:?}");
-		Ok(Self::new_offline(genesis_hash, metadata, runtime_version, client))
-	}
-
-	/// Create a new Api client with call to the node to retrieve metadata.
-	#[maybe_async::sync_impl]
-	pub fn new(client: Client) -> Result<Self> {
-		let genesis_hash = Self::get_genesis_hash(&client)?;
-		info!("Got genesis hash: {genesis_hash:?}");
-
-		let metadata = Self::get_metadata(&client)?;
-		debug!("Metadata: {metadata:?}");
-
-		let runtime_version = Self::get_runtime_version(&client)?;
-		info!("Runtime Version: {runtime_version:?}");
-
-		Ok(Self::new_offline(genesis_hash, metadata, runtime_version, client))
+		ApiBuilder::new(client).build().await
 	}
 }
 
-#[maybe_async::maybe_async(?Send)]
+#[ac_async_macros::maybe_async]
 pub trait UpdateRuntime {
 	/// Updates the runtime and metadata of the api via node query.
 	/// Ideally, this function is called if a substrate update runtime event is encountered.
+	/// In shared runtime mode, all api instances sharing the runtime are updated.
+	/// If the api has a [`MetadataCache`], the metadata is loaded from it if possible.
 	async fn update_runtime(&mut self) -> Result<()>;
 }
 
-#[maybe_async::maybe_async(?Send)]
+#[ac_async_macros::maybe_async]
 impl<T, Client> UpdateRuntime for Api<T, Client>
 where
 	T: Config,
@@ -186,19 +309,49 @@ where
 {
 	#[maybe_async::sync_impl]
 	fn update_runtime(&mut self) -> Result<()> {
+		#[cfg(feature = "std")]
+		if let Some(metadata_cache) = &self.metadata_cache {
+			let runtime_version = Self::get_runtime_version(&self.client)?;
+			let (metadata, runtime_version) = Self::get_cached_metadata(
+				&self.client,
+				metadata_cache,
+				self.genesis_hash,
+				&runtime_version,
+			)?;
+			info!("Runtime Version: {runtime_version:?}");
+
+			self.set_runtime(metadata, runtime_version);
+			return Ok(())
+		}
+
 		let metadata = Self::get_metadata(&self.client)?;
 		let runtime_version = Self::get_runtime_version(&self.client)?;
 
 		debug!("Metadata: {metadata:?}");
 		info!("Runtime Version: {runtime_version:?}");
 
-		self.metadata = metadata;
-		self.runtime_version = runtime_version;
+		self.set_runtime(metadata, runtime_version);
 		Ok(())
 	}
 
 	#[maybe_async::async_impl(?Send)]
 	async fn update_runtime(&mut self) -> Result<()> {
+		#[cfg(feature = "std")]
+		if let Some(metadata_cache) = &self.metadata_cache {
+			let runtime_version = Self::get_runtime_version(&self.client).await?;
+			let (metadata, runtime_version) = Self::get_cached_metadata(
+				&self.client,
+				metadata_cache,
+				self.genesis_hash,
+				&runtime_version,
+			)
+			.await?;
+			info!("Runtime Version: {runtime_version:?}");
+
+			self.set_runtime(metadata, runtime_version);
+			return Ok(())
+		}
+
 		let metadata_future = Self::get_metadata(&self.client);
 		let runtime_version_future = Self::get_runtime_version(&self.client);
 
@@ -208,8 +361,7 @@ where
 		debug!("Metadata: {metadata:?}");
 		info!("Runtime Version: {runtime_version:?}");
 
-		self.metadata = metadata;
-		self.runtime_version = runtime_version;
+		self.set_runtime(metadata, runtime_version);
 		Ok(())
 	}
 }
@@ -231,6 +383,96 @@ where
 		let account = self.signer_account().ok_or(Error::NoSigner)?;
 		self.get_account_nonce(account).await
 	}
+
+	/// Get the next nonce of the api signer account from the nonce manager.
+	/// See [`Self::get_managed_nonce_for`].
+	#[cfg(feature = "std")]
+	#[maybe_async::maybe_async(?Send)]
+	pub async fn get_managed_nonce(&self) -> Result<T::Index> {
+		let account = self.signer_account().ok_or(Error::NoSigner)?;
+		self.get_managed_nonce_for(account).await
+	}
+
+	/// Hand out the next nonce of the `account` from the [`NonceManager`] of the api.
+	/// The node is only asked for the nonce, via `system_accountNextIndex`, if the manager has
+	/// not synced it yet. Subsequent nonces are handed out without any node request.
+	///
+	/// Nonces of extrinsics that are not going to be submitted should be returned with
+	/// [`NonceManager::reclaim`]. If the node rejects an extrinsic due to its nonce, the nonce
+	/// needs to be synced again, see [`Self::resync_nonce_on_error`].
+	#[cfg(feature = "std")]
+	#[maybe_async::maybe_async(?Send)]
+	pub async fn get_managed_nonce_for(&self, account: &T::AccountId) -> Result<T::Index> {
+		if let Some(nonce) = self.nonce_manager.next_nonce(account) {
+			return Ok(nonce)
+		}
+		let next_index = self.get_system_account_next_index(account.clone()).await?;
+		Ok(self.nonce_manager.next_nonce_or_sync(account, next_index))
+	}
+
+	/// Sync the managed nonce of the `account` with `system_accountNextIndex` of the node,
+	/// which includes the extrinsics in the transaction pool. Returns the next nonce.
+	#[cfg(feature = "std")]
+	#[maybe_async::maybe_async(?Send)]
+	pub async fn resync_nonce(&self, account: &T::AccountId) -> Result<T::Index> {
+		let next_index = self.get_system_account_next_index(account.clone()).await?;
+		self.nonce_manager.sync(account, next_index);
+		Ok(next_index)
+	}
+
+	/// Sync the managed nonce of the `account` again, if the `error` returned upon submitting or
+	/// watching one of its extrinsics indicates a nonce mismatch, see
+	/// [`Error::requires_nonce_resync`]. Returns true if the nonce has been synced.
+	#[cfg(feature = "std")]
+	#[maybe_async::maybe_async(?Send)]
+	pub async fn resync_nonce_on_error(
+		&self,
+		account: &T::AccountId,
+		error: &Error,
+	) -> Result<bool> {
+		if !error.requires_nonce_resync() {
+			return Ok(false)
+		}
+		let next_index = self.resync_nonce(account).await?;
+		debug!("Resynced nonce of {account:?} to {next_index:?} due to {error:?}");
+		Ok(true)
+	}
+
+	/// Release the managed `nonce` of the `account` after submitting or watching its extrinsic
+	/// failed with `error`. The nonce is reclaimed if the transaction pool rejected or dropped
+	/// the extrinsic. It is synced with the node again if the node rejected the extrinsic due to
+	/// its nonce, see [`Error::requires_nonce_resync`], or if the submission failed otherwise,
+	/// as the node might have received the extrinsic anyway. Returns true if the nonce has
+	/// been synced.
+	#[cfg(feature = "std")]
+	#[maybe_async::maybe_async(?Send)]
+	pub async fn release_managed_nonce(
+		&self,
+		account: &T::AccountId,
+		nonce: T::Index,
+		error: &Error,
+	) -> Result<bool> {
+		let rejected_by_pool = match error {
+			Error::RpcClient(e) => matches!(
+				e.pool_error(),
+				Some(pool_error) if pool_error != TransactionPoolError::AlreadyImported
+			),
+			_ => false,
+		};
+		if error.requires_nonce_resync()
+			|| (matches!(error, Error::RpcClient(_)) && !rejected_by_pool)
+		{
+			let next_index = self.resync_nonce(account).await?;
+			debug!("Resynced nonce of {account:?} to {next_index:?} due to {error:?}");
+			return Ok(true)
+		}
+		if rejected_by_pool
+			|| matches!(error, Error::UnexpectedTxStatus(UnexpectedTxStatus::Dropped))
+		{
+			self.nonce_manager.reclaim(account, nonce);
+		}
+		Ok(false)
+	}
 }
 
 /// Private node query methods. They should be used internally only, because the user should retrieve the data from the struct cache.
@@ -242,7 +484,7 @@ where
 {
 	/// Get the genesis hash from node via websocket query.
 	#[maybe_async::maybe_async(?Send)]
-	async fn get_genesis_hash(client: &Client) -> Result<T::Hash> {
+	pub(crate) async fn get_genesis_hash(client: &Client) -> Result<T::Hash> {
 		let genesis: Option<T::Hash> =
 			client.request("chain_getBlockHash", rpc_params![Some(0)]).await?;
 		genesis.ok_or(Error::FetchGenesisHash)
@@ -250,7 +492,7 @@ where
 
 	/// Get runtime version from node via websocket query.
 	#[maybe_async::maybe_async(?Send)]
-	async fn get_runtime_version(client: &Client) -> Result<RuntimeVersion> {
+	pub(crate) async fn get_runtime_version(client: &Client) -> Result<RuntimeVersion> {
 		let version: RuntimeVersion =
 			client.request("state_getRuntimeVersion", rpc_params![]).await?;
 		Ok(version)
@@ -258,23 +500,111 @@ where
 
 	/// Get metadata from node via websocket query.
 	#[maybe_async::maybe_async(?Send)]
-	async fn get_metadata(client: &Client) -> Result<Metadata> {
+	pub(crate) async fn get_metadata(client: &Client) -> Result<Metadata> {
 		let metadata_bytes: Bytes = client.request("state_getMetadata", rpc_params![]).await?;
+		decode_metadata(&metadata_bytes)
+	}
+
+	/// Get the metadata of the `runtime_version` from the `metadata_cache` or, if it has not
+	/// been cached yet, from the node and store it in the cache.
+	///
+	/// The node is asked for the runtime version and the metadata at the same block, so the
+	/// metadata is never cached for the wrong runtime version if the runtime is upgraded in
+	/// between. Returns the metadata together with the runtime version it belongs to, which
+	/// differs from `runtime_version` in that case.
+	#[cfg(feature = "std")]
+	#[maybe_async::maybe_async(?Send)]
+	pub(crate) async fn get_cached_metadata(
+		client: &Client,
+		metadata_cache: &MetadataCache,
+		genesis_hash: T::Hash,
+		runtime_version: &RuntimeVersion,
+	) -> Result<(Metadata, RuntimeVersion)> {
+		if let Some(metadata) = metadata_cache.load(genesis_hash, runtime_version) {
+			return Ok((metadata, runtime_version.clone()))
+		}
+		let (metadata_bytes, runtime_version) = Self::get_latest_runtime(client).await?;
+		let metadata = decode_metadata(&metadata_bytes)?;
+		// Failing to cache the metadata only slows down the next start.
+		if let Err(e) = metadata_cache.store(genesis_hash, &runtime_version, &metadata_bytes) {
+			warn!("Could not cache metadata: {e:?}");
+		}
+		Ok((metadata, runtime_version))
+	}
 
-		let metadata = RuntimeMetadataPrefixed::decode(&mut metadata_bytes.0.as_slice())?;
-		Metadata::try_from(metadata).map_err(|e| e.into())
+	/// Get the encoded metadata together with the runtime version it belongs to. Both are
+	/// fetched at the latest block, so they match even if the runtime is upgraded in between.
+	#[cfg(feature = "std")]
+	#[maybe_async::maybe_async(?Send)]
+	pub(crate) async fn get_latest_runtime(client: &Client) -> Result<(Bytes, RuntimeVersion)> {
+		let at: Option<T::Hash> = client.request("chain_getBlockHash", rpc_params![]).await?;
+		let runtime_version: RuntimeVersion =
+			client.request("state_getRuntimeVersion", rpc_params![at]).await?;
+		let metadata_bytes: Bytes = client.request("state_getMetadata", rpc_params![at]).await?;
+		Ok((metadata_bytes, runtime_version))
+	}
+
+	/// Get the rpc methods offered by the node. Not all nodes answer `rpc_methods`,
+	/// so a failure is not fatal, but leaves the methods unknown.
+	#[maybe_async::maybe_async(?Send)]
+	pub(crate) async fn get_rpc_capabilities(client: &Client) -> RpcCapabilities {
+		match client.request::<RpcMethods>("rpc_methods", rpc_params![]).await {
+			Ok(rpc_methods) => rpc_methods.into(),
+			Err(e) => {
+				warn!("Could not fetch the rpc methods of the node, using legacy methods: {e:?}");
+				RpcCapabilities::unknown()
+			},
+		}
 	}
 }
+
+/// Decode the metadata, as returned by `state_getMetadata`.
+pub(crate) fn decode_metadata(bytes: &[u8]) -> Result<Metadata> {
+	let metadata = RuntimeMetadataPrefixed::decode(&mut &*bytes)?;
+	Metadata::try_from(metadata).map_err(|e| e.into())
+}
+
+/// Compile time check: the futures of the async api must be `Send` with the `send-futures`
+/// feature, so they can be spawned on multi-threaded runtimes.
+#[cfg(all(feature = "send-futures", not(feature = "sync-api")))]
+#[allow(dead_code)]
+fn assert_api_futures_are_send<T, Client>(
+	api: &Api<T, Client>,
+	account: &T::AccountId,
+	extrinsic: &Bytes,
+) where
+	T: Config,
+	Client: Request + Subscribe,
+{
+	use crate::{GetChainInfo, GetStorage, SubmitAndWatch, SubmitExtrinsic, XtStatus};
+
+	fn assert_send<F: Send>(_: &F) {}
+
+	assert_send(&api.get_storage::<u32>("System", "Number", None));
+	assert_send(&api.get_block(None));
+	assert_send(&api.submit_opaque_extrinsic(extrinsic));
+	assert_send(&api.submit_and_watch_opaque_extrinsic_until(extrinsic, XtStatus::InBlock));
+	assert_send(&api.get_managed_nonce_for(account));
+	assert_send(&api.mortality(64));
+}
+
 #[cfg(test)]
 mod tests {
 	use super::*;
-	use crate::rpc::mocks::RpcClientMock;
+	use crate::{
+		api::UnexpectedTxStatus,
+		rpc::{
+			Error as RpcClientError, JsonRpcError,
+			mocks::{self, RpcClientMock},
+		},
+	};
 	use ac_primitives::{
 		DefaultRuntimeConfig, GenericAdditionalParams, GenericExtrinsicParams, PlainTip,
 	};
 	use frame_metadata::{RuntimeMetadata, v14::ExtrinsicMetadata};
 	use scale_info::form::PortableForm;
 	use sp_core::H256;
+	use sp_runtime::AccountId32;
 	use std::{collections::HashMap, fs};
 
 	fn create_mock_api(
@@ -366,4 +696,115 @@ mod tests {
 		assert_eq!(api.metadata.extrinsic(), metadata.extrinsic());
 		assert_eq!(api.runtime_version, runtime_version);
 	}
+
+	#[test]
+	fn cached_metadata_is_fetched_with_runtime_version_of_same_block() {
+		let dir = std::env::temp_dir().join(format!("ac-api-client-{}", std::process::id()));
+		let _ = fs::remove_dir_all(&dir);
+		let metadata_cache = MetadataCache::new(&dir);
+		let encoded_metadata: Bytes = fs::read(KSM_V14_METADATA_PATH).unwrap().into();
+		let at = H256::repeat_byte(2);
+		let outdated_version = RuntimeVersion { spec_version: 10, ..Default::default() };
+		let upgraded_version = RuntimeVersion { spec_version: 11, ..Default::default() };
+		let client = RpcClientMock::default()
+			.with_response_for("chain_getBlockHash", rpc_params![], Some(at))
+			.with_response_for("state_getRuntimeVersion", rpc_params![Some(at)], &upgraded_version)
+			.with_response_for("state_getMetadata", rpc_params![Some(at)], encoded_metadata);
+
+		// The runtime has been upgraded since `outdated_version` was fetched.
+		let (_, runtime_version) = Api::<DefaultRuntimeConfig, _>::get_cached_metadata(
+			&client,
+			&metadata_cache,
+			H256::zero(),
+			&outdated_version,
+		)
+		.unwrap();
+
+		assert_eq!(runtime_version, upgraded_version);
+		assert!(metadata_cache.load(H256::zero(), &upgraded_version).is_some());
+		assert!(metadata_cache.load(H256::zero(), &outdated_version).is_none());
+		let _ = fs::remove_dir_all(&dir);
+	}
+
+	fn create_node_mock() -> RpcClientMock {
+		let encoded_metadata: Bytes = fs::read(KSM_V14_METADATA_PATH).unwrap().into();
+		RpcClientMock::default()
+			.with_response("chain_getBlockHash", Some(H256::from([1u8; 32])))
+			.with_response("state_getRuntimeVersion", RuntimeVersion::default())
+			.with_response("state_getMetadata", encoded_metadata)
+	}
+
+	#[test]
+	fn api_new_fetches_rpc_capabilities() {
+		let rpc_methods =
+			RpcMethods { methods: vec!["chain_getBlock".into(), "archive_v1_body".into()] };
+		let client = create_node_mock().with_response("rpc_methods", rpc_methods);
+
+		let api = Api::<DefaultRuntimeConfig, _>::new(client).unwrap();
+
+		assert_eq!(
+			api.rpc_capabilities(),
+			&RpcCapabilities::new(["chain_getBlock", "archive_v1_body"])
+		);
+	}
+
+	#[test]
+	fn api_new_without_rpc_methods_leaves_capabilities_unknown() {
+		let api = Api::<DefaultRuntimeConfig, _>::new(create_node_mock()).unwrap();
+
+		assert!(!api.rpc_capabilities().is_known());
+	}
+
+	#[test]
+	fn managed_nonce_is_only_fetched_once() {
+		let account = AccountId32::new([1; 32]);
+		let client = RpcClientMock::default().with_response_for(
+			"system_accountNextIndex",
+			rpc_params![account.clone()],
+			5u32,
+		);
+		let api = mocks::create_mock_api(client);
+
+		assert_eq!(api.get_managed_nonce_for(&account).unwrap(), 5);
+		// Clones share the nonce manager.
+		assert_eq!(api.clone().get_managed_nonce_for(&account).unwrap(), 6);
+
+		// Without `system_accountNextIndex`, the nonce can only be handed out locally.
+		let api = mocks::create_mock_api(RpcClientMock::default());
+		api.nonce_manager().sync(&account, 10);
+		assert_eq!(api.get_managed_nonce_for(&account).unwrap(), 10);
+		assert!(api.get_managed_nonce_for(&AccountId32::new([2; 32])).is_err());
+	}
+
+	#[test]
+	fn managed_nonce_is_resynced_upon_nonce_errors() {
+		let account = AccountId32::new([1; 32]);
+		let client = RpcClientMock::default().with_response("system_accountNextIndex", 5u32);
+		let api = mocks::create_mock_api(client);
+		for _ in 0..3 {
+			api.get_managed_nonce_for(&account).unwrap();
+		}
+
+		let dropped = Error::UnexpectedTxStatus(UnexpectedTxStatus::Dropped);
+		assert!(!api.resync_nonce_on_error(&account, &dropped).unwrap());
+		assert_eq!(api.nonce_manager().peek_nonce(&account), Some(8));
+
+		let usurped = Error::UnexpectedTxStatus(UnexpectedTxStatus::Usurped);
+		assert!(api.resync_nonce_on_error(&account, &usurped).unwrap());
+		assert_eq!(api.get_managed_nonce_for(&account).unwrap(), 5);
+	}
+
+	#[test]
+	fn stale_nonce_pool_error_requires_nonce_resync() {
+		let stale =
+			JsonRpcError::new(1010, "Invalid Transaction", Some("Transaction is outdated".into()));
+		let bad_proof = JsonRpcError::new(
+			1010,
+			"Invalid Transaction",
+			Some("Transaction has a bad signature".into()),
+		);
+
+		assert!(Error::from(RpcClientError::JsonRpc(stale)).requires_nonce_resync());
+		assert!(!Error::from(RpcClientError::JsonRpc(bad_proof)).requires_nonce_resync());
+	}
 }
@@ -0,0 +1,394 @@
+/*
+   Copyright 2019 Supercomputing Systems AG
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+	   http://www.apache.org/licenses/LICENSE-2.0
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+#[cfg(feature = "std")]
+use crate::api::metadata_cache::MetadataCache;
+use crate::{
+	api::{Api, api_client::decode_metadata, error::Result, rpc_capabilities::RpcCapabilities},
+	rpc::Request,
+};
+use ac_node_api::metadata::Metadata;
+use ac_primitives::{Config, ExtrinsicParams};
+use log::{debug, info};
+use sp_version::RuntimeVersion;
+
+type AdditionalParamsFor<T> = <<T as Config>::ExtrinsicParams as ExtrinsicParams<
+	<T as Config>::Index,
+	<T as Config>::Hash,
+>>::AdditionalParams;
+
+/// Builder for an [`Api`], which only fetches the items from the node that have not been set.
+///
+/// In async mode, the missing items are fetched concurrently. If the genesis hash, metadata,
+/// runtime version and rpc capabilities are all set, no request is sent to the node at all.
+/// With a [`MetadataCache`], missing metadata is only fetched if it is not cached for the
+/// runtime version of the node yet.
+pub struct ApiBuilder<T: Config, Client> {
+	client: Client,
+	genesis_hash: Option<T::Hash>,
+	metadata: Option<Metadata>,
+	runtime_version: Option<RuntimeVersion>,
+	rpc_capabilities: Option<RpcCapabilities>,
+	signer: Option<T::ExtrinsicSigner>,
+	additional_params: Option<AdditionalParamsFor<T>>,
+	#[cfg(feature = "std")]
+	metadata_cache: Option<MetadataCache>,
+}
+
+impl<T: Config, Client> ApiBuilder<T, Client> {
+	/// Create a new builder for an api using the given `client`.
+	pub fn new(client: Client) -> Self {
+		Self {
+			client,
+			genesis_hash: None,
+			metadata: None,
+			runtime_version: None,
+			rpc_capabilities: None,
+			signer: None,
+			additional_params: None,
+			#[cfg(feature = "std")]
+			metadata_cache: None,
+		}
+	}
+
+	/// Set the genesis hash of the chain instead of fetching it.
+	pub fn genesis_hash(mut self, genesis_hash: T::Hash) -> Self {
+		self.genesis_hash = Some(genesis_hash);
+		self
+	}
+
+	/// Set the metadata instead of fetching it.
+	pub fn metadata(mut self, metadata: Metadata) -> Self {
+		self.metadata = Some(metadata);
+		self
+	}
+
+	/// Set the metadata from its scale encoded bytes, prefixed with the metadata magic number,
+	/// as returned by `state_getMetadata`.
+	pub fn metadata_from_bytes(self, bytes: &[u8]) -> Result<Self> {
+		Ok(self.metadata(decode_metadata(bytes)?))
+	}
+
+	/// Set the metadata from a file containing its encoded bytes, e.g. `ksm_metadata_v14.bin`.
+	#[cfg(feature = "std")]
+	pub fn metadata_from_file(self, path: impl AsRef<std::path::Path>) -> Result<Self> {
+		let bytes = std::fs::read(path).map_err(|e| crate::Error::Other(e.into()))?;
+		self.metadata_from_bytes(&bytes)
+	}
+
+	/// Load the metadata from the `metadata_cache`, if it has been cached for the runtime version
+	/// of the node. Otherwise it is fetched and stored in the cache. The api keeps using the
+	/// cache upon runtime updates.
+	#[cfg(feature = "std")]
+	pub fn metadata_cache(mut self, metadata_cache: MetadataCache) -> Self {
+		self.metadata_cache = Some(metadata_cache);
+		self
+	}
+
+	/// Set the runtime version instead of fetching it. It must match the metadata.
+	pub fn runtime_version(mut self, runtime_version: RuntimeVersion) -> Self {
+		self.runtime_version = Some(runtime_version);
+		self
+	}
+
+	/// Set the rpc methods offered by the node instead of fetching them.
+	/// Set [`RpcCapabilities::unknown`] to use the legacy methods without asking the node.
+	pub fn rpc_capabilities(mut self, rpc_capabilities: RpcCapabilities) -> Self {
+		self.rpc_capabilities = Some(rpc_capabilities);
+		self
+	}
+
+	/// Set the api signer account.
+	pub fn signer(mut self, signer: T::ExtrinsicSigner) -> Self {
+		self.signer = Some(signer);
+		self
+	}
+
+	/// Set the additional params used for all extrinsics created by the api.
+	pub fn additional_params(mut self, additional_params: AdditionalParamsFor<T>) -> Self {
+		self.additional_params = Some(additional_params);
+		self
+	}
+}
+
+impl<T: Config, Client: Request> ApiBuilder<T, Client> {
+	/// Fetch the missing items concurrently and create the api.
+	#[maybe_async::async_impl]
+	pub async fn build(self) -> Result<Api<T, Client>> {
+		#[cfg(feature = "std")]
+		let mut builder = self.load_cached_metadata().await?;
+		#[cfg(not(feature = "std"))]
+		let mut builder = self;
+		let genesis_hash = builder.genesis_hash.take();
+		let metadata = builder.metadata.take();
+		let runtime_version = builder.runtime_version.take();
+		let rpc_capabilities = builder.rpc_capabilities.take();
+		let client = &builder.client;
+
+		let genesis_hash_future = async {
+			match genesis_hash {
+				Some(genesis_hash) => Ok(genesis_hash),
+				None => Api::<T, Client>::get_genesis_hash(client).await,
+			}
+		};
+		let metadata_future = async {
+			match metadata {
+				Some(metadata) => Ok(metadata),
+				None => Api::<T, Client>::get_metadata(client).await,
+			}
+		};
+		let runtime_version_future = async {
+			match runtime_version {
+				Some(runtime_version) => Ok(runtime_version),
+				None => Api::<T, Client>::get_runtime_version(client).await,
+			}
+		};
+		let rpc_capabilities_future = async {
+			match rpc_capabilities {
+				Some(rpc_capabilities) => rpc_capabilities,
+				None => Api::<T, Client>::get_rpc_capabilities(client).await,
+			}
+		};
+
+		let (result, rpc_capabilities) = futures_util::future::join(
+			futures_util::future::try_join3(
+				genesis_hash_future,
+				metadata_future,
+				runtime_version_future,
+			),
+			rpc_capabilities_future,
+		)
+		.await;
+		let (genesis_hash, metadata, runtime_version) = result?;
+		Ok(builder.create_api(genesis_hash, metadata, runtime_version, rpc_capabilities))
+	}
+
+	/// Fetch the missing items and create the api.
+	#[maybe_async::sync_impl]
+	pub fn build(self) -> Result<Api<T, Client>> {
+		#[cfg(feature = "std")]
+		let mut builder = self.load_cached_metadata()?;
+		#[cfg(not(feature = "std"))]
+		let mut builder = self;
+		let genesis_hash = match builder.genesis_hash.take() {
+			Some(genesis_hash) => genesis_hash,
+			None => Api::<T, Client>::get_genesis_hash(&builder.client)?,
+		};
+		let metadata = match builder.metadata.take() {
+			Some(metadata) => metadata,
+			None => Api::<T, Client>::get_metadata(&builder.client)?,
+		};
+		let runtime_version = match builder.runtime_version.take() {
+			Some(runtime_version) => runtime_version,
+			None => Api::<T, Client>::get_runtime_version(&builder.client)?,
+		};
+		let rpc_capabilities = match builder.rpc_capabilities.take() {
+			Some(rpc_capabilities) => rpc_capabilities,
+			None => Api::<T, Client>::get_rpc_capabilities(&builder.client),
+		};
+		Ok(builder.create_api(genesis_hash, metadata, runtime_version, rpc_capabilities))
+	}
+
+	/// Set the metadata from the cache, if it is missing and a cache is set. The cache is keyed
+	/// by the genesis hash and runtime version, so these are fetched first if they are missing.
+	/// If the metadata is not cached, the runtime version is replaced by the one of the block
+	/// the metadata is fetched at.
+	#[cfg(feature = "std")]
+	#[maybe_async::async_impl]
+	async fn load_cached_metadata(mut self) -> Result<Self> {
+		let (None, Some(metadata_cache)) = (&self.metadata, &self.metadata_cache) else {
+			return Ok(self)
+		};
+		let genesis_hash_future = async {
+			match self.genesis_hash {
+				Some(genesis_hash) => Ok(genesis_hash),
+				None => Api::<T, Client>::get_genesis_hash(&self.client).await,
+			}
+		};
+		let runtime_version_future = async {
+			match &self.runtime_version {
+				Some(runtime_version) => Ok(runtime_version.clone()),
+				None => Api::<T, Client>::get_runtime_version(&self.client).await,
+			}
+		};
+		let (genesis_hash, runtime_version) =
+			futures_util::future::try_join(genesis_hash_future, runtime_version_future).await?;
+		let (metadata, runtime_version) = Api::<T, Client>::get_cached_metadata(
+			&self.client,
+			metadata_cache,
+			genesis_hash,
+			&runtime_version,
+		)
+		.await?;
+		self.genesis_hash = Some(genesis_hash);
+		self.runtime_version = Some(runtime_version);
+		self.metadata = Some(metadata);
+		Ok(self)
+	}
+
+	/// Set the metadata from the cache, if it is missing and a cache is set. The cache is keyed
+	/// by the genesis hash and runtime version, so these are fetched first if they are missing.
+	/// If the metadata is not cached, the runtime version is replaced by the one of the block
+	/// the metadata is fetched at.
+	#[cfg(feature = "std")]
+	#[maybe_async::sync_impl]
+	fn load_cached_metadata(mut self) -> Result<Self> {
+		let (None, Some(metadata_cache)) = (&self.metadata, &self.metadata_cache) else {
+			return Ok(self)
+		};
+		let genesis_hash = match self.genesis_hash {
+			Some(genesis_hash) => genesis_hash,
+			None => Api::<T, Client>::get_genesis_hash(&self.client)?,
+		};
+		let runtime_version = match &self.runtime_version {
+			Some(runtime_version) => runtime_version.clone(),
+			None => Api::<T, Client>::get_runtime_version(&self.client)?,
+		};
+		let (metadata, runtime_version) = Api::<T, Client>::get_cached_metadata(
+			&self.client,
+			metadata_cache,
+			genesis_hash,
+			&runtime_version,
+		)?;
+		self.genesis_hash = Some(genesis_hash);
+		self.runtime_version = Some(runtime_version);
+		self.metadata = Some(metadata);
+		Ok(self)
+	}
+
+	/// Create the api from the fetched items and the remaining settings of the builder.
+	fn create_api(
+		self,
+		genesis_hash: T::Hash,
+		metadata: Metadata,
+		runtime_version: RuntimeVersion,
+		rpc_capabilities: RpcCapabilities,
+	) -> Api<T, Client> {
+		info!("Genesis hash: {genesis_hash:?}");
+		debug!("Metadata: {metadata:?}");
+		info!("Runtime Version: {runtime_version:?}");
+		debug!("Rpc capabilities: {rpc_capabilities:?}");
+		let mut api = Api::new_offline(genesis_hash, metadata, runtime_version, self.client);
+		api.set_rpc_capabilities(rpc_capabilities);
+		if let Some(signer) = self.signer {
+			api.set_signer(signer);
+		}
+		if let Some(additional_params) = self.additional_params {
+			api.set_additional_params(additional_params);
+		}
+		#[cfg(feature = "std")]
+		if let Some(metadata_cache) = self.metadata_cache {
+			api.set_metadata_cache(metadata_cache);
+		}
+		api
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{Error, api::api_client::KSM_V14_METADATA_PATH, rpc::mocks::RpcClientMock};
+	use ac_primitives::{
+		DefaultRuntimeConfig, GenericAdditionalParams, GenericExtrinsicParams, PlainTip,
+	};
+	use sp_core::{Bytes, H256};
+	use std::fs;
+
+	#[test]
+	fn build_fetches_missing_items() {
+		let genesis_hash = H256::repeat_byte(1);
+		let runtime_version = RuntimeVersion { spec_version: 10, ..Default::default() };
+		let encoded_metadata: Bytes = fs::read(KSM_V14_METADATA_PATH).unwrap().into();
+		let client = RpcClientMock::default()
+			.with_response("chain_getBlockHash", Some(genesis_hash))
+			.with_response("state_getRuntimeVersion", runtime_version.clone())
+			.with_response("state_getMetadata", encoded_metadata);
+
+		let additional_params = GenericAdditionalParams::new().tip(5u128);
+
+		let api = ApiBuilder::<DefaultRuntimeConfig, _>::new(client)
+			.additional_params(additional_params)
+			.build()
+			.unwrap();
+
+		assert_eq!(api.genesis_hash(), genesis_hash);
+		assert_eq!(api.runtime_version(), &runtime_version);
+		assert!(!api.rpc_capabilities().is_known());
+		let expected_params = GenericExtrinsicParams::<DefaultRuntimeConfig, PlainTip<u128>>::new(
+			runtime_version.spec_version,
+			runtime_version.transaction_version,
+			0,
+			genesis_hash,
+			additional_params,
+		);
+		assert_eq!(api.extrinsic_params(0), expected_params);
+	}
+
+	#[test]
+	fn build_without_missing_items_does_not_query_the_node() {
+		let genesis_hash = H256::repeat_byte(1);
+
+		// The mock fails all requests, as no responses are defined.
+		let api = ApiBuilder::<DefaultRuntimeConfig, _>::new(RpcClientMock::default())
+			.metadata_from_file(KSM_V14_METADATA_PATH)
+			.unwrap()
+			.genesis_hash(genesis_hash)
+			.runtime_version(RuntimeVersion::default())
+			.rpc_capabilities(RpcCapabilities::unknown())
+			.build()
+			.unwrap();
+
+		assert_eq!(api.genesis_hash(), genesis_hash);
+		assert!(api.metadata().pallet_by_name("Balances").is_some());
+	}
+
+	#[test]
+	fn build_loads_cached_metadata() {
+		let dir = std::env::temp_dir().join(format!("ac-api-builder-{}", std::process::id()));
+		let _ = fs::remove_dir_all(&dir);
+		let encoded_metadata: Bytes = fs::read(KSM_V14_METADATA_PATH).unwrap().into();
+		let node_mock = || {
+			RpcClientMock::default()
+				.with_response("chain_getBlockHash", Some(H256::repeat_byte(1)))
+				.with_response("state_getRuntimeVersion", RuntimeVersion::default())
+		};
+
+		// The first api fetches the metadata and stores it in the cache.
+		ApiBuilder::<DefaultRuntimeConfig, _>::new(
+			node_mock().with_response("state_getMetadata", encoded_metadata),
+		)
+		.metadata_cache(MetadataCache::new(&dir))
+		.build()
+		.unwrap();
+		// The second one can't fetch the metadata anymore.
+		let api = ApiBuilder::<DefaultRuntimeConfig, _>::new(node_mock())
+			.metadata_cache(MetadataCache::new(&dir))
+			.build()
+			.unwrap();
+
+		assert!(api.metadata().pallet_by_name("Balances").is_some());
+		assert_eq!(api.metadata_cache(), Some(&MetadataCache::new(&dir)));
+		let _ = fs::remove_dir_all(&dir);
+	}
+
+	#[test]
+	fn build_fails_if_a_missing_item_can_not_be_fetched() {
+		let result = ApiBuilder::<DefaultRuntimeConfig, _>::new(RpcClientMock::default())
+			.metadata_from_file(KSM_V14_METADATA_PATH)
+			.unwrap()
+			.runtime_version(RuntimeVersion::default())
+			.build();
+
+		assert!(matches!(result, Err(Error::RpcClient(_))));
+	}
+}
@@ -15,14 +15,19 @@
 
 */
 
-use crate::{ExtrinsicReport, api::UnexpectedTxStatus, rpc::Error as RpcClientError};
+use crate::{
+	ExtrinsicReport,
+	api::UnexpectedTxStatus,
+	rpc::{Error as RpcClientError, TransactionPoolError},
+};
 use ac_node_api::{
 	error::DispatchError,
 	metadata::{MetadataConversionError, MetadataError},
 };
-use alloc::{boxed::Box, vec::Vec};
+use alloc::{boxed::Box, string::String, vec::Vec};
 use codec::{Decode, Encode};
 use core::error::Error as ErrorT;
+use sp_runtime::transaction_validity::InvalidTransaction;
 
 pub type Result<T> = core::result::Result<T, Error>;
 
@@ -60,10 +65,39 @@ pub enum Error {
 	EventsMissing,
 	/// Operation wants to add events but they are already present.
 	EventsAlreadyPresent,
+	/// The rpc client does not provide the id of a subscription, which is needed to refer to it.
+	MissingSubscriptionId,
+	/// The node reported the failure of an operation of the new json-rpc specification, e.g. of
+	/// an archive storage query or runtime call.
+	OperationFailed(String),
 	/// Any custom Error.
 	Other(Box<dyn ErrorT + Send + Sync + 'static>),
 }
 
+impl Error {
+	/// Returns true if the extrinsic has been rejected or replaced because its nonce is not the
+	/// one the node expected: It has been `Usurped` or became `Invalid` while being watched, or
+	/// the transaction pool rejected it due to a stale or future nonce, or because another
+	/// extrinsic with the same nonce is already in the pool. Locally managed nonces need to be
+	/// synced with the node again in this case.
+	pub fn requires_nonce_resync(&self) -> bool {
+		match self {
+			Self::UnexpectedTxStatus(UnexpectedTxStatus::Usurped | UnexpectedTxStatus::Invalid) =>
+				true,
+			Self::RpcClient(error) => matches!(
+				error.pool_error(),
+				Some(
+					TransactionPoolError::InvalidTransaction(Some(
+						InvalidTransaction::Stale | InvalidTransaction::Future
+					)) | TransactionPoolError::TooLowPriority
+						| TransactionPoolError::RejectedFutureTransaction
+				)
+			),
+			_ => false,
+		}
+	}
+}
+
 /// Encountered unexpected tx status during watch process or the extrinsic failed.
 #[derive(Debug)]
 pub struct FailedExtrinsicError {
@@ -0,0 +1,159 @@
+/*
+   Copyright 2019 Supercomputing Systems AG
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+	   http://www.apache.org/licenses/LICENSE-2.0
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! On-disk cache of the metadata, so it does not need to be downloaded upon every start.
+
+use crate::api::{
+	api_client::decode_metadata,
+	error::{Error, Result},
+};
+use ac_node_api::metadata::Metadata;
+use codec::{Decode, Encode};
+use log::*;
+use sp_version::RuntimeVersion;
+use std::{
+	fs,
+	path::{Path, PathBuf},
+};
+
+/// Directory caching the metadata and runtime version of the nodes the `Api` connects to.
+///
+/// There is one file per chain and runtime, keyed by the genesis hash and spec version.
+/// Cached metadata is only used if the node reports exactly the runtime version it was stored
+/// with. The directory may be shared by several processes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MetadataCache {
+	dir: PathBuf,
+}
+
+impl MetadataCache {
+	/// Cache the metadata in the directory at `dir`. It is created upon the first write.
+	pub fn new(dir: impl Into<PathBuf>) -> Self {
+		Self { dir: dir.into() }
+	}
+
+	/// The directory of the cache.
+	pub fn dir(&self) -> &Path {
+		&self.dir
+	}
+
+	/// Path of the file holding the metadata of the given chain and spec version.
+	pub fn path(&self, genesis_hash: impl AsRef<[u8]>, spec_version: u32) -> PathBuf {
+		self.dir.join(format!("{}-{spec_version}.bin", hex::encode(genesis_hash)))
+	}
+
+	/// Load the metadata cached for the chain and runtime version. Returns `None` if no
+	/// metadata has been cached for it, or if the cached file can't be read.
+	pub fn load(
+		&self,
+		genesis_hash: impl AsRef<[u8]>,
+		runtime_version: &RuntimeVersion,
+	) -> Option<Metadata> {
+		let path = self.path(genesis_hash, runtime_version.spec_version);
+		let bytes = fs::read(&path).ok()?;
+		match decode_entry(&bytes, runtime_version) {
+			Ok(Some(metadata)) => {
+				debug!("Loaded metadata from {path:?}");
+				Some(metadata)
+			},
+			Ok(None) => {
+				debug!("Cached metadata at {path:?} belongs to a different runtime version");
+				None
+			},
+			Err(e) => {
+				warn!("Could not decode cached metadata at {path:?}: {e:?}");
+				None
+			},
+		}
+	}
+
+	/// Store the encoded metadata, as returned by `state_getMetadata`, for the chain and
+	/// runtime version.
+	pub fn store(
+		&self,
+		genesis_hash: impl AsRef<[u8]>,
+		runtime_version: &RuntimeVersion,
+		encoded_metadata: &[u8],
+	) -> Result<()> {
+		let path = self.path(genesis_hash, runtime_version.spec_version);
+		let entry = (runtime_version, encoded_metadata).encode();
+		fs::create_dir_all(&self.dir).map_err(io_error)?;
+		// Write to a temporary file first, so no other process reads a partially written file.
+		let temporary_path = path.with_extension(format!("{}.tmp", std::process::id()));
+		fs::write(&temporary_path, entry).map_err(io_error)?;
+		fs::rename(&temporary_path, &path).map_err(io_error)?;
+		debug!("Stored metadata at {path:?}");
+		Ok(())
+	}
+}
+
+/// Returns `None` if the entry has been stored for a different runtime version.
+fn decode_entry(bytes: &[u8], runtime_version: &RuntimeVersion) -> Result<Option<Metadata>> {
+	let (cached_runtime_version, encoded_metadata) =
+		<(RuntimeVersion, Vec<u8>)>::decode(&mut &*bytes)?;
+	if &cached_runtime_version != runtime_version {
+		return Ok(None)
+	}
+	decode_metadata(&encoded_metadata).map(Some)
+}
+
+fn io_error(e: std::io::Error) -> Error {
+	Error::Other(e.into())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::api::api_client::KSM_V14_METADATA_PATH;
+	use sp_core::H256;
+
+	fn temporary_cache(name: &str) -> MetadataCache {
+		let dir =
+			std::env::temp_dir().join(format!("ac-metadata-cache-{name}-{}", std::process::id()));
+		let _ = fs::remove_dir_all(&dir);
+		MetadataCache::new(dir)
+	}
+
+	#[test]
+	fn stored_metadata_is_loaded_for_same_runtime_version() {
+		let cache = temporary_cache("same");
+		let genesis_hash = H256::repeat_byte(1);
+		let runtime_version = RuntimeVersion { spec_version: 10, ..Default::default() };
+		let encoded_metadata = fs::read(KSM_V14_METADATA_PATH).unwrap();
+
+		assert!(cache.load(genesis_hash, &runtime_version).is_none());
+		cache.store(genesis_hash, &runtime_version, &encoded_metadata).unwrap();
+
+		let metadata = cache.load(genesis_hash, &runtime_version).unwrap();
+		assert!(metadata.pallet_by_name("Balances").is_some());
+		let _ = fs::remove_dir_all(cache.dir());
+	}
+
+	#[test]
+	fn stored_metadata_is_not_loaded_for_other_chain_or_version() {
+		let cache = temporary_cache("other");
+		let genesis_hash = H256::repeat_byte(1);
+		let runtime_version = RuntimeVersion { spec_version: 10, ..Default::default() };
+		let encoded_metadata = fs::read(KSM_V14_METADATA_PATH).unwrap();
+		cache.store(genesis_hash, &runtime_version, &encoded_metadata).unwrap();
+
+		let other_spec_version = RuntimeVersion { spec_version: 11, ..Default::default() };
+		let other_transaction_version =
+			RuntimeVersion { spec_version: 10, transaction_version: 2, ..Default::default() };
+
+		assert!(cache.load(H256::repeat_byte(2), &runtime_version).is_none());
+		assert!(cache.load(genesis_hash, &other_spec_version).is_none());
+		assert!(cache.load(genesis_hash, &other_transaction_version).is_none());
+		let _ = fs::remove_dir_all(cache.dir());
+	}
+}
@@ -23,17 +23,39 @@ use serde::{Deserialize, Serialize};
 use sp_core::Bytes;
 
 pub use api_client::Api;
+pub use builder::ApiBuilder;
 pub use error::{Error, Result};
+#[cfg(feature = "std")]
+pub use metadata_cache::MetadataCache;
+#[cfg(feature = "std")]
+pub use nonce_manager::NonceManager;
 pub use rpc_api::{
-	FetchEvents, GetAccountInformation, GetBalance, GetChainInfo, GetStorage,
-	GetTransactionPayment, SubmitAndWatch, SubmitExtrinsic, SubscribeChain, SubscribeEvents,
-	SystemApi,
+	Archive, ChainHead, FetchEvents, GetAccountInformation, GetBalance, GetChainInfo,
+	GetChainInfoBatch, GetStorage, GetStorageBatch, GetStorageWithFallback, GetTransactionPayment,
+	SubmitAndWatch, SubmitExtrinsic, SubscribeChain, SubscribeEvents, SystemApi, TransactionWatch,
 };
+pub use rpc_capabilities::{RpcCapabilities, RpcMethodFamily, RpcMethods};
+#[cfg(feature = "std")]
+pub use shared_runtime::{RuntimeUpdater, SharedRuntime};
+#[cfg(feature = "std")]
+pub use submission_queue::{ResubmitPolicy, SubmissionOutcome, SubmissionQueue};
+pub use transaction_builder::{SignedExtrinsic, TransactionBuilder};
 
 pub mod api_client;
+pub mod builder;
 pub mod error;
+#[cfg(feature = "std")]
+pub mod metadata_cache;
+#[cfg(feature = "std")]
+pub mod nonce_manager;
 pub mod rpc_api;
+pub mod rpc_capabilities;
 pub mod runtime_api;
+#[cfg(feature = "std")]
+pub mod shared_runtime;
+#[cfg(feature = "std")]
+pub mod submission_queue;
+pub mod transaction_builder;
 
 /// Extrinsic report returned upon a submit_and_watch request.
 /// Holds as much information as available.
@@ -107,6 +129,8 @@ pub enum UnexpectedTxStatus {
 	Usurped,
 	Dropped,
 	Invalid,
+	/// The node failed to process the transaction, reported by `transactionWatch_v1` only.
+	Error,
 }
 
 /// Possible transaction status events.
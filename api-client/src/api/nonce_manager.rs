@@ -0,0 +1,238 @@
+/*
+   Copyright 2019 Supercomputing Systems AG
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+	   http://www.apache.org/licenses/LICENSE-2.0
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Local bookkeeping of the nonces of the signer accounts, so several extrinsics can be
+//! submitted without asking the node for the nonce of each of them.
+
+use codec::Encode;
+use core::marker::PhantomData;
+use sp_runtime::traits::{AtLeast32Bit, One};
+use std::{
+	collections::{BTreeMap, BTreeSet},
+	sync::{Arc, Mutex, MutexGuard},
+};
+
+/// Hands out the nonces of the signer accounts, keyed by the encoded account id.
+///
+/// The nonce of an account is synced with the node once, with `system_accountNextIndex`.
+/// Afterwards, the nonces are handed out locally in increasing order. Nonces that have been
+/// handed out but not used, e.g. because composing or submitting the extrinsic failed, can be
+/// reclaimed and are handed out again before any new one.
+///
+/// The manager is shared by all clones of an api. See [`Api::get_managed_nonce`] on how it is
+/// used by the api.
+///
+/// [`Api::get_managed_nonce`]: crate::Api::get_managed_nonce
+#[derive(Debug)]
+pub struct NonceManager<AccountId, Index> {
+	accounts: Arc<Mutex<BTreeMap<Vec<u8>, AccountNonces<Index>>>>,
+	_phantom: PhantomData<AccountId>,
+}
+
+#[derive(Debug)]
+struct AccountNonces<Index> {
+	// The nonce following all nonces handed out so far.
+	next: Index,
+	// Nonces below `next` that have been handed out, but not used.
+	reclaimed: BTreeSet<Index>,
+}
+
+impl<AccountId, Index> Clone for NonceManager<AccountId, Index> {
+	fn clone(&self) -> Self {
+		Self { accounts: self.accounts.clone(), _phantom: PhantomData }
+	}
+}
+
+impl<AccountId, Index> Default for NonceManager<AccountId, Index> {
+	fn default() -> Self {
+		Self { accounts: Default::default(), _phantom: PhantomData }
+	}
+}
+
+impl<AccountId, Index> NonceManager<AccountId, Index>
+where
+	AccountId: Encode,
+	Index: AtLeast32Bit + Copy,
+{
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Returns true if the nonce of the `account` has been synced.
+	pub fn is_synced(&self, account: &AccountId) -> bool {
+		self.lock().contains_key(&account.encode())
+	}
+
+	/// Hand out the next nonce of the `account`. Reclaimed nonces are handed out first,
+	/// the lowest one first. Returns `None` if the nonce of the account has not been synced yet.
+	pub fn next_nonce(&self, account: &AccountId) -> Option<Index> {
+		let mut accounts = self.lock();
+		let nonces = accounts.get_mut(&account.encode())?;
+		Some(nonces.take_next())
+	}
+
+	/// Hand out the next nonce of the `account`. If its nonce has not been synced yet,
+	/// it is synced with `next_index` first.
+	pub fn next_nonce_or_sync(&self, account: &AccountId, next_index: Index) -> Index {
+		self.lock()
+			.entry(account.encode())
+			.or_insert_with(|| AccountNonces::new(next_index))
+			.take_next()
+	}
+
+	/// The nonce the next call to [`Self::next_nonce`] will hand out, without handing it out.
+	pub fn peek_nonce(&self, account: &AccountId) -> Option<Index> {
+		self.lock().get(&account.encode()).map(|nonces| nonces.peek_next())
+	}
+
+	/// Sync the nonce of the `account` with the `next_index` reported by the node.
+	/// All reclaimed nonces are discarded, because the node knows best which ones are used.
+	pub fn sync(&self, account: &AccountId, next_index: Index) {
+		self.lock().insert(account.encode(), AccountNonces::new(next_index));
+	}
+
+	/// Return a nonce that has been handed out, but will not be used, so it is handed out again.
+	/// Otherwise, all extrinsics with higher nonces would be stuck in the future queue of the
+	/// node. Nonces that have not been handed out are ignored.
+	pub fn reclaim(&self, account: &AccountId, nonce: Index) {
+		if let Some(nonces) = self.lock().get_mut(&account.encode()) {
+			nonces.reclaim(nonce);
+		}
+	}
+
+	/// Forget the nonce of the `account`, so it is synced again before the next one is handed out.
+	pub fn forget(&self, account: &AccountId) {
+		self.lock().remove(&account.encode());
+	}
+
+	fn lock(&self) -> MutexGuard<'_, BTreeMap<Vec<u8>, AccountNonces<Index>>> {
+		// The nonces are updated in single steps, so a panic can not leave them inconsistent.
+		self.accounts.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+	}
+}
+
+impl<Index: AtLeast32Bit + Copy> AccountNonces<Index> {
+	fn new(next: Index) -> Self {
+		Self { next, reclaimed: BTreeSet::new() }
+	}
+
+	fn take_next(&mut self) -> Index {
+		if let Some(nonce) = self.reclaimed.pop_first() {
+			return nonce
+		}
+		let nonce = self.next;
+		self.next = nonce + Index::one();
+		nonce
+	}
+
+	fn peek_next(&self) -> Index {
+		self.reclaimed.first().copied().unwrap_or(self.next)
+	}
+
+	fn reclaim(&mut self, nonce: Index) {
+		if nonce >= self.next {
+			return
+		}
+		if nonce + Index::one() != self.next {
+			self.reclaimed.insert(nonce);
+			return
+		}
+		// The gap is at the end, so the following nonces can be handed out in order again.
+		self.next = nonce;
+		while let Some(&last) = self.reclaimed.last() {
+			if last + Index::one() != self.next {
+				break
+			}
+			self.reclaimed.pop_last();
+			self.next = last;
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use sp_runtime::AccountId32;
+
+	type TestNonceManager = NonceManager<AccountId32, u32>;
+
+	#[test]
+	fn nonces_are_handed_out_in_order_per_account() {
+		let manager = TestNonceManager::new();
+		let alice = AccountId32::new([1; 32]);
+		let bob = AccountId32::new([2; 32]);
+
+		assert_eq!(manager.next_nonce(&alice), None);
+		assert_eq!(manager.next_nonce_or_sync(&alice, 5), 5);
+		assert_eq!(manager.next_nonce_or_sync(&alice, 0), 6);
+		assert_eq!(manager.next_nonce(&alice), Some(7));
+		assert_eq!(manager.next_nonce_or_sync(&bob, 0), 0);
+		assert_eq!(manager.peek_nonce(&alice), Some(8));
+		assert_eq!(manager.peek_nonce(&bob), Some(1));
+	}
+
+	#[test]
+	fn reclaimed_nonces_are_handed_out_first() {
+		let manager = TestNonceManager::new();
+		let alice = AccountId32::new([1; 32]);
+		manager.sync(&alice, 0);
+		for _ in 0..5 {
+			manager.next_nonce(&alice);
+		}
+
+		manager.reclaim(&alice, 3);
+		manager.reclaim(&alice, 1);
+		// Not handed out yet, so there is nothing to reclaim.
+		manager.reclaim(&alice, 7);
+
+		assert_eq!(manager.next_nonce(&alice), Some(1));
+		assert_eq!(manager.next_nonce(&alice), Some(3));
+		assert_eq!(manager.next_nonce(&alice), Some(5));
+	}
+
+	#[test]
+	fn reclaiming_trailing_nonces_rewinds_the_next_nonce() {
+		let manager = TestNonceManager::new();
+		let alice = AccountId32::new([1; 32]);
+		manager.sync(&alice, 0);
+		for _ in 0..5 {
+			manager.next_nonce(&alice);
+		}
+
+		manager.reclaim(&alice, 3);
+		manager.reclaim(&alice, 2);
+		manager.reclaim(&alice, 4);
+
+		assert_eq!(manager.peek_nonce(&alice), Some(2));
+		assert_eq!(manager.next_nonce(&alice), Some(2));
+		assert_eq!(manager.next_nonce(&alice), Some(3));
+		assert_eq!(manager.next_nonce(&alice), Some(4));
+		assert_eq!(manager.next_nonce(&alice), Some(5));
+	}
+
+	#[test]
+	fn sync_discards_reclaimed_nonces() {
+		let manager = TestNonceManager::new();
+		let alice = AccountId32::new([1; 32]);
+		manager.sync(&alice, 0);
+		manager.next_nonce(&alice);
+		manager.next_nonce(&alice);
+		manager.reclaim(&alice, 0);
+
+		manager.sync(&alice, 10);
+
+		assert_eq!(manager.next_nonce(&alice), Some(10));
+		manager.forget(&alice);
+		assert!(!manager.is_synced(&alice));
+	}
+}
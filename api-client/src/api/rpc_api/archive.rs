@@ -0,0 +1,449 @@
+/*
+   Copyright 2019 Supercomputing Systems AG
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+	   http://www.apache.org/licenses/LICENSE-2.0
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Interface to the `archive_v1` functions of the new json-rpc specification, which query
+//! blocks of the whole history of the chain.
+//! https://paritytech.github.io/json-rpc-interface-spec/api/archive.html
+
+use crate::{
+	Error,
+	api::{Api, Result},
+	rpc::{HandleSubscription, Request, Subscribe},
+};
+use ac_compose_macros::rpc_params;
+use ac_primitives::{
+	ArchiveCallResult, ArchiveStorageEvent, ArchiveStorageResult, BlockTrait, MaybeSend,
+	PaginatedStorageQuery, StorageQueryType, config::Config,
+};
+#[cfg(all(not(feature = "sync-api"), not(feature = "std")))]
+use alloc::boxed::Box;
+use alloc::{vec, vec::Vec};
+use codec::{Decode, Encode};
+use log::*;
+use serde::de::DeserializeOwned;
+use sp_core::Bytes;
+use sp_storage::StorageKey;
+
+pub type ArchiveStorageSubscriptionFor<Client> =
+	<Client as Subscribe>::Subscription<ArchiveStorageEvent>;
+
+#[ac_async_macros::maybe_async]
+pub trait Archive {
+	type Client: Subscribe;
+	type Hash: DeserializeOwned;
+	type BlockNumber;
+	type Header;
+	type Extrinsic;
+
+	/// Returns the hash of the genesis block.
+	async fn archive_genesis_hash(&self) -> Result<Self::Hash>;
+
+	/// Returns the number of the last finalized block.
+	async fn archive_finalized_height(&self) -> Result<Self::BlockNumber>;
+
+	/// Returns the hashes of all known blocks at `height`. Returns more than one hash if the
+	/// height is above the finalized block and there are several forks.
+	async fn archive_hash_by_height(&self, height: Self::BlockNumber) -> Result<Vec<Self::Hash>>;
+
+	/// Returns the header of the block `hash`, or `None` if the block is not known.
+	async fn archive_header(&self, hash: Self::Hash) -> Result<Option<Self::Header>>;
+
+	/// Returns the extrinsics of the block `hash`, or `None` if the block is not known.
+	async fn archive_body(&self, hash: Self::Hash) -> Result<Option<Vec<Self::Extrinsic>>>;
+
+	/// Call the runtime api `function` with the encoded `call_parameters` at the block `hash`
+	/// and decode the output. Returns `None` if the block is not known and
+	/// [`Error::OperationFailed`] if the call failed.
+	async fn archive_call<V: Decode>(
+		&self,
+		hash: Self::Hash,
+		function: &str,
+		call_parameters: Bytes,
+	) -> Result<Option<V>>;
+
+	/// Query the storage `items` of the block `hash`, optionally within a `child_trie`.
+	/// The results are reported as events of the returned subscription.
+	async fn archive_storage(
+		&self,
+		hash: Self::Hash,
+		items: Vec<PaginatedStorageQuery>,
+		child_trie: Option<StorageKey>,
+	) -> Result<ArchiveStorageSubscriptionFor<Self::Client>>;
+
+	/// Query the storage `items` of the block `hash` and collect the results.
+	/// Stops after `limit` results, if set.
+	async fn archive_storage_items(
+		&self,
+		hash: Self::Hash,
+		items: Vec<PaginatedStorageQuery>,
+		child_trie: Option<StorageKey>,
+		limit: Option<usize>,
+	) -> Result<Vec<ArchiveStorageResult>>;
+
+	/// Retrieve the storage value at the block `hash`.
+	///
+	/// The key is created with the current metadata, so the storage item must have existed
+	/// the same way at that block.
+	async fn archive_storage_value<V: Decode>(
+		&self,
+		pallet: &'static str,
+		storage_item: &'static str,
+		hash: Self::Hash,
+	) -> Result<Option<V>>;
+
+	/// Retrieve the storage value from a map for the given `map_key` at the block `hash`.
+	///
+	/// The key is created with the current metadata, so the storage item must have existed
+	/// the same way at that block.
+	async fn archive_storage_map<K: Encode + MaybeSend, V: Decode>(
+		&self,
+		pallet: &'static str,
+		storage_item: &'static str,
+		map_key: K,
+		hash: Self::Hash,
+	) -> Result<Option<V>>;
+
+	/// Retrieve the storage value of the `storage_key` at the block `hash`.
+	async fn archive_storage_by_key<V: Decode>(
+		&self,
+		storage_key: StorageKey,
+		hash: Self::Hash,
+	) -> Result<Option<V>>;
+
+	/// Retrieve up to `count` values or hashes, depending on the `query_type`, of the keys
+	/// starting with `prefix` at the block `hash`. Only keys after `start_key` are returned,
+	/// so the next page is requested with the key of the last returned item.
+	async fn archive_storage_descendants_paged(
+		&self,
+		hash: Self::Hash,
+		prefix: StorageKey,
+		query_type: StorageQueryType,
+		start_key: Option<StorageKey>,
+		count: usize,
+	) -> Result<Vec<ArchiveStorageResult>>;
+
+	/// Retrieve the merkle value of the closest descendant of `key` at the block `hash`.
+	/// It changes whenever any storage value starting with `key` changes.
+	async fn archive_closest_descendant_merkle_value(
+		&self,
+		key: StorageKey,
+		hash: Self::Hash,
+	) -> Result<Option<Bytes>>;
+}
+
+#[ac_async_macros::maybe_async]
+impl<T, Client> Archive for Api<T, Client>
+where
+	T: Config,
+	Client: Subscribe + Request,
+{
+	type Client = Client;
+	type Hash = T::Hash;
+	type BlockNumber = T::BlockNumber;
+	type Header = T::Header;
+	type Extrinsic = <T::Block as BlockTrait>::Extrinsic;
+
+	async fn archive_genesis_hash(&self) -> Result<Self::Hash> {
+		let genesis_hash = self.client().request("archive_v1_genesisHash", rpc_params![]).await?;
+		Ok(genesis_hash)
+	}
+
+	async fn archive_finalized_height(&self) -> Result<Self::BlockNumber> {
+		let height = self.client().request("archive_v1_finalizedHeight", rpc_params![]).await?;
+		Ok(height)
+	}
+
+	async fn archive_hash_by_height(&self, height: Self::BlockNumber) -> Result<Vec<Self::Hash>> {
+		let hashes = self.client().request("archive_v1_hashByHeight", rpc_params![height]).await?;
+		Ok(hashes)
+	}
+
+	async fn archive_header(&self, hash: Self::Hash) -> Result<Option<Self::Header>> {
+		self.fetch_archive_header(hash).await
+	}
+
+	async fn archive_body(&self, hash: Self::Hash) -> Result<Option<Vec<Self::Extrinsic>>> {
+		self.fetch_archive_body(hash).await
+	}
+
+	async fn archive_call<V: Decode>(
+		&self,
+		hash: Self::Hash,
+		function: &str,
+		call_parameters: Bytes,
+	) -> Result<Option<V>> {
+		let result: Option<ArchiveCallResult> = self
+			.client()
+			.request("archive_v1_call", rpc_params![hash, function, call_parameters])
+			.await?;
+		let Some(result) = result else { return Ok(None) };
+		let output = result.into_result().map_err(Error::OperationFailed)?;
+		Ok(Some(Decode::decode(&mut output.0.as_slice())?))
+	}
+
+	async fn archive_storage(
+		&self,
+		hash: Self::Hash,
+		items: Vec<PaginatedStorageQuery>,
+		child_trie: Option<StorageKey>,
+	) -> Result<ArchiveStorageSubscriptionFor<Self::Client>> {
+		debug!("querying archive storage at {hash:?}");
+		self.client()
+			.subscribe(
+				"archive_v1_storage",
+				rpc_params![hash, items, child_trie],
+				"archive_v1_stopStorage",
+			)
+			.await
+			.map_err(|e| e.into())
+	}
+
+	async fn archive_storage_items(
+		&self,
+		hash: Self::Hash,
+		items: Vec<PaginatedStorageQuery>,
+		child_trie: Option<StorageKey>,
+		limit: Option<usize>,
+	) -> Result<Vec<ArchiveStorageResult>> {
+		if limit == Some(0) {
+			return Ok(Vec::new())
+		}
+		let mut subscription = self.archive_storage(hash, items, child_trie).await?;
+		let mut results = Vec::new();
+		while let Some(event) = subscription.next().await {
+			match event? {
+				ArchiveStorageEvent::Storage(result) => {
+					results.push(result);
+					if limit.is_some_and(|limit| results.len() >= limit) {
+						subscription.unsubscribe().await?;
+						return Ok(results)
+					}
+				},
+				ArchiveStorageEvent::StorageError(e) => return Err(Error::OperationFailed(e.error)),
+				ArchiveStorageEvent::StorageDone => return Ok(results),
+			}
+		}
+		Err(Error::NoStream)
+	}
+
+	async fn archive_storage_value<V: Decode>(
+		&self,
+		pallet: &'static str,
+		storage_item: &'static str,
+		hash: Self::Hash,
+	) -> Result<Option<V>> {
+		let storage_key = self.metadata_snapshot().storage_value_key(pallet, storage_item)?;
+		self.archive_storage_by_key(storage_key, hash).await
+	}
+
+	async fn archive_storage_map<K: Encode + MaybeSend, V: Decode>(
+		&self,
+		pallet: &'static str,
+		storage_item: &'static str,
+		map_key: K,
+		hash: Self::Hash,
+	) -> Result<Option<V>> {
+		let storage_key =
+			self.metadata_snapshot().storage_map_key::<K>(pallet, storage_item, map_key)?;
+		self.archive_storage_by_key(storage_key, hash).await
+	}
+
+	async fn archive_storage_by_key<V: Decode>(
+		&self,
+		storage_key: StorageKey,
+		hash: Self::Hash,
+	) -> Result<Option<V>> {
+		let query = PaginatedStorageQuery::new(storage_key, StorageQueryType::Value);
+		let results = self.archive_storage_items(hash, vec![query], None, Some(1)).await?;
+		match results.first() {
+			Some(result) => Ok(result.item.decode_value()?),
+			None => Ok(None),
+		}
+	}
+
+	async fn archive_storage_descendants_paged(
+		&self,
+		hash: Self::Hash,
+		prefix: StorageKey,
+		query_type: StorageQueryType,
+		start_key: Option<StorageKey>,
+		count: usize,
+	) -> Result<Vec<ArchiveStorageResult>> {
+		let mut query = PaginatedStorageQuery::new(prefix, query_type);
+		query.pagination_start_key = start_key;
+		self.archive_storage_items(hash, vec![query], None, Some(count)).await
+	}
+
+	async fn archive_closest_descendant_merkle_value(
+		&self,
+		key: StorageKey,
+		hash: Self::Hash,
+	) -> Result<Option<Bytes>> {
+		let query = PaginatedStorageQuery::new(key, StorageQueryType::ClosestDescendantMerkleValue);
+		let results = self.archive_storage_items(hash, vec![query], None, Some(1)).await?;
+		Ok(results
+			.into_iter()
+			.next()
+			.and_then(|result| result.item.closest_descendant_merkle_value))
+	}
+}
+
+/// Requests shared by the [`Archive`] implementation and the fallbacks of the legacy methods
+/// for nodes that don't offer them.
+impl<T, Client> Api<T, Client>
+where
+	T: Config,
+	Client: Request,
+{
+	/// Get the hash of the block `number` or of the last finalized block, if `None`.
+	/// If there are several forks at `number`, the first reported block is taken.
+	#[maybe_async::maybe_async(?Send)]
+	pub(crate) async fn archive_block_hash(
+		&self,
+		number: Option<T::BlockNumber>,
+	) -> Result<Option<T::Hash>> {
+		let number: T::BlockNumber = match number {
+			Some(number) => number,
+			None => self.client().request("archive_v1_finalizedHeight", rpc_params![]).await?,
+		};
+		let hashes: Vec<T::Hash> =
+			self.client().request("archive_v1_hashByHeight", rpc_params![number]).await?;
+		Ok(hashes.into_iter().next())
+	}
+
+	/// Returns `hash`, or the hash of the last finalized block if `None`.
+	#[maybe_async::maybe_async(?Send)]
+	pub(crate) async fn archive_hash_or_finalized(
+		&self,
+		hash: Option<T::Hash>,
+	) -> Result<Option<T::Hash>> {
+		match hash {
+			Some(hash) => Ok(Some(hash)),
+			None => self.archive_block_hash(None).await,
+		}
+	}
+
+	/// Fetch and decode the header of the block `hash`.
+	#[maybe_async::maybe_async(?Send)]
+	pub(crate) async fn fetch_archive_header(&self, hash: T::Hash) -> Result<Option<T::Header>> {
+		let header: Option<Bytes> =
+			self.client().request("archive_v1_header", rpc_params![hash]).await?;
+		match header {
+			Some(header) => Ok(Some(Decode::decode(&mut header.0.as_slice())?)),
+			None => Ok(None),
+		}
+	}
+
+	/// Fetch and decode the extrinsics of the block `hash`.
+	#[maybe_async::maybe_async(?Send)]
+	pub(crate) async fn fetch_archive_body(
+		&self,
+		hash: T::Hash,
+	) -> Result<Option<Vec<<T::Block as BlockTrait>::Extrinsic>>> {
+		let body: Option<Vec<Bytes>> =
+			self.client().request("archive_v1_body", rpc_params![hash]).await?;
+		let Some(body) = body else { return Ok(None) };
+		let extrinsics = body
+			.iter()
+			.map(|extrinsic| Decode::decode(&mut extrinsic.0.as_slice()))
+			.collect::<core::result::Result<_, _>>()?;
+		Ok(Some(extrinsics))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::rpc::mocks::{RpcClientMock, create_mock_api};
+	use ac_primitives::StorageResultItem;
+	use serde_json::json;
+	use sp_core::H256;
+
+	fn storage_event(key: u8, value: u32) -> ArchiveStorageEvent {
+		ArchiveStorageEvent::Storage(ArchiveStorageResult {
+			item: StorageResultItem {
+				key: StorageKey(vec![key]),
+				value: Some(Bytes(value.encode())),
+				hash: None,
+				closest_descendant_merkle_value: None,
+			},
+			child_trie_key: None,
+		})
+	}
+
+	#[test]
+	fn storage_value_is_decoded() {
+		let api = create_mock_api(RpcClientMock::default().with_subscription(
+			"archive_v1_storage",
+			vec![storage_event(1, 42), ArchiveStorageEvent::StorageDone],
+		));
+
+		let value: Option<u32> =
+			api.archive_storage_value("Timestamp", "Now", H256::repeat_byte(1)).unwrap();
+
+		assert_eq!(value, Some(42));
+	}
+
+	#[test]
+	fn descendants_are_paged() {
+		let hash = H256::repeat_byte(1);
+		let events = vec![storage_event(1, 1), storage_event(2, 2), storage_event(3, 3)];
+		let query =
+			PaginatedStorageQuery::new(StorageKey(vec![]), StorageQueryType::DescendantsValues)
+				.with_pagination_start_key(StorageKey(vec![0]));
+		let client = RpcClientMock::default().with_subscription_for(
+			"archive_v1_storage",
+			rpc_params![hash, vec![query], Option::<StorageKey>::None],
+			events,
+		);
+		let api = create_mock_api(client);
+
+		let page = api
+			.archive_storage_descendants_paged(
+				hash,
+				StorageKey(vec![]),
+				StorageQueryType::DescendantsValues,
+				Some(StorageKey(vec![0])),
+				2,
+			)
+			.unwrap();
+
+		let keys: Vec<_> = page.iter().map(|result| result.item.key.clone()).collect();
+		assert_eq!(keys, vec![StorageKey(vec![1]), StorageKey(vec![2])]);
+	}
+
+	#[test]
+	fn storage_error_is_returned() {
+		let events = vec![ArchiveStorageEvent::StorageError(ac_primitives::ArchiveStorageError {
+			error: "Block not found".into(),
+		})];
+		let api = create_mock_api(
+			RpcClientMock::default().with_subscription("archive_v1_storage", events),
+		);
+
+		let result = api.archive_storage_by_key::<u32>(StorageKey(vec![1]), H256::zero());
+
+		assert!(matches!(result, Err(Error::OperationFailed(e)) if e == "Block not found"));
+	}
+
+	#[test]
+	fn failed_call_returns_error() {
+		let client = RpcClientMock::default()
+			.with_response("archive_v1_call", json!({ "success": false, "error": "Wasm trap" }));
+		let api = create_mock_api(client);
+
+		let result = api.archive_call::<u32>(H256::zero(), "Core_version", Bytes(vec![]));
+
+		assert!(matches!(result, Err(Error::OperationFailed(e)) if e == "Wasm trap"));
+	}
+}
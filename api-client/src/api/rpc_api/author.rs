@@ -15,11 +15,14 @@
 
 use crate::{
 	Api, ExtrinsicReport, TransactionStatus, XtStatus,
-	api::{Error, Result, rpc_api::events::FetchEvents},
+	api::{
+		Error, Result,
+		rpc_api::{events::FetchEvents, transaction::TransactionWatch},
+	},
 	rpc::{HandleSubscription, Request, Subscribe},
 };
 use ac_compose_macros::rpc_params;
-use ac_primitives::{UncheckedExtrinsic, config::Config};
+use ac_primitives::{MaybeSend, UncheckedExtrinsic, config::Config};
 #[cfg(all(not(feature = "sync-api"), not(feature = "std")))]
 use alloc::boxed::Box;
 use codec::{Decode, Encode};
@@ -32,7 +35,7 @@ pub type TransactionSubscriptionFor<Client, Hash> =
 	<Client as Subscribe>::Subscription<TransactionStatus<Hash, Hash>>;
 
 /// Simple extrinsic submission without any subscription.
-#[maybe_async::maybe_async(?Send)]
+#[ac_async_macros::maybe_async]
 pub trait SubmitExtrinsic {
 	type Hash;
 
@@ -43,17 +46,17 @@ pub trait SubmitExtrinsic {
 		extrinsic: UncheckedExtrinsic<Address, Call, Signature, TransactionExtension>,
 	) -> Result<Self::Hash>
 	where
-		Address: Encode,
-		Call: Encode,
-		Signature: Encode,
-		TransactionExtension: Encode;
+		Address: Encode + MaybeSend,
+		Call: Encode + MaybeSend,
+		Signature: Encode + MaybeSend,
+		TransactionExtension: Encode + MaybeSend;
 
 	/// Submit an encoded, opaque extrinsic to the substrate node.
 	/// Returns the extrinsic hash.
 	async fn submit_opaque_extrinsic(&self, encoded_extrinsic: &Bytes) -> Result<Self::Hash>;
 }
 
-#[maybe_async::maybe_async(?Send)]
+#[ac_async_macros::maybe_async]
 impl<T, Client> SubmitExtrinsic for Api<T, Client>
 where
 	T: Config,
@@ -66,10 +69,10 @@ where
 		extrinsic: UncheckedExtrinsic<Address, Call, Signature, TransactionExtension>,
 	) -> Result<Self::Hash>
 	where
-		Address: Encode,
-		Call: Encode,
-		Signature: Encode,
-		TransactionExtension: Encode,
+		Address: Encode + MaybeSend,
+		Call: Encode + MaybeSend,
+		Signature: Encode + MaybeSend,
+		TransactionExtension: Encode + MaybeSend,
 	{
 		self.submit_opaque_extrinsic(&extrinsic.encode().into()).await
 	}
@@ -82,10 +85,15 @@ where
 	}
 }
 
-#[maybe_async::maybe_async(?Send)]
+/// Submit extrinsics and watch their progress.
+///
+/// The `*_until*` methods of the `Api` use the legacy `author_submitAndWatchExtrinsic` as long as
+/// the node offers it, see [`RpcCapabilities`](crate::RpcCapabilities). Otherwise they fall back
+/// to `transactionWatch_v1_submitAndWatch`, as [`TransactionWatch`] does.
+#[ac_async_macros::maybe_async]
 pub trait SubmitAndWatch {
 	type Client: Subscribe;
-	type Hash: DeserializeOwned + Decode + Encode;
+	type Hash: DeserializeOwned + Decode + Encode + MaybeSend;
 
 	/// Submit an extrinsic an return a Subscription
 	/// to watch the extrinsic progress.
@@ -96,10 +104,10 @@ pub trait SubmitAndWatch {
 		extrinsic: UncheckedExtrinsic<Address, Call, Signature, TransactionExtension>,
 	) -> Result<TransactionSubscriptionFor<Self::Client, Self::Hash>>
 	where
-		Address: Encode,
-		Call: Encode,
-		Signature: Encode,
-		TransactionExtension: Encode;
+		Address: Encode + MaybeSend,
+		Call: Encode + MaybeSend,
+		Signature: Encode + MaybeSend,
+		TransactionExtension: Encode + MaybeSend;
 
 	/// Submit an encoded, opaque extrinsic an return a Subscription to
 	/// watch the extrinsic progress.
@@ -136,10 +144,10 @@ pub trait SubmitAndWatch {
 		watch_until: XtStatus,
 	) -> Result<ExtrinsicReport<Self::Hash>>
 	where
-		Address: Encode,
-		Call: Encode,
-		Signature: Encode,
-		TransactionExtension: Encode;
+		Address: Encode + MaybeSend,
+		Call: Encode + MaybeSend,
+		Signature: Encode + MaybeSend,
+		TransactionExtension: Encode + MaybeSend;
 
 	/// Submit an encoded, opaque extrinsic until the desired status
 	/// is reached, if no error is encountered previously.
@@ -189,10 +197,10 @@ pub trait SubmitAndWatch {
 		watch_until: XtStatus,
 	) -> Result<ExtrinsicReport<Self::Hash>>
 	where
-		Address: Encode,
-		Call: Encode,
-		Signature: Encode,
-		TransactionExtension: Encode;
+		Address: Encode + MaybeSend,
+		Call: Encode + MaybeSend,
+		Signature: Encode + MaybeSend,
+		TransactionExtension: Encode + MaybeSend;
 
 	/// Submit an encoded, opaque extrinsic and watch it until the desired status
 	/// is reached, if no error is encountered previously.
@@ -218,7 +226,7 @@ pub trait SubmitAndWatch {
 	async fn populate_events(&self, report: &mut ExtrinsicReport<Self::Hash>) -> Result<()>;
 }
 
-#[maybe_async::maybe_async(?Send)]
+#[ac_async_macros::maybe_async]
 impl<T, Client> SubmitAndWatch for Api<T, Client>
 where
 	T: Config,
@@ -232,10 +240,10 @@ where
 		extrinsic: UncheckedExtrinsic<Address, Call, Signature, TransactionExtension>,
 	) -> Result<TransactionSubscriptionFor<Self::Client, Self::Hash>>
 	where
-		Address: Encode,
-		Call: Encode,
-		Signature: Encode,
-		TransactionExtension: Encode,
+		Address: Encode + MaybeSend,
+		Call: Encode + MaybeSend,
+		Signature: Encode + MaybeSend,
+		TransactionExtension: Encode + MaybeSend,
 	{
 		self.submit_and_watch_opaque_extrinsic(&extrinsic.encode().into()).await
 	}
@@ -260,10 +268,10 @@ where
 		watch_until: XtStatus,
 	) -> Result<ExtrinsicReport<Self::Hash>>
 	where
-		Address: Encode,
-		Call: Encode,
-		Signature: Encode,
-		TransactionExtension: Encode,
+		Address: Encode + MaybeSend,
+		Call: Encode + MaybeSend,
+		Signature: Encode + MaybeSend,
+		TransactionExtension: Encode + MaybeSend,
 	{
 		self.submit_and_watch_opaque_extrinsic_until(&extrinsic.encode().into(), watch_until)
 			.await
@@ -282,7 +290,7 @@ where
 			return Ok(report)
 		}
 		self.populate_events(&mut report).await?;
-		report.check_events_for_dispatch_error(self.metadata())?;
+		report.check_events_for_dispatch_error(&self.metadata_snapshot())?;
 		Ok(report)
 	}
 
@@ -308,10 +316,10 @@ where
 		watch_until: XtStatus,
 	) -> Result<ExtrinsicReport<Self::Hash>>
 	where
-		Address: Encode,
-		Call: Encode,
-		Signature: Encode,
-		TransactionExtension: Encode,
+		Address: Encode + MaybeSend,
+		Call: Encode + MaybeSend,
+		Signature: Encode + MaybeSend,
+		TransactionExtension: Encode + MaybeSend,
 	{
 		self.submit_and_watch_opaque_extrinsic_until_without_events(
 			&extrinsic.encode().into(),
@@ -325,6 +333,17 @@ where
 		encoded_extrinsic: &Bytes,
 		watch_until: XtStatus,
 	) -> Result<ExtrinsicReport<Self::Hash>> {
+		if !self
+			.rpc_capabilities()
+			.use_legacy("author_submitAndWatchExtrinsic", "transactionWatch_v1_submitAndWatch")
+		{
+			return self
+				.transaction_submit_and_watch_opaque_until_without_events(
+					encoded_extrinsic,
+					watch_until,
+				)
+				.await
+		}
 		let tx_hash = T::Hasher::hash(encoded_extrinsic);
 		let mut subscription: TransactionSubscriptionFor<Self::Client, Self::Hash> =
 			self.submit_and_watch_opaque_extrinsic(encoded_extrinsic).await?;
@@ -14,10 +14,10 @@
 use crate::{
 	Error,
 	api::{Api, Result},
-	rpc::{Request, Subscribe},
+	rpc::{BatchRequest, Request, Subscribe},
 };
 use ac_compose_macros::rpc_params;
-use ac_primitives::config::Config;
+use ac_primitives::{BlockTrait, config::Config};
 #[cfg(all(not(feature = "sync-api"), not(feature = "std")))]
 use alloc::boxed::Box;
 use alloc::vec::Vec;
@@ -25,7 +25,13 @@ use log::*;
 use serde::de::DeserializeOwned;
 use sp_runtime::generic::SignedBlock;
 
-#[maybe_async::maybe_async(?Send)]
+/// Retrieve blocks and headers of the chain.
+///
+/// The implementation of the `Api` uses the legacy `chain_*` methods as long as the node offers
+/// them, see [`RpcCapabilities`](crate::RpcCapabilities). Otherwise it falls back to the
+/// `archive_v1_*` methods. These don't know about the best block, so the last finalized block is
+/// taken instead if no block is specified. Blocks fetched this way have no justifications.
+#[ac_async_macros::maybe_async]
 pub trait GetChainInfo {
 	type BlockNumber;
 	type Hash;
@@ -74,7 +80,7 @@ pub trait GetChainInfo {
 	) -> Result<Vec<SignedBlock<Self::Block>>>;
 }
 
-#[maybe_async::maybe_async(?Send)]
+#[ac_async_macros::maybe_async]
 impl<T, Client> GetChainInfo for Api<T, Client>
 where
 	T: Config,
@@ -86,12 +92,21 @@ where
 	type Block = T::Block;
 
 	async fn get_finalized_head(&self) -> Result<Option<Self::Hash>> {
+		if !self
+			.rpc_capabilities()
+			.use_legacy("chain_getFinalizedHead", "archive_v1_finalizedHeight")
+		{
+			return self.archive_block_hash(None).await
+		}
 		let finalized_block_hash =
 			self.client().request("chain_getFinalizedHead", rpc_params![]).await?;
 		Ok(finalized_block_hash)
 	}
 
 	async fn get_header(&self, hash: Option<Self::Hash>) -> Result<Option<Self::Header>> {
+		if !self.rpc_capabilities().use_legacy("chain_getHeader", "archive_v1_header") {
+			return self.get_archive_header(hash).await
+		}
 		let block_hash = self.client().request("chain_getHeader", rpc_params![hash]).await?;
 		Ok(block_hash)
 	}
@@ -100,6 +115,12 @@ where
 		&self,
 		number: Option<Self::BlockNumber>,
 	) -> Result<Option<Self::Hash>> {
+		if !self
+			.rpc_capabilities()
+			.use_legacy("chain_getBlockHash", "archive_v1_hashByHeight")
+		{
+			return self.archive_block_hash(number).await
+		}
 		let block_hash = self.client().request("chain_getBlockHash", rpc_params![number]).await?;
 		Ok(block_hash)
 	}
@@ -125,6 +146,9 @@ where
 		&self,
 		hash: Option<Self::Hash>,
 	) -> Result<Option<SignedBlock<Self::Block>>> {
+		if !self.rpc_capabilities().use_legacy("chain_getBlock", "archive_v1_body") {
+			return self.get_archive_signed_block(hash).await
+		}
 		let block = self.client().request("chain_getBlock", rpc_params![hash]).await?;
 		Ok(block)
 	}
@@ -158,7 +182,98 @@ where
 		Ok(blocks)
 	}
 }
-#[maybe_async::maybe_async(?Send)]
+
+/// Retrieve several blocks of the chain with json-rpc batch requests.
+#[ac_async_macros::maybe_async]
+pub trait GetChainInfoBatch {
+	type BlockNumber;
+	type Block;
+
+	/// Same as [`GetChainInfo::get_signed_blocks`], but the block hashes and then the blocks are
+	/// fetched with one batch request each. Blocks that are not found are skipped.
+	/// If fetching any of the block fails then a `Result::Err` will be returned.
+	async fn get_signed_blocks_batched(
+		&self,
+		block_numbers: &[Self::BlockNumber],
+	) -> Result<Vec<SignedBlock<Self::Block>>>;
+}
+
+#[ac_async_macros::maybe_async]
+impl<T, Client> GetChainInfoBatch for Api<T, Client>
+where
+	T: Config,
+	Client: Request + BatchRequest,
+{
+	type BlockNumber = T::BlockNumber;
+	type Block = T::Block;
+
+	async fn get_signed_blocks_batched(
+		&self,
+		block_numbers: &[Self::BlockNumber],
+	) -> Result<Vec<SignedBlock<Self::Block>>> {
+		let rpc_capabilities = self.rpc_capabilities();
+		if !rpc_capabilities.use_legacy("chain_getBlockHash", "archive_v1_hashByHeight")
+			|| !rpc_capabilities.use_legacy("chain_getBlock", "archive_v1_body")
+		{
+			return self.get_signed_blocks(block_numbers).await
+		}
+
+		// An empty batch is not a valid json-rpc request.
+		if block_numbers.is_empty() {
+			return Ok(Vec::new())
+		}
+		let requests = block_numbers
+			.iter()
+			.map(|number| ("chain_getBlockHash", rpc_params![Some(*number)]))
+			.collect();
+		let hashes = self.client().batch_request::<Option<T::Hash>>(requests).await?;
+		let requests = hashes
+			.into_iter()
+			.filter_map(|hash| hash.transpose())
+			.map(|hash| Ok(("chain_getBlock", rpc_params![Some(hash?)])))
+			.collect::<Result<Vec<_>>>()?;
+
+		if requests.is_empty() {
+			return Ok(Vec::new())
+		}
+		let blocks = self
+			.client()
+			.batch_request::<Option<SignedBlock<Self::Block>>>(requests)
+			.await?;
+		blocks
+			.into_iter()
+			.filter_map(|block| block.transpose())
+			.map(|block| Ok(block?))
+			.collect()
+	}
+}
+
+/// Fallback to the `archive_v1` methods for nodes that don't offer the legacy `chain_*` methods.
+impl<T, Client> Api<T, Client>
+where
+	T: Config,
+	Client: Request,
+{
+	#[maybe_async::maybe_async(?Send)]
+	async fn get_archive_header(&self, hash: Option<T::Hash>) -> Result<Option<T::Header>> {
+		let Some(hash) = self.archive_hash_or_finalized(hash).await? else { return Ok(None) };
+		self.fetch_archive_header(hash).await
+	}
+
+	#[maybe_async::maybe_async(?Send)]
+	async fn get_archive_signed_block(
+		&self,
+		hash: Option<T::Hash>,
+	) -> Result<Option<SignedBlock<T::Block>>> {
+		let Some(hash) = self.archive_hash_or_finalized(hash).await? else { return Ok(None) };
+		let header = self.fetch_archive_header(hash).await?;
+		let body = self.fetch_archive_body(hash).await?;
+		let (Some(header), Some(extrinsics)) = (header, body) else { return Ok(None) };
+		Ok(Some(SignedBlock { block: T::Block::new(header, extrinsics), justifications: None }))
+	}
+}
+
+#[ac_async_macros::maybe_async]
 pub trait SubscribeChain {
 	type Client: Subscribe;
 	type Header: DeserializeOwned;
@@ -168,7 +283,7 @@ pub trait SubscribeChain {
 	) -> Result<<Self::Client as Subscribe>::Subscription<Self::Header>>;
 }
 
-#[maybe_async::maybe_async(?Send)]
+#[ac_async_macros::maybe_async]
 impl<T, Client> SubscribeChain for Api<T, Client>
 where
 	T: Config,
@@ -191,3 +306,129 @@ where
 			.map_err(|e| e.into())
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		RpcCapabilities,
+		rpc::mocks::{RpcClientMock, create_mock_api},
+	};
+	use ac_primitives::{DefaultRuntimeConfig, HeaderTrait, MaybeSend, RpcParams};
+	use codec::Encode;
+	use core::{
+		fmt::Debug,
+		sync::atomic::{AtomicUsize, Ordering},
+	};
+	use sp_core::{Bytes, H256};
+
+	type Header = <DefaultRuntimeConfig as Config>::Header;
+	type Block = <DefaultRuntimeConfig as Config>::Block;
+
+	/// Client that only answers batch requests, to make sure no single requests are sent.
+	#[derive(Default)]
+	struct BatchOnlyClient {
+		mock: RpcClientMock,
+		batches: AtomicUsize,
+	}
+
+	impl Request for BatchOnlyClient {
+		fn request<R: DeserializeOwned + MaybeSend>(
+			&self,
+			method: &str,
+			_params: RpcParams,
+		) -> crate::rpc::Result<R> {
+			panic!("Unexpected single request {method}")
+		}
+	}
+
+	impl BatchRequest for BatchOnlyClient {
+		fn batch_request<R: DeserializeOwned + Debug + MaybeSend>(
+			&self,
+			requests: Vec<(&str, RpcParams)>,
+		) -> crate::rpc::Result<Vec<crate::rpc::Result<R>>> {
+			self.batches.fetch_add(1, Ordering::Relaxed);
+			self.mock.batch_request(requests)
+		}
+	}
+
+	fn signed_block(number: u32) -> SignedBlock<Block> {
+		let header = Header::new(
+			number,
+			Default::default(),
+			Default::default(),
+			Default::default(),
+			Default::default(),
+		);
+		SignedBlock { block: Block::new(header, Vec::new()), justifications: None }
+	}
+
+	#[test]
+	fn get_block_uses_archive_without_legacy_methods() {
+		let hash = H256::random();
+		let header = Header::new(
+			10,
+			Default::default(),
+			Default::default(),
+			Default::default(),
+			Default::default(),
+		);
+		let client = RpcClientMock::default()
+			.with_response("archive_v1_finalizedHeight", 10u32)
+			.with_response_for("archive_v1_hashByHeight", rpc_params![10u32], vec![hash])
+			.with_response_for("archive_v1_header", rpc_params![hash], Some(Bytes(header.encode())))
+			.with_response_for("archive_v1_body", rpc_params![hash], Vec::<Bytes>::new());
+		let mut api = create_mock_api(client);
+		api.set_rpc_capabilities(RpcCapabilities::new([
+			"archive_v1_body",
+			"archive_v1_finalizedHeight",
+			"archive_v1_hashByHeight",
+			"archive_v1_header",
+		]));
+
+		let block = api.get_block(None).unwrap().unwrap();
+
+		assert_eq!(block.header(), &header);
+		assert!(block.extrinsics().is_empty());
+		assert_eq!(api.get_finalized_head().unwrap(), Some(hash));
+	}
+
+	#[test]
+	fn get_block_hash_prefers_legacy_methods() {
+		let hash = H256::random();
+		let client = RpcClientMock::default().with_response("chain_getBlockHash", Some(hash));
+		let mut api = create_mock_api(client);
+		api.set_rpc_capabilities(RpcCapabilities::new([
+			"archive_v1_hashByHeight",
+			"chain_getBlockHash",
+		]));
+
+		assert_eq!(api.get_block_hash(Some(1)).unwrap(), Some(hash));
+	}
+
+	#[test]
+	fn get_signed_blocks_batched_sends_two_batches() {
+		let (hash1, hash2) = (H256::repeat_byte(1), H256::repeat_byte(2));
+		let mock = RpcClientMock::default()
+			.with_response_for("chain_getBlockHash", rpc_params![Some(1u32)], Some(hash1))
+			.with_response_for("chain_getBlockHash", rpc_params![Some(2u32)], Some(hash2))
+			.with_response_for("chain_getBlockHash", rpc_params![Some(3u32)], None::<H256>)
+			.with_response_for("chain_getBlock", rpc_params![Some(hash1)], Some(signed_block(1)))
+			.with_response_for("chain_getBlock", rpc_params![Some(hash2)], Some(signed_block(2)));
+		let api = create_mock_api(BatchOnlyClient { mock, ..Default::default() });
+
+		let blocks = api.get_signed_blocks_batched(&[1, 2, 3]).unwrap();
+
+		// Block 3 does not exist yet and is skipped.
+		assert_eq!(blocks, vec![signed_block(1), signed_block(2)]);
+		assert_eq!(api.client().batches.load(Ordering::Relaxed), 2);
+	}
+
+	#[test]
+	fn get_signed_blocks_batched_without_block_numbers_sends_no_request() {
+		let api = create_mock_api(BatchOnlyClient::default());
+
+		assert!(api.get_signed_blocks_batched(&[]).unwrap().is_empty());
+		assert_eq!(api.client().batches.load(Ordering::Relaxed), 0);
+	}
+}
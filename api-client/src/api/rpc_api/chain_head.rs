@@ -0,0 +1,324 @@
+/*
+   Copyright 2019 Supercomputing Systems AG
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+	   http://www.apache.org/licenses/LICENSE-2.0
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Interface to the `chainHead_v1` functions of the new json-rpc specification:
+//! https://paritytech.github.io/json-rpc-interface-spec/api/chainHead.html
+//!
+//! All functions refer to a follow subscription, which pins the reported blocks until they
+//! are unpinned. The results of the body, storage and call operations are reported as events
+//! of the follow subscription.
+
+use crate::{
+	Error,
+	api::{Api, Result},
+	rpc::{HandleSubscription, Request, Subscribe},
+};
+use ac_compose_macros::rpc_params;
+use ac_primitives::{FollowEvent, MethodResponse, StorageQuery, config::Config};
+#[cfg(all(not(feature = "sync-api"), not(feature = "std")))]
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use codec::Decode;
+use log::*;
+use serde::de::DeserializeOwned;
+use sp_core::Bytes;
+use sp_storage::StorageKey;
+
+pub type FollowSubscriptionFor<Client, Hash> =
+	<Client as Subscribe>::Subscription<FollowEvent<Hash>>;
+
+#[ac_async_macros::maybe_async]
+pub trait ChainHead {
+	type Client: Subscribe;
+	type Hash: DeserializeOwned;
+	type Header;
+
+	/// Follow the blocks of the chain, starting with the current finalized block.
+	///
+	/// The id of the returned subscription, available via
+	/// [`HandleSubscription::subscription_id`], is needed by all other functions.
+	/// If `with_runtime` is set, the runtime of the blocks is reported as well. This allows to
+	/// update the metadata once a new runtime is reported.
+	/// Fails with [`Error::MissingSubscriptionId`] if the rpc client does not provide the id.
+	async fn chain_head_follow(
+		&self,
+		with_runtime: bool,
+	) -> Result<FollowSubscriptionFor<Self::Client, Self::Hash>>;
+
+	/// Returns the header of the pinned block `hash`. Returns `None` if the follow subscription
+	/// is not active anymore.
+	async fn chain_head_header(
+		&self,
+		follow_subscription: &str,
+		hash: Self::Hash,
+	) -> Result<Option<Self::Header>>;
+
+	/// Fetch the body of the pinned block `hash`. The extrinsics are reported with the
+	/// `OperationBodyDone` event.
+	async fn chain_head_body(
+		&self,
+		follow_subscription: &str,
+		hash: Self::Hash,
+	) -> Result<MethodResponse>;
+
+	/// Query the storage `items` of the pinned block `hash`, optionally within a `child_trie`.
+	/// The results are reported with `OperationStorageItems` events, followed by an
+	/// `OperationStorageDone` event.
+	///
+	/// The keys can be created with the metadata, e.g. `api.metadata().storage_map_key(..)`.
+	async fn chain_head_storage(
+		&self,
+		follow_subscription: &str,
+		hash: Self::Hash,
+		items: Vec<StorageQuery>,
+		child_trie: Option<StorageKey>,
+	) -> Result<MethodResponse>;
+
+	/// Call the runtime api `function` with the encoded `call_parameters` at the pinned block
+	/// `hash`. The output is reported with the `OperationCallDone` event.
+	async fn chain_head_call(
+		&self,
+		follow_subscription: &str,
+		hash: Self::Hash,
+		function: &str,
+		call_parameters: Bytes,
+	) -> Result<MethodResponse>;
+
+	/// Resume a storage operation, which waits after an `OperationWaitingForContinue` event.
+	async fn chain_head_continue(
+		&self,
+		follow_subscription: &str,
+		operation_id: &str,
+	) -> Result<()>;
+
+	/// Stop an ongoing operation. No further events are reported for it.
+	async fn chain_head_stop_operation(
+		&self,
+		follow_subscription: &str,
+		operation_id: &str,
+	) -> Result<()>;
+
+	/// Unpin the blocks `hashes`, which allows the node to discard them. Every reported block
+	/// must be unpinned once it is not needed anymore, otherwise the node stops the
+	/// subscription.
+	async fn chain_head_unpin(
+		&self,
+		follow_subscription: &str,
+		hashes: Vec<Self::Hash>,
+	) -> Result<()>;
+}
+
+#[ac_async_macros::maybe_async]
+impl<T, Client> ChainHead for Api<T, Client>
+where
+	T: Config,
+	Client: Subscribe + Request,
+{
+	type Client = Client;
+	type Hash = T::Hash;
+	type Header = T::Header;
+
+	async fn chain_head_follow(
+		&self,
+		with_runtime: bool,
+	) -> Result<FollowSubscriptionFor<Self::Client, Self::Hash>> {
+		debug!("following the chain head");
+		let subscription: FollowSubscriptionFor<Self::Client, Self::Hash> = self
+			.client()
+			.subscribe("chainHead_v1_follow", rpc_params![with_runtime], "chainHead_v1_unfollow")
+			.await?;
+		if subscription.subscription_id().is_none() {
+			subscription.unsubscribe().await?;
+			return Err(Error::MissingSubscriptionId)
+		}
+		Ok(subscription)
+	}
+
+	async fn chain_head_header(
+		&self,
+		follow_subscription: &str,
+		hash: Self::Hash,
+	) -> Result<Option<Self::Header>> {
+		let header: Option<Bytes> = self
+			.client()
+			.request("chainHead_v1_header", rpc_params![follow_subscription, hash])
+			.await?;
+		match header {
+			Some(header) => Ok(Some(Decode::decode(&mut header.0.as_slice())?)),
+			None => Ok(None),
+		}
+	}
+
+	async fn chain_head_body(
+		&self,
+		follow_subscription: &str,
+		hash: Self::Hash,
+	) -> Result<MethodResponse> {
+		let response = self
+			.client()
+			.request("chainHead_v1_body", rpc_params![follow_subscription, hash])
+			.await?;
+		Ok(response)
+	}
+
+	async fn chain_head_storage(
+		&self,
+		follow_subscription: &str,
+		hash: Self::Hash,
+		items: Vec<StorageQuery>,
+		child_trie: Option<StorageKey>,
+	) -> Result<MethodResponse> {
+		let response = self
+			.client()
+			.request(
+				"chainHead_v1_storage",
+				rpc_params![follow_subscription, hash, items, child_trie],
+			)
+			.await?;
+		Ok(response)
+	}
+
+	async fn chain_head_call(
+		&self,
+		follow_subscription: &str,
+		hash: Self::Hash,
+		function: &str,
+		call_parameters: Bytes,
+	) -> Result<MethodResponse> {
+		let response = self
+			.client()
+			.request(
+				"chainHead_v1_call",
+				rpc_params![follow_subscription, hash, function, call_parameters],
+			)
+			.await?;
+		Ok(response)
+	}
+
+	async fn chain_head_continue(
+		&self,
+		follow_subscription: &str,
+		operation_id: &str,
+	) -> Result<()> {
+		self.client()
+			.request("chainHead_v1_continue", rpc_params![follow_subscription, operation_id])
+			.await?;
+		Ok(())
+	}
+
+	async fn chain_head_stop_operation(
+		&self,
+		follow_subscription: &str,
+		operation_id: &str,
+	) -> Result<()> {
+		self.client()
+			.request("chainHead_v1_stopOperation", rpc_params![follow_subscription, operation_id])
+			.await?;
+		Ok(())
+	}
+
+	async fn chain_head_unpin(
+		&self,
+		follow_subscription: &str,
+		hashes: Vec<Self::Hash>,
+	) -> Result<()> {
+		self.client()
+			.request("chainHead_v1_unpin", rpc_params![follow_subscription, hashes])
+			.await?;
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::rpc::mocks::{RpcClientMock, create_mock_api};
+	use ac_primitives::{Initialized, MethodResponseStarted};
+	use codec::Encode;
+	use serde_json::Value;
+	use sp_core::H256;
+	use sp_runtime::{generic::Header, traits::BlakeTwo256};
+
+	#[test]
+	fn follow_returns_typed_events() {
+		let hash = H256::repeat_byte(1);
+		let events = vec![
+			FollowEvent::Initialized(Initialized {
+				finalized_block_hashes: vec![hash],
+				finalized_block_runtime: None,
+			}),
+			FollowEvent::Stop,
+		];
+		let api = create_mock_api(
+			RpcClientMock::default().with_subscription("chainHead_v1_follow", events),
+		);
+
+		let mut subscription = api.chain_head_follow(false).unwrap();
+
+		assert_eq!(subscription.subscription_id().as_deref(), Some("0"));
+		let Some(Ok(FollowEvent::Initialized(initialized))) = subscription.next() else {
+			panic!("Expected initialized event")
+		};
+		assert_eq!(initialized.finalized_block_hashes, vec![hash]);
+		assert!(matches!(subscription.next(), Some(Ok(FollowEvent::Stop))));
+	}
+
+	#[test]
+	fn header_is_decoded() {
+		let hash = H256::repeat_byte(1);
+		let header = Header::<u32, BlakeTwo256> {
+			parent_hash: hash,
+			number: 5,
+			state_root: Default::default(),
+			extrinsics_root: Default::default(),
+			digest: Default::default(),
+		};
+		let client = RpcClientMock::default()
+			.with_response_for(
+				"chainHead_v1_header",
+				rpc_params!["0", hash],
+				Bytes(header.encode()),
+			)
+			.with_response_for("chainHead_v1_header", rpc_params!["1", hash], Value::Null);
+		let api = create_mock_api(client);
+
+		assert_eq!(api.chain_head_header("0", hash).unwrap(), Some(header));
+		assert_eq!(api.chain_head_header("1", hash).unwrap(), None);
+	}
+
+	#[test]
+	fn storage_operation_is_started() {
+		let hash = H256::repeat_byte(1);
+		let key = StorageKey(vec![1, 2]);
+		let started = MethodResponse::Started(MethodResponseStarted {
+			operation_id: "op".into(),
+			discarded_items: Some(0),
+		});
+		let client = RpcClientMock::default().with_response_for(
+			"chainHead_v1_storage",
+			rpc_params![
+				"0",
+				hash,
+				vec![StorageQuery::value(key.clone())],
+				Option::<StorageKey>::None
+			],
+			started.clone(),
+		);
+		let api = create_mock_api(client);
+
+		let response =
+			api.chain_head_storage("0", hash, vec![StorageQuery::value(key)], None).unwrap();
+
+		assert_eq!(response, started);
+	}
+}
@@ -11,6 +11,8 @@
    limitations under the License.
 */
 
+#[cfg(feature = "std")]
+use crate::api::SharedRuntime;
 use crate::{
 	GetChainInfo, GetStorage,
 	api::{Api, Error, Result},
@@ -21,7 +23,7 @@ use ac_node_api::{EventDetails, EventRecord, Events, Phase, metadata::Metadata};
 use ac_primitives::config::Config;
 #[cfg(all(not(feature = "sync-api"), not(feature = "std")))]
 use alloc::boxed::Box;
-use alloc::{vec, vec::Vec};
+use alloc::{sync::Arc, vec, vec::Vec};
 use codec::{Decode, Encode};
 use core::marker::PhantomData;
 use log::*;
@@ -32,7 +34,7 @@ use sp_storage::StorageChangeSet;
 pub type EventSubscriptionFor<Client, Hash> =
 	EventSubscription<<Client as Subscribe>::Subscription<StorageChangeSet<Hash>>, Hash>;
 
-#[maybe_async::maybe_async(?Send)]
+#[ac_async_macros::maybe_async]
 pub trait FetchEvents {
 	type Hash: Encode + Decode;
 
@@ -47,7 +49,7 @@ pub trait FetchEvents {
 	) -> Result<Vec<EventDetails<Self::Hash>>>;
 }
 
-#[maybe_async::maybe_async(?Send)]
+#[ac_async_macros::maybe_async]
 impl<T, Client> FetchEvents for Api<T, Client>
 where
 	T: Config,
@@ -61,8 +63,11 @@ where
 			.get_opaque_storage_by_key(key, Some(block_hash))
 			.await?
 			.ok_or(Error::BlockNotFound)?;
-		let events =
-			Events::<Self::Hash>::new(self.metadata().clone(), Default::default(), event_bytes);
+		let events = Events::<Self::Hash>::new(
+			Metadata::clone(&self.metadata_snapshot()),
+			Default::default(),
+			event_bytes,
+		);
 		Ok(events)
 	}
 
@@ -80,21 +85,48 @@ where
 
 /// Wrapper around a Event `StorageChangeSet` subscription.
 /// Simplifies the event retrieval from the subscription.
+///
+/// If the subscription has been created from an api in shared runtime mode, the events are
+/// decoded with the metadata of the shared runtime instead of `metadata`.
 pub struct EventSubscription<Subscription, Hash> {
 	pub subscription: Subscription,
-	pub metadata: Metadata,
+	pub metadata: Arc<Metadata>,
+	#[cfg(feature = "std")]
+	shared_runtime: Option<SharedRuntime>,
 	_phantom: PhantomData<Hash>,
 }
 
 impl<Subscription, Hash> EventSubscription<Subscription, Hash> {
 	/// Create a new wrapper around the subscription.
-	pub fn new(subscription: Subscription, metadata: Metadata) -> Self {
-		Self { subscription, metadata, _phantom: Default::default() }
+	pub fn new(subscription: Subscription, metadata: impl Into<Arc<Metadata>>) -> Self {
+		Self {
+			subscription,
+			metadata: metadata.into(),
+			#[cfg(feature = "std")]
+			shared_runtime: None,
+			_phantom: Default::default(),
+		}
+	}
+
+	/// Decode the events with the metadata of the `shared_runtime`, if there is one.
+	#[cfg(feature = "std")]
+	pub(crate) fn with_shared_runtime(mut self, shared_runtime: Option<SharedRuntime>) -> Self {
+		self.shared_runtime = shared_runtime;
+		self
 	}
 
 	/// Update the metadata.
-	pub fn update_metadata(&mut self, metadata: Metadata) {
-		self.metadata = metadata
+	pub fn update_metadata(&mut self, metadata: impl Into<Arc<Metadata>>) {
+		self.metadata = metadata.into()
+	}
+
+	/// The metadata the next events are decoded with.
+	pub(crate) fn current_metadata(&self) -> Arc<Metadata> {
+		#[cfg(feature = "std")]
+		if let Some(shared_runtime) = &self.shared_runtime {
+			return shared_runtime.metadata()
+		}
+		self.metadata.clone()
 	}
 }
 
@@ -139,7 +171,8 @@ where
 		let storage_data = change_set.changes[0].1.as_ref()?;
 		let event_bytes = storage_data.0.clone();
 
-		let events = Events::<Hash>::new(self.metadata.clone(), block_hash, event_bytes);
+		let metadata = Metadata::clone(&self.current_metadata());
+		let events = Events::<Hash>::new(metadata, block_hash, event_bytes);
 		Some(Ok(events))
 	}
 
@@ -150,7 +183,7 @@ where
 	}
 }
 
-#[maybe_async::maybe_async(?Send)]
+#[ac_async_macros::maybe_async]
 pub trait SubscribeEvents {
 	type Client: Subscribe;
 	type Hash: DeserializeOwned;
@@ -159,7 +192,7 @@ pub trait SubscribeEvents {
 	async fn subscribe_events(&self) -> Result<EventSubscriptionFor<Self::Client, Self::Hash>>;
 }
 
-#[maybe_async::maybe_async(?Send)]
+#[ac_async_macros::maybe_async]
 impl<T, Client> SubscribeEvents for Api<T, Client>
 where
 	T: Config,
@@ -174,7 +207,9 @@ where
 			.client()
 			.subscribe("state_subscribeStorage", rpc_params![vec![key]], "state_unsubscribeStorage")
 			.await
-			.map(|sub| EventSubscription::new(sub, self.metadata().clone()))?;
+			.map(|sub| EventSubscription::new(sub, self.metadata_snapshot()))?;
+		#[cfg(feature = "std")]
+		let subscription = subscription.with_shared_runtime(self.shared_runtime().cloned());
 		Ok(subscription)
 	}
 }
@@ -231,11 +266,10 @@ where
 #[cfg(test)]
 mod tests {
 	use super::*;
-	use crate::rpc::mocks::RpcClientMock;
-	use ac_node_api::{metadata::Metadata, test_utils::*};
-	use ac_primitives::RococoRuntimeConfig;
+	use crate::rpc::mocks::{RpcClientMock, create_mock_api, create_mock_api_with_metadata};
+	use ac_node_api::test_utils::*;
+	use ac_primitives::DefaultRuntimeConfig;
 	use codec::{Decode, Encode};
-	use frame_metadata::RuntimeMetadataPrefixed;
 	use rococo_runtime::{BalancesCall, RuntimeCall, UncheckedExtrinsic};
 	use scale_info::TypeInfo;
 	use sp_core::{Bytes, H256, crypto::Ss58Codec, sr25519};
@@ -244,8 +278,7 @@ mod tests {
 		generic::{Block, SignedBlock},
 	};
 	use sp_storage::StorageData;
-	use sp_version::RuntimeVersion;
-	use std::{collections::HashMap, fs};
+	use std::collections::HashMap;
 	use test_case::test_case;
 
 	#[derive(Clone, Copy, Debug, PartialEq, Decode, Encode, TypeInfo)]
@@ -254,17 +287,6 @@ mod tests {
 		B(bool),
 	}
 
-	fn create_mock_api(
-		metadata: Metadata,
-		data: HashMap<String, String>,
-	) -> Api<RococoRuntimeConfig, RpcClientMock> {
-		// Create new api.
-		let genesis_hash = H256::random();
-		let runtime_version = RuntimeVersion::default();
-		let client = RpcClientMock::new(data);
-		Api::new_offline(genesis_hash, metadata, runtime_version, client)
-	}
-
 	fn default_header() -> rococo_runtime::Header {
 		rococo_runtime::Header {
 			number: Default::default(),
@@ -304,7 +326,7 @@ mod tests {
 		let _not_associated_event_details4 = event_details.next().unwrap().unwrap();
 		assert!(event_details.next().is_none());
 
-		let api = create_mock_api(metadata, Default::default());
+		let api = create_mock_api_with_metadata(metadata, RpcClientMock::default());
 
 		let associated_events = api.filter_extrinsic_events(block_events, extrinsic_index).unwrap();
 		assert_eq!(associated_events.len(), 2);
@@ -344,7 +366,7 @@ mod tests {
 			serde_json::to_string(&Some(StorageData(event_bytes))).unwrap(),
 		)]);
 
-		let api = create_mock_api(metadata, data);
+		let api = create_mock_api_with_metadata(metadata, RpcClientMock::new(data));
 
 		let fetched_events = api.fetch_events_from_block(H256::random()).unwrap();
 
@@ -353,12 +375,6 @@ mod tests {
 
 	#[test]
 	fn retrieve_extrinsic_index_from_block_works() {
-		// We need a pallet balance in the metadata, so ` api.balance_transfer` can create the extrinsic.
-		let encoded_metadata = fs::read("./../ksm_metadata_v14.bin").unwrap();
-		let metadata: RuntimeMetadataPrefixed =
-			Decode::decode(&mut encoded_metadata.as_slice()).unwrap();
-		let metadata = Metadata::try_from(metadata).unwrap();
-
 		let bob: AccountId32 =
 			sr25519::Public::from_ss58check("5FHneW46xGXgs5mUiveU4sbTyGBzmstUspZC92UhjJM694ty")
 				.unwrap()
@@ -381,9 +397,9 @@ mod tests {
 		let xt2: Bytes = UncheckedExtrinsic::new_bare(call2).encode().into();
 		let xt3: Bytes = UncheckedExtrinsic::new_bare(call3).encode().into();
 
-		let xt_hash1 = <RococoRuntimeConfig as Config>::Hasher::hash(&xt1);
-		let xt_hash2 = <RococoRuntimeConfig as Config>::Hasher::hash(&xt2);
-		let xt_hash3 = <RococoRuntimeConfig as Config>::Hasher::hash(&xt3);
+		let xt_hash1 = <DefaultRuntimeConfig as Config>::Hasher::hash(&xt1);
+		let xt_hash2 = <DefaultRuntimeConfig as Config>::Hasher::hash(&xt2);
+		let xt_hash3 = <DefaultRuntimeConfig as Config>::Hasher::hash(&xt3);
 
 		let block = Block { header: default_header(), extrinsics: vec![xt1, xt2, xt3] };
 		let signed_block = SignedBlock { block, justifications: None };
@@ -393,7 +409,7 @@ mod tests {
 		)]);
 
 		// Create api with block as storage data:
-		let api = create_mock_api(metadata, data);
+		let api = create_mock_api(RpcClientMock::new(data));
 		let block_hash = H256::default();
 
 		let (index1, index2, index3) = (
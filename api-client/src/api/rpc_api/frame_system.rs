@@ -25,7 +25,7 @@ use alloc::{string::String, vec::Vec};
 use log::*;
 use sp_storage::StorageKey;
 
-#[maybe_async::maybe_async(?Send)]
+#[ac_async_macros::maybe_async]
 pub trait GetAccountInformation {
 	type AccountId;
 	type Index;
@@ -51,7 +51,7 @@ pub trait GetAccountInformation {
 	async fn get_account_nonce(&self, account: &Self::AccountId) -> Result<Self::Index>;
 }
 
-#[maybe_async::maybe_async(?Send)]
+#[ac_async_macros::maybe_async]
 impl<T, Client> GetAccountInformation for Api<T, Client>
 where
 	T: Config,
@@ -76,7 +76,7 @@ where
 		&self,
 		address: &Self::AccountId,
 	) -> Result<Option<AccountInfo<Self::Index, Self::AccountData>>> {
-		let storagekey: StorageKey = self.metadata().storage_map_key::<Self::AccountId>(
+		let storagekey: StorageKey = self.metadata_snapshot().storage_map_key::<Self::AccountId>(
 			"System",
 			"Account",
 			address.clone(),
@@ -101,7 +101,7 @@ where
 }
 
 /// Helper functions for some common SystemApi function.
-#[maybe_async::maybe_async(?Send)]
+#[ac_async_macros::maybe_async]
 pub trait SystemApi {
 	type ChainType;
 	type Properties;
@@ -139,7 +139,7 @@ pub trait SystemApi {
 	async fn get_system_local_listen_addresses(&self) -> Result<Vec<String>>;
 }
 
-#[maybe_async::maybe_async(?Send)]
+#[ac_async_macros::maybe_async]
 impl<T, Client> SystemApi for Api<T, Client>
 where
 	T: Config,
@@ -12,15 +12,18 @@
 */
 
 pub use self::{
-	author::*, chain::*, events::*, frame_system::*, pallet_balances::*,
-	pallet_transaction_payment::*, runtime_update::*, state::*,
+	archive::*, author::*, chain::*, chain_head::*, events::*, frame_system::*, pallet_balances::*,
+	pallet_transaction_payment::*, runtime_update::*, state::*, transaction::*,
 };
 
+pub mod archive;
 pub mod author;
 pub mod chain;
+pub mod chain_head;
 pub mod events;
 pub mod frame_system;
 pub mod pallet_balances;
 pub mod pallet_transaction_payment;
 pub mod runtime_update;
 pub mod state;
+pub mod transaction;
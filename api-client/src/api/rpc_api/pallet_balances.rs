@@ -19,14 +19,14 @@ use ac_primitives::config::Config;
 use alloc::boxed::Box;
 
 /// Interface to common calls of the substrate balances pallet.
-#[maybe_async::maybe_async(?Send)]
+#[ac_async_macros::maybe_async]
 pub trait GetBalance {
 	type Balance;
 
 	async fn get_existential_deposit(&self) -> Result<Self::Balance>;
 }
 
-#[maybe_async::maybe_async(?Send)]
+#[ac_async_macros::maybe_async]
 impl<T, Client> GetBalance for Api<T, Client>
 where
 	T: Config,
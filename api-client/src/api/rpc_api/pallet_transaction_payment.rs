@@ -21,7 +21,7 @@ use alloc::boxed::Box;
 use core::str::FromStr;
 use sp_core::Bytes;
 /// Interface to common calls of the substrate transaction payment pallet.
-#[maybe_async::maybe_async(?Send)]
+#[ac_async_macros::maybe_async]
 pub trait GetTransactionPayment {
 	type Hash;
 	type Balance;
@@ -39,7 +39,7 @@ pub trait GetTransactionPayment {
 	) -> Result<Option<RuntimeDispatchInfo<Self::Balance>>>;
 }
 
-#[maybe_async::maybe_async(?Send)]
+#[ac_async_macros::maybe_async]
 impl<T, Client> GetTransactionPayment for Api<T, Client>
 where
 	T: Config,
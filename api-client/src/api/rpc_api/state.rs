@@ -11,13 +11,13 @@
    limitations under the License.
 */
 use crate::{
-	Api, ReadProof,
+	Api, Archive, Error, ReadProof,
 	api::Result,
-	rpc::{Request, Subscribe},
+	rpc::{BatchRequest, Request, Subscribe},
 };
 use ac_compose_macros::rpc_params;
 use ac_node_api::MetadataError;
-use ac_primitives::config::Config;
+use ac_primitives::{MaybeSend, PaginatedStorageQuery, StorageQueryType, config::Config};
 #[cfg(all(not(feature = "sync-api"), not(feature = "std")))]
 use alloc::boxed::Box;
 use alloc::{borrow::ToOwned, string::String, vec, vec::Vec};
@@ -35,7 +35,12 @@ pub type StorageChangeSetSubscriptionFor<Client, Hash> =
 	<Client as Subscribe>::Subscription<StorageChangeSet<Hash>>;
 
 /// Generic interface to substrate storage.
-#[maybe_async::maybe_async(?Send)]
+///
+/// The implementation of the `Api` only needs a [`Request`] client and therefore always uses the
+/// legacy `state_*` methods, regardless of the [`RpcCapabilities`](crate::RpcCapabilities):
+/// the storage queries of the new json-rpc specification are subscriptions. For nodes that
+/// don't offer the legacy methods, use [`GetStorageWithFallback`] or [`Archive`] instead.
+#[ac_async_macros::maybe_async]
 pub trait GetStorage {
 	type Hash;
 	/// Retrieve the storage value.
@@ -51,7 +56,7 @@ pub trait GetStorage {
 	/// Retrieve the storage value from a map for the given `map_key`.
 	///
 	/// `at_block`: the state is queried at this block, set to `None` to get the state from the latest known block.
-	async fn get_storage_map<K: Encode, V: Decode>(
+	async fn get_storage_map<K: Encode + MaybeSend, V: Decode>(
 		&self,
 		pallet: &'static str,
 		storage_item: &'static str,
@@ -66,7 +71,7 @@ pub trait GetStorage {
 		storage_item: &'static str,
 	) -> Result<StorageKey>;
 
-	async fn get_storage_double_map_key_prefix<K: Encode>(
+	async fn get_storage_double_map_key_prefix<K: Encode + MaybeSend>(
 		&self,
 		storage_prefix: &'static str,
 		storage_key_name: &'static str,
@@ -76,7 +81,7 @@ pub trait GetStorage {
 	/// Retrieve the storage value from a double map for the given keys: `first_double_map_key` and `second_double_map_key`.
 	///
 	/// `at_block`: the state is queried at this block, set to `None` to get the state from the latest known block.
-	async fn get_storage_double_map<K: Encode, Q: Encode, V: Decode>(
+	async fn get_storage_double_map<K: Encode + MaybeSend, Q: Encode + MaybeSend, V: Decode>(
 		&self,
 		pallet: &'static str,
 		storage_item: &'static str,
@@ -146,7 +151,7 @@ pub trait GetStorage {
 	/// Retrieve the storage proof of the corresponding storage map value.
 	///
 	/// `at_block`: the state is queried at this block, set to `None` to get the state from the latest known block.
-	async fn get_storage_map_proof<K: Encode>(
+	async fn get_storage_map_proof<K: Encode + MaybeSend>(
 		&self,
 		pallet: &'static str,
 		storage_item: &'static str,
@@ -157,7 +162,7 @@ pub trait GetStorage {
 	/// Retrieve the storage proof of the corresponding storage double map value.
 	///
 	/// `at_block`: the state is queried at this block, set to `None` to get the state from the latest known block.
-	async fn get_storage_double_map_proof<K: Encode, Q: Encode>(
+	async fn get_storage_double_map_proof<K: Encode + MaybeSend, Q: Encode + MaybeSend>(
 		&self,
 		pallet: &'static str,
 		storage_item: &'static str,
@@ -188,7 +193,7 @@ pub trait GetStorage {
 	) -> Result<C>;
 }
 
-#[maybe_async::maybe_async(?Send)]
+#[ac_async_macros::maybe_async]
 impl<T, Client> GetStorage for Api<T, Client>
 where
 	T: Config,
@@ -202,19 +207,20 @@ where
 		storage_item: &'static str,
 		at_block: Option<Self::Hash>,
 	) -> Result<Option<V>> {
-		let storagekey = self.metadata().storage_value_key(pallet, storage_item)?;
+		let storagekey = self.metadata_snapshot().storage_value_key(pallet, storage_item)?;
 		info!("storage key is: 0x{}", hex::encode(&storagekey));
 		self.get_storage_by_key(storagekey, at_block).await
 	}
 
-	async fn get_storage_map<K: Encode, V: Decode>(
+	async fn get_storage_map<K: Encode + MaybeSend, V: Decode>(
 		&self,
 		pallet: &'static str,
 		storage_item: &'static str,
 		map_key: K,
 		at_block: Option<Self::Hash>,
 	) -> Result<Option<V>> {
-		let storagekey = self.metadata().storage_map_key::<K>(pallet, storage_item, map_key)?;
+		let storagekey =
+			self.metadata_snapshot().storage_map_key::<K>(pallet, storage_item, map_key)?;
 		info!("storage key is: 0x{}", hex::encode(&storagekey));
 		self.get_storage_by_key(storagekey, at_block).await
 	}
@@ -224,12 +230,12 @@ where
 		pallet: &'static str,
 		storage_item: &'static str,
 	) -> Result<StorageKey> {
-		self.metadata()
+		self.metadata_snapshot()
 			.storage_map_key_prefix(pallet, storage_item)
 			.map_err(|e| e.into())
 	}
 
-	async fn get_storage_double_map<K: Encode, Q: Encode, V: Decode>(
+	async fn get_storage_double_map<K: Encode + MaybeSend, Q: Encode + MaybeSend, V: Decode>(
 		&self,
 		pallet: &'static str,
 		storage_item: &'static str,
@@ -237,7 +243,7 @@ where
 		second_double_map_key: Q,
 		at_block: Option<Self::Hash>,
 	) -> Result<Option<V>> {
-		let storagekey = self.metadata().storage_double_map_key::<K, Q>(
+		let storagekey = self.metadata_snapshot().storage_double_map_key::<K, Q>(
 			pallet,
 			storage_item,
 			first_double_map_key,
@@ -247,13 +253,13 @@ where
 		self.get_storage_by_key(storagekey, at_block).await
 	}
 
-	async fn get_storage_double_map_key_prefix<K: Encode>(
+	async fn get_storage_double_map_key_prefix<K: Encode + MaybeSend>(
 		&self,
 		storage_prefix: &'static str,
 		storage_key_name: &'static str,
 		first: K,
 	) -> Result<StorageKey> {
-		self.metadata()
+		self.metadata_snapshot()
 			.storage_double_map_key_prefix(storage_prefix, storage_key_name, first)
 			.map_err(|e| e.into())
 	}
@@ -338,24 +344,25 @@ where
 		storage_item: &'static str,
 		at_block: Option<Self::Hash>,
 	) -> Result<Option<ReadProof<Self::Hash>>> {
-		let storagekey = self.metadata().storage_value_key(pallet, storage_item)?;
+		let storagekey = self.metadata_snapshot().storage_value_key(pallet, storage_item)?;
 		info!("storage key is: 0x{}", hex::encode(&storagekey));
 		self.get_storage_proof_by_keys(vec![storagekey], at_block).await
 	}
 
-	async fn get_storage_map_proof<K: Encode>(
+	async fn get_storage_map_proof<K: Encode + MaybeSend>(
 		&self,
 		pallet: &'static str,
 		storage_item: &'static str,
 		map_key: K,
 		at_block: Option<Self::Hash>,
 	) -> Result<Option<ReadProof<Self::Hash>>> {
-		let storagekey = self.metadata().storage_map_key::<K>(pallet, storage_item, map_key)?;
+		let storagekey =
+			self.metadata_snapshot().storage_map_key::<K>(pallet, storage_item, map_key)?;
 		info!("storage key is: 0x{}", hex::encode(&storagekey));
 		self.get_storage_proof_by_keys(vec![storagekey], at_block).await
 	}
 
-	async fn get_storage_double_map_proof<K: Encode, Q: Encode>(
+	async fn get_storage_double_map_proof<K: Encode + MaybeSend, Q: Encode + MaybeSend>(
 		&self,
 		pallet: &'static str,
 		storage_item: &'static str,
@@ -363,7 +370,7 @@ where
 		second_double_map_key: Q,
 		at_block: Option<Self::Hash>,
 	) -> Result<Option<ReadProof<Self::Hash>>> {
-		let storage_key = self.metadata().storage_double_map_key::<K, Q>(
+		let storage_key = self.metadata_snapshot().storage_double_map_key::<K, Q>(
 			pallet,
 			storage_item,
 			first_double_map_key,
@@ -402,8 +409,8 @@ where
 		pallet: &'static str,
 		constant: &'static str,
 	) -> Result<C> {
-		let c = self
-			.metadata()
+		let metadata = self.metadata_snapshot();
+		let c = metadata
 			.pallet_by_name_err(pallet)?
 			.constant_by_name(constant)
 			.ok_or(MetadataError::ConstantNotFound(constant))?;
@@ -412,7 +419,147 @@ where
 	}
 }
 
-#[maybe_async::maybe_async(?Send)]
+/// Storage values that are read with the legacy `state_getStorage` as long as the node offers it,
+/// see [`RpcCapabilities`](crate::RpcCapabilities), and with the `archive_v1_storage`
+/// subscription otherwise.
+#[ac_async_macros::maybe_async]
+pub trait GetStorageWithFallback {
+	type Hash;
+
+	/// Retrieve the storage value from the given `storage_key`.
+	///
+	/// `at_block`: the state is queried at this block, set to `None` to get the state from the latest known block.
+	/// The archive only knows about finalized blocks, so it is queried at the last finalized block instead.
+	async fn get_storage_by_key_with_fallback<V: Decode>(
+		&self,
+		storage_key: StorageKey,
+		at_block: Option<Self::Hash>,
+	) -> Result<Option<V>>;
+
+	/// Retrieve the raw storage for the given `storage_key`.
+	///
+	/// `at_block`: the state is queried at this block, set to `None` to get the state from the latest known block.
+	/// The archive only knows about finalized blocks, so it is queried at the last finalized block instead.
+	async fn get_opaque_storage_by_key_with_fallback(
+		&self,
+		storage_key: StorageKey,
+		at_block: Option<Self::Hash>,
+	) -> Result<Option<Vec<u8>>>;
+}
+
+#[ac_async_macros::maybe_async]
+impl<T, Client> GetStorageWithFallback for Api<T, Client>
+where
+	T: Config,
+	Client: Request + Subscribe,
+{
+	type Hash = T::Hash;
+
+	async fn get_storage_by_key_with_fallback<V: Decode>(
+		&self,
+		storage_key: StorageKey,
+		at_block: Option<Self::Hash>,
+	) -> Result<Option<V>> {
+		let s = self.get_opaque_storage_by_key_with_fallback(storage_key, at_block).await?;
+		match s {
+			Some(storage) => Ok(Some(Decode::decode(&mut storage.as_slice())?)),
+			None => Ok(None),
+		}
+	}
+
+	async fn get_opaque_storage_by_key_with_fallback(
+		&self,
+		storage_key: StorageKey,
+		at_block: Option<Self::Hash>,
+	) -> Result<Option<Vec<u8>>> {
+		if self.rpc_capabilities().use_legacy("state_getStorage", "archive_v1_storage") {
+			return self.get_opaque_storage_by_key(storage_key, at_block).await
+		}
+		let hash = self
+			.archive_hash_or_finalized(at_block)
+			.await?
+			.ok_or(Error::BlockHashNotFound)?;
+		let query = PaginatedStorageQuery::new(storage_key, StorageQueryType::Value);
+		let results = self.archive_storage_items(hash, vec![query], None, Some(1)).await?;
+		Ok(results
+			.into_iter()
+			.next()
+			.and_then(|result| result.item.value)
+			.map(|value| value.0))
+	}
+}
+
+/// Storage queries that are sent to the node within one json-rpc batch, instead of
+/// one request per storage key.
+///
+/// The batch always consists of legacy `state_getStorage` requests, regardless of the
+/// [`RpcCapabilities`](crate::RpcCapabilities), so the node has to offer the legacy methods.
+#[ac_async_macros::maybe_async]
+pub trait GetStorageBatch {
+	type Hash;
+
+	/// Retrieve the storage values of all given `storage_keys`.
+	/// The values are returned in the same order as the keys.
+	///
+	/// `at_block`: the state is queried at this block, set to `None` to get the state from the latest known block.
+	async fn get_storage_by_keys<V: Decode>(
+		&self,
+		storage_keys: Vec<StorageKey>,
+		at_block: Option<Self::Hash>,
+	) -> Result<Vec<Option<V>>>;
+
+	/// Retrieve the raw storage of all given `storage_keys`.
+	/// The values are returned in the same order as the keys.
+	///
+	/// `at_block`: the state is queried at this block, set to `None` to get the state from the latest known block.
+	async fn get_opaque_storage_by_keys(
+		&self,
+		storage_keys: Vec<StorageKey>,
+		at_block: Option<Self::Hash>,
+	) -> Result<Vec<Option<Vec<u8>>>>;
+}
+
+#[ac_async_macros::maybe_async]
+impl<T, Client> GetStorageBatch for Api<T, Client>
+where
+	T: Config,
+	Client: BatchRequest,
+{
+	type Hash = T::Hash;
+
+	async fn get_storage_by_keys<V: Decode>(
+		&self,
+		storage_keys: Vec<StorageKey>,
+		at_block: Option<Self::Hash>,
+	) -> Result<Vec<Option<V>>> {
+		let storages = self.get_opaque_storage_by_keys(storage_keys, at_block).await?;
+		storages
+			.into_iter()
+			.map(|storage| match storage {
+				Some(storage) => Ok(Some(Decode::decode(&mut storage.as_slice())?)),
+				None => Ok(None),
+			})
+			.collect()
+	}
+
+	async fn get_opaque_storage_by_keys(
+		&self,
+		storage_keys: Vec<StorageKey>,
+		at_block: Option<Self::Hash>,
+	) -> Result<Vec<Option<Vec<u8>>>> {
+		let requests = storage_keys
+			.into_iter()
+			.map(|storage_key| ("state_getStorage", rpc_params![storage_key, at_block]))
+			.collect();
+		let storages: Vec<_> = self.client().batch_request::<Option<StorageData>>(requests).await?;
+		storages
+			.into_iter()
+			.map(|storage| Ok(storage?.map(|storage_data| storage_data.0)))
+			.collect()
+	}
+}
+
+#[ac_async_macros::maybe_async]
 pub trait SubscribeState {
 	type Client: Subscribe;
 	type Hash: DeserializeOwned;
@@ -424,7 +571,7 @@ pub trait SubscribeState {
 	) -> Result<StorageChangeSetSubscriptionFor<Self::Client, Self::Hash>>;
 }
 
-#[maybe_async::maybe_async(?Send)]
+#[ac_async_macros::maybe_async]
 impl<T, Client> SubscribeState for Api<T, Client>
 where
 	T: Config,
@@ -446,3 +593,61 @@ where
 			.map_err(|e| e.into())
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		RpcCapabilities,
+		rpc::mocks::{RpcClientMock, create_mock_api},
+	};
+	use ac_primitives::{ArchiveStorageEvent, ArchiveStorageResult, StorageResultItem};
+	use sp_core::{Bytes, H256};
+
+	#[test]
+	fn get_storage_uses_archive_without_legacy_method() {
+		let hash = H256::random();
+		let key = StorageKey(vec![1]);
+		let query = PaginatedStorageQuery::new(key.clone(), StorageQueryType::Value);
+		let event = ArchiveStorageEvent::Storage(ArchiveStorageResult {
+			item: StorageResultItem {
+				key: key.clone(),
+				value: Some(Bytes(42u32.encode())),
+				hash: None,
+				closest_descendant_merkle_value: None,
+			},
+			child_trie_key: None,
+		});
+		let client = RpcClientMock::default()
+			.with_response("archive_v1_finalizedHeight", 10u32)
+			.with_response_for("archive_v1_hashByHeight", rpc_params![10u32], vec![hash])
+			.with_subscription_for(
+				"archive_v1_storage",
+				rpc_params![hash, vec![query], Option::<StorageKey>::None],
+				vec![event, ArchiveStorageEvent::StorageDone],
+			);
+		let mut api = create_mock_api(client);
+		api.set_rpc_capabilities(RpcCapabilities::new([
+			"archive_v1_finalizedHeight",
+			"archive_v1_hashByHeight",
+			"archive_v1_storage",
+		]));
+
+		let value: Option<u32> = api.get_storage_by_key_with_fallback(key, None).unwrap();
+
+		assert_eq!(value, Some(42));
+	}
+
+	#[test]
+	fn get_storage_prefers_legacy_method() {
+		let client = RpcClientMock::default()
+			.with_response("state_getStorage", Some(StorageData(42u32.encode())));
+		let mut api = create_mock_api(client);
+		api.set_rpc_capabilities(RpcCapabilities::new(["archive_v1_storage", "state_getStorage"]));
+
+		let value: Option<u32> =
+			api.get_storage_by_key_with_fallback(StorageKey(vec![1]), None).unwrap();
+
+		assert_eq!(value, Some(42));
+	}
+}
@@ -0,0 +1,422 @@
+/*
+   Copyright 2019 Supercomputing Systems AG
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+	   http://www.apache.org/licenses/LICENSE-2.0
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Interface to the `transactionWatch_v1` and `transaction_v1` functions of the new json-rpc
+//! specification, as alternative to the `author` functions.
+//! https://paritytech.github.io/json-rpc-interface-spec/api/transactionWatch.html
+
+use crate::{
+	Api, ExtrinsicReport, TransactionStatus, UnexpectedTxStatus, XtStatus,
+	api::{Error, Result, rpc_api::author::SubmitAndWatch},
+	rpc::{HandleSubscription, Request, Subscribe},
+};
+use ac_compose_macros::rpc_params;
+use ac_primitives::{
+	MaybeSend, TransactionBlockIncluded, TransactionEvent, UncheckedExtrinsic, config::Config,
+};
+#[cfg(all(not(feature = "sync-api"), not(feature = "std")))]
+use alloc::boxed::Box;
+use alloc::{string::String, vec::Vec};
+use codec::{Decode, Encode};
+use log::*;
+use serde::de::DeserializeOwned;
+use sp_core::Bytes;
+use sp_runtime::traits::Hash as HashTrait;
+
+pub type TransactionEventSubscriptionFor<Client, Hash> =
+	<Client as Subscribe>::Subscription<TransactionEvent<Hash>>;
+
+/// Extrinsic submission via the `transactionWatch_v1` and `transaction_v1` functions.
+///
+/// The results are reported the same way as with [`SubmitAndWatch`], so both can be handled alike.
+#[ac_async_macros::maybe_async]
+pub trait TransactionWatch {
+	type Client: Subscribe;
+	type Hash: DeserializeOwned + Decode + Encode + MaybeSend;
+
+	/// Submit an extrinsic and return a subscription to watch its progress.
+	///
+	/// This method is blocking if the sync-api feature is activated
+	async fn transaction_submit_and_watch<Address, Call, Signature, TransactionExtension>(
+		&self,
+		extrinsic: UncheckedExtrinsic<Address, Call, Signature, TransactionExtension>,
+	) -> Result<TransactionEventSubscriptionFor<Self::Client, Self::Hash>>
+	where
+		Address: Encode + MaybeSend,
+		Call: Encode + MaybeSend,
+		Signature: Encode + MaybeSend,
+		TransactionExtension: Encode + MaybeSend;
+
+	/// Submit an encoded, opaque extrinsic and return a subscription to watch its progress.
+	///
+	/// This method is blocking if the sync-api feature is activated
+	async fn transaction_submit_and_watch_opaque(
+		&self,
+		encoded_extrinsic: &Bytes,
+	) -> Result<TransactionEventSubscriptionFor<Self::Client, Self::Hash>>;
+
+	/// Submit an extrinsic and watch it until the desired status is reached, if no error is
+	/// encountered previously.
+	///
+	/// The events are mapped to the corresponding [`TransactionStatus`]: `Validated` to `Ready`,
+	/// `Broadcasted` to `Broadcast`, `BestChainBlockIncluded` to `InBlock` (or `Retracted` if
+	/// the block is not part of the best chain anymore) and `Finalized` to `Finalized`.
+	/// The events `Invalid`, `Dropped` and `Error` result in an [`Error::UnexpectedTxStatus`].
+	///
+	/// If watched until `InBlock` or `Finalized`, the events of the extrinsic are added to the
+	/// report and an error is returned if the extrinsic was not successfully executed.
+	///
+	/// This method is blocking if the sync-api feature is activated
+	async fn transaction_submit_and_watch_until<Address, Call, Signature, TransactionExtension>(
+		&self,
+		extrinsic: UncheckedExtrinsic<Address, Call, Signature, TransactionExtension>,
+		watch_until: XtStatus,
+	) -> Result<ExtrinsicReport<Self::Hash>>
+	where
+		Address: Encode + MaybeSend,
+		Call: Encode + MaybeSend,
+		Signature: Encode + MaybeSend,
+		TransactionExtension: Encode + MaybeSend;
+
+	/// Submit an encoded, opaque extrinsic and watch it until the desired status is reached.
+	/// See [`TransactionWatch::transaction_submit_and_watch_until`].
+	///
+	/// This method is blocking if the sync-api feature is activated
+	async fn transaction_submit_and_watch_opaque_until(
+		&self,
+		encoded_extrinsic: &Bytes,
+		watch_until: XtStatus,
+	) -> Result<ExtrinsicReport<Self::Hash>>;
+
+	/// Submit an encoded, opaque extrinsic and watch it until the desired status is reached.
+	/// The events are not fetched. So no events are listed in the report.
+	///
+	/// This method is blocking if the sync-api feature is activated
+	async fn transaction_submit_and_watch_opaque_until_without_events(
+		&self,
+		encoded_extrinsic: &Bytes,
+		watch_until: XtStatus,
+	) -> Result<ExtrinsicReport<Self::Hash>>;
+
+	/// Let the node broadcast an extrinsic to its peers, without validating or watching it.
+	/// The node keeps broadcasting until [`TransactionWatch::transaction_stop`] is called.
+	///
+	/// Returns the id of the broadcast operation, or `None` if the node does not accept further
+	/// broadcast operations.
+	async fn transaction_broadcast<Address, Call, Signature, TransactionExtension>(
+		&self,
+		extrinsic: UncheckedExtrinsic<Address, Call, Signature, TransactionExtension>,
+	) -> Result<Option<String>>
+	where
+		Address: Encode + MaybeSend,
+		Call: Encode + MaybeSend,
+		Signature: Encode + MaybeSend,
+		TransactionExtension: Encode + MaybeSend;
+
+	/// Let the node broadcast an encoded, opaque extrinsic to its peers.
+	/// See [`TransactionWatch::transaction_broadcast`].
+	async fn transaction_broadcast_opaque(
+		&self,
+		encoded_extrinsic: &Bytes,
+	) -> Result<Option<String>>;
+
+	/// Stop the broadcast operation with the given `operation_id`.
+	async fn transaction_stop(&self, operation_id: &str) -> Result<()>;
+}
+
+#[ac_async_macros::maybe_async]
+impl<T, Client> TransactionWatch for Api<T, Client>
+where
+	T: Config,
+	Client: Subscribe + Request,
+{
+	type Client = Client;
+	type Hash = T::Hash;
+
+	async fn transaction_submit_and_watch<Address, Call, Signature, TransactionExtension>(
+		&self,
+		extrinsic: UncheckedExtrinsic<Address, Call, Signature, TransactionExtension>,
+	) -> Result<TransactionEventSubscriptionFor<Self::Client, Self::Hash>>
+	where
+		Address: Encode + MaybeSend,
+		Call: Encode + MaybeSend,
+		Signature: Encode + MaybeSend,
+		TransactionExtension: Encode + MaybeSend,
+	{
+		self.transaction_submit_and_watch_opaque(&extrinsic.encode().into()).await
+	}
+
+	async fn transaction_submit_and_watch_opaque(
+		&self,
+		encoded_extrinsic: &Bytes,
+	) -> Result<TransactionEventSubscriptionFor<Self::Client, Self::Hash>> {
+		self.client()
+			.subscribe(
+				"transactionWatch_v1_submitAndWatch",
+				rpc_params![encoded_extrinsic],
+				"transactionWatch_v1_unwatch",
+			)
+			.await
+			.map_err(|e| e.into())
+	}
+
+	async fn transaction_submit_and_watch_until<Address, Call, Signature, TransactionExtension>(
+		&self,
+		extrinsic: UncheckedExtrinsic<Address, Call, Signature, TransactionExtension>,
+		watch_until: XtStatus,
+	) -> Result<ExtrinsicReport<Self::Hash>>
+	where
+		Address: Encode + MaybeSend,
+		Call: Encode + MaybeSend,
+		Signature: Encode + MaybeSend,
+		TransactionExtension: Encode + MaybeSend,
+	{
+		self.transaction_submit_and_watch_opaque_until(&extrinsic.encode().into(), watch_until)
+			.await
+	}
+
+	async fn transaction_submit_and_watch_opaque_until(
+		&self,
+		encoded_extrinsic: &Bytes,
+		watch_until: XtStatus,
+	) -> Result<ExtrinsicReport<Self::Hash>> {
+		let mut report = self
+			.transaction_submit_and_watch_opaque_until_without_events(
+				encoded_extrinsic,
+				watch_until,
+			)
+			.await?;
+
+		if watch_until < XtStatus::InBlock {
+			return Ok(report)
+		}
+		self.populate_events(&mut report).await?;
+		report.check_events_for_dispatch_error(&self.metadata_snapshot())?;
+		Ok(report)
+	}
+
+	async fn transaction_submit_and_watch_opaque_until_without_events(
+		&self,
+		encoded_extrinsic: &Bytes,
+		watch_until: XtStatus,
+	) -> Result<ExtrinsicReport<Self::Hash>> {
+		let tx_hash = T::Hasher::hash(encoded_extrinsic);
+		let mut subscription: TransactionEventSubscriptionFor<Self::Client, Self::Hash> =
+			self.transaction_submit_and_watch_opaque(encoded_extrinsic).await?;
+
+		// Block of the best chain the extrinsic has last been included in.
+		let mut included_in = None;
+		while let Some(event) = subscription.next().await {
+			let event = event?;
+			// The node ends the subscription itself after a final event.
+			let is_final = event.is_final();
+			let transaction_status = match to_transaction_status(event, included_in) {
+				Ok(transaction_status) => transaction_status,
+				Err(e) => {
+					if !is_final {
+						subscription.unsubscribe().await?;
+					}
+					return Err(e)
+				},
+			};
+			if let TransactionStatus::InBlock(block_hash) = transaction_status {
+				included_in = Some(block_hash);
+			}
+			if transaction_status.reached_status(watch_until) {
+				if !is_final {
+					subscription.unsubscribe().await?;
+				}
+				let block_hash = transaction_status.get_maybe_block_hash().copied();
+				return Ok(ExtrinsicReport::new(tx_hash, block_hash, transaction_status, None))
+			}
+		}
+		Err(Error::NoStream)
+	}
+
+	async fn transaction_broadcast<Address, Call, Signature, TransactionExtension>(
+		&self,
+		extrinsic: UncheckedExtrinsic<Address, Call, Signature, TransactionExtension>,
+	) -> Result<Option<String>>
+	where
+		Address: Encode + MaybeSend,
+		Call: Encode + MaybeSend,
+		Signature: Encode + MaybeSend,
+		TransactionExtension: Encode + MaybeSend,
+	{
+		self.transaction_broadcast_opaque(&extrinsic.encode().into()).await
+	}
+
+	async fn transaction_broadcast_opaque(
+		&self,
+		encoded_extrinsic: &Bytes,
+	) -> Result<Option<String>> {
+		let operation_id = self
+			.client()
+			.request("transaction_v1_broadcast", rpc_params![encoded_extrinsic])
+			.await?;
+		Ok(operation_id)
+	}
+
+	async fn transaction_stop(&self, operation_id: &str) -> Result<()> {
+		self.client().request("transaction_v1_stop", rpc_params![operation_id]).await?;
+		Ok(())
+	}
+}
+
+/// Maps the `event` to the corresponding legacy [`TransactionStatus`].
+/// `included_in` is the block of the best chain the transaction has last been included in.
+fn to_transaction_status<Hash: Encode + Decode>(
+	event: TransactionEvent<Hash>,
+	included_in: Option<Hash>,
+) -> Result<TransactionStatus<Hash, Hash>> {
+	match event {
+		TransactionEvent::Validated => Ok(TransactionStatus::Ready),
+		TransactionEvent::Broadcasted(_) => Ok(TransactionStatus::Broadcast(Vec::new())),
+		TransactionEvent::BestChainBlockIncluded(TransactionBlockIncluded { block }) =>
+			match (block, included_in) {
+				(Some(block), _) => Ok(TransactionStatus::InBlock(block.hash)),
+				(None, Some(retracted)) => Ok(TransactionStatus::Retracted(retracted)),
+				// Back in the pool, without having been reported in a block before.
+				(None, None) => Ok(TransactionStatus::Ready),
+			},
+		TransactionEvent::Finalized(finalized) =>
+			Ok(TransactionStatus::Finalized(finalized.block.hash)),
+		TransactionEvent::Error(e) => {
+			warn!("Transaction could not be processed: {}", e.error);
+			Err(Error::UnexpectedTxStatus(UnexpectedTxStatus::Error))
+		},
+		TransactionEvent::Invalid(e) => {
+			warn!("Transaction is invalid: {}", e.error);
+			Err(Error::UnexpectedTxStatus(UnexpectedTxStatus::Invalid))
+		},
+		TransactionEvent::Dropped(e) => {
+			warn!("Transaction has been dropped: {}", e.error);
+			Err(Error::UnexpectedTxStatus(UnexpectedTxStatus::Dropped))
+		},
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		RpcCapabilities,
+		rpc::mocks::{RpcClientMock, create_mock_api},
+	};
+	use ac_primitives::{
+		TransactionBlock, TransactionBroadcasted, TransactionError, TransactionFinalized,
+	};
+	use sp_core::H256;
+	use sp_runtime::traits::BlakeTwo256;
+
+	fn included(hash: Option<H256>) -> TransactionEvent<H256> {
+		TransactionEvent::BestChainBlockIncluded(TransactionBlockIncluded {
+			block: hash.map(|hash| TransactionBlock { hash, index: 1 }),
+		})
+	}
+
+	#[test]
+	fn watch_until_finalized_returns_report() {
+		let extrinsic = Bytes(vec![1, 2, 3]);
+		let (first_block, second_block) = (H256::repeat_byte(1), H256::repeat_byte(2));
+		let events = vec![
+			TransactionEvent::Validated,
+			included(Some(first_block)),
+			included(None),
+			included(Some(second_block)),
+			TransactionEvent::Finalized(TransactionFinalized {
+				block: TransactionBlock { hash: second_block, index: 1 },
+			}),
+		];
+		let api = create_mock_api(
+			RpcClientMock::default()
+				.with_subscription("transactionWatch_v1_submitAndWatch", events),
+		);
+
+		let report = api
+			.transaction_submit_and_watch_opaque_until_without_events(
+				&extrinsic,
+				XtStatus::Finalized,
+			)
+			.unwrap();
+
+		assert_eq!(report.extrinsic_hash, BlakeTwo256::hash(&extrinsic));
+		assert_eq!(report.block_hash, Some(second_block));
+		assert_eq!(report.status, TransactionStatus::Finalized(second_block));
+		assert!(report.events.is_none());
+	}
+
+	#[test]
+	fn invalid_event_returns_error() {
+		let events = vec![
+			TransactionEvent::Validated,
+			TransactionEvent::Invalid(TransactionError { error: "Stale".into() }),
+		];
+		let api = create_mock_api(
+			RpcClientMock::default()
+				.with_subscription("transactionWatch_v1_submitAndWatch", events),
+		);
+
+		let result = api.transaction_submit_and_watch_opaque_until_without_events(
+			&Bytes(vec![1]),
+			XtStatus::InBlock,
+		);
+
+		assert!(matches!(result, Err(Error::UnexpectedTxStatus(UnexpectedTxStatus::Invalid))));
+	}
+
+	#[test]
+	fn retracted_block_maps_to_retracted_status() {
+		let block = H256::repeat_byte(1);
+
+		assert_eq!(
+			to_transaction_status(included(None), Some(block)).unwrap(),
+			TransactionStatus::Retracted(block)
+		);
+		assert_eq!(to_transaction_status(included(None), None).unwrap(), TransactionStatus::Ready);
+	}
+
+	#[test]
+	fn submit_and_watch_uses_transaction_watch_without_legacy_methods() {
+		let events = vec![
+			TransactionEvent::Validated,
+			TransactionEvent::Broadcasted(TransactionBroadcasted { num_peers: 2 }),
+		];
+		let mut api = create_mock_api(
+			RpcClientMock::default()
+				.with_subscription("transactionWatch_v1_submitAndWatch", events),
+		);
+		api.set_rpc_capabilities(RpcCapabilities::new(["transactionWatch_v1_submitAndWatch"]));
+
+		let report = api
+			.submit_and_watch_opaque_extrinsic_until_without_events(
+				&Bytes(vec![1]),
+				XtStatus::Broadcast,
+			)
+			.unwrap();
+
+		assert!(matches!(report.status, TransactionStatus::Broadcast(_)));
+	}
+
+	#[test]
+	fn broadcast_returns_operation_id() {
+		let api = create_mock_api(
+			RpcClientMock::default().with_response("transaction_v1_broadcast", Some("op")),
+		);
+
+		assert_eq!(
+			api.transaction_broadcast_opaque(&Bytes(vec![1])).unwrap().as_deref(),
+			Some("op")
+		);
+	}
+}
@@ -0,0 +1,185 @@
+/*
+   Copyright 2019 Supercomputing Systems AG
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+	   http://www.apache.org/licenses/LICENSE-2.0
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! The json-rpc methods offered by a node, as reported by `rpc_methods`.
+
+use alloc::{collections::BTreeSet, string::String, vec::Vec};
+use serde::{Deserialize, Serialize};
+
+/// Group of json-rpc methods, which a node either offers as a whole or not at all.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum RpcMethodFamily {
+	/// Legacy `chain_*` methods.
+	Chain,
+	/// Legacy `state_*` methods.
+	State,
+	/// Legacy `author_*` methods.
+	Author,
+	/// `chainHead_v1_*` methods of the new json-rpc specification.
+	ChainHead,
+	/// `archive_v1_*` methods of the new json-rpc specification.
+	Archive,
+	/// `transactionWatch_v1_*` methods of the new json-rpc specification.
+	TransactionWatch,
+	/// `transaction_v1_*` methods of the new json-rpc specification.
+	Transaction,
+}
+
+impl RpcMethodFamily {
+	pub const ALL: [RpcMethodFamily; 7] = [
+		Self::Chain,
+		Self::State,
+		Self::Author,
+		Self::ChainHead,
+		Self::Archive,
+		Self::TransactionWatch,
+		Self::Transaction,
+	];
+
+	/// The prefix shared by all methods of the family.
+	pub fn prefix(&self) -> &'static str {
+		match self {
+			Self::Chain => "chain_",
+			Self::State => "state_",
+			Self::Author => "author_",
+			Self::ChainHead => "chainHead_v1_",
+			Self::Archive => "archive_v1_",
+			Self::TransactionWatch => "transactionWatch_v1_",
+			Self::Transaction => "transaction_v1_",
+		}
+	}
+
+	/// Returns true if the family is part of the new json-rpc specification.
+	pub fn is_new_spec(&self) -> bool {
+		matches!(self, Self::ChainHead | Self::Archive | Self::TransactionWatch | Self::Transaction)
+	}
+}
+
+/// Response of `rpc_methods`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RpcMethods {
+	pub methods: Vec<String>,
+}
+
+/// The json-rpc methods offered by the node the `Api` is connected to.
+///
+/// The `Api` uses them to decide between the legacy methods and the ones of the new json-rpc
+/// specification: The legacy methods are used as long as the node offers them, the new ones take
+/// over for nodes that don't offer the legacy methods anymore. If the methods are unknown, e.g.
+/// because the node does not answer `rpc_methods`, the legacy methods are used.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RpcCapabilities {
+	// `None` if the methods of the node are unknown.
+	methods: Option<BTreeSet<String>>,
+}
+
+impl RpcCapabilities {
+	/// Capabilities of a node offering exactly the given `methods`.
+	pub fn new<I: IntoIterator<Item = S>, S: Into<String>>(methods: I) -> Self {
+		Self { methods: Some(methods.into_iter().map(Into::into).collect()) }
+	}
+
+	/// Capabilities of a node whose methods are not known. All calls use the legacy methods.
+	pub fn unknown() -> Self {
+		Self::default()
+	}
+
+	/// Returns true if the methods of the node are known.
+	pub fn is_known(&self) -> bool {
+		self.methods.is_some()
+	}
+
+	/// All methods offered by the node, or `None` if they are unknown.
+	pub fn methods(&self) -> Option<&BTreeSet<String>> {
+		self.methods.as_ref()
+	}
+
+	/// Returns true if the node is known to offer `method`.
+	pub fn supports(&self, method: &str) -> bool {
+		self.methods.as_ref().is_some_and(|methods| methods.contains(method))
+	}
+
+	/// Returns true if the node is known to offer methods of the `family`.
+	pub fn supports_family(&self, family: RpcMethodFamily) -> bool {
+		self.methods
+			.as_ref()
+			.is_some_and(|methods| methods.iter().any(|m| m.starts_with(family.prefix())))
+	}
+
+	/// All method families the node is known to offer.
+	pub fn families(&self) -> Vec<RpcMethodFamily> {
+		RpcMethodFamily::ALL
+			.into_iter()
+			.filter(|family| self.supports_family(*family))
+			.collect()
+	}
+
+	/// Returns true if `legacy_method` should be used instead of `new_spec_method`, i.e. unless
+	/// the node is known to offer only the latter.
+	pub(crate) fn use_legacy(&self, legacy_method: &str, new_spec_method: &str) -> bool {
+		!self.is_known() || self.supports(legacy_method) || !self.supports(new_spec_method)
+	}
+}
+
+impl From<RpcMethods> for RpcCapabilities {
+	fn from(rpc_methods: RpcMethods) -> Self {
+		Self::new(rpc_methods.methods)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn unknown_capabilities_use_legacy_methods() {
+		let capabilities = RpcCapabilities::unknown();
+
+		assert!(!capabilities.is_known());
+		assert!(!capabilities.supports("chain_getBlock"));
+		assert!(capabilities.families().is_empty());
+		assert!(capabilities.use_legacy("chain_getBlock", "archive_v1_body"));
+	}
+
+	#[test]
+	fn families_are_derived_from_methods() {
+		let rpc_methods: RpcMethods = serde_json::from_str(
+			r#"{"methods":["archive_v1_body","chain_getBlock","transactionWatch_v1_submitAndWatch"]}"#,
+		)
+		.unwrap();
+
+		let capabilities = RpcCapabilities::from(rpc_methods);
+
+		assert_eq!(
+			capabilities.families(),
+			vec![
+				RpcMethodFamily::Chain,
+				RpcMethodFamily::Archive,
+				RpcMethodFamily::TransactionWatch
+			]
+		);
+		assert!(capabilities.supports_family(RpcMethodFamily::Archive));
+		assert!(!capabilities.supports_family(RpcMethodFamily::ChainHead));
+	}
+
+	#[test]
+	fn new_spec_methods_are_used_only_without_legacy_methods() {
+		let both = RpcCapabilities::new(["chain_getBlock", "archive_v1_body"]);
+		let new_spec_only = RpcCapabilities::new(["archive_v1_body"]);
+		let none = RpcCapabilities::new(Vec::<String>::new());
+
+		assert!(both.use_legacy("chain_getBlock", "archive_v1_body"));
+		assert!(!new_spec_only.use_legacy("chain_getBlock", "archive_v1_body"));
+		assert!(none.use_legacy("chain_getBlock", "archive_v1_body"));
+	}
+}
@@ -19,7 +19,7 @@ use alloc::boxed::Box;
 use alloc::vec;
 use sp_core::Encode;
 
-#[maybe_async::maybe_async(?Send)]
+#[ac_async_macros::maybe_async]
 pub trait AccountNonceApi: RuntimeApi {
 	type Index;
 	type AccountId;
@@ -32,7 +32,7 @@ pub trait AccountNonceApi: RuntimeApi {
 	) -> Result<Self::Index>;
 }
 
-#[maybe_async::maybe_async(?Send)]
+#[ac_async_macros::maybe_async]
 impl<T, Client> AccountNonceApi for RuntimeApiClient<T, Client>
 where
 	T: Config,
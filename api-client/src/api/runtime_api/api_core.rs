@@ -19,7 +19,7 @@ use alloc::boxed::Box;
 use alloc::vec;
 use sp_core::{Bytes, Encode};
 
-#[maybe_async::maybe_async(?Send)]
+#[ac_async_macros::maybe_async]
 pub trait CoreApi: RuntimeApi {
 	type Block;
 	type Header;
@@ -42,7 +42,7 @@ pub trait CoreApi: RuntimeApi {
 	async fn version(&self, at_block: Option<Self::Hash>) -> Result<Self::RuntimeVersion>;
 }
 
-#[maybe_async::maybe_async(?Send)]
+#[ac_async_macros::maybe_async]
 impl<T, Client> CoreApi for RuntimeApiClient<T, Client>
 where
 	T: Config,
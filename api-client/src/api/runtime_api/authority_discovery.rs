@@ -19,7 +19,7 @@ use alloc::boxed::Box;
 use alloc::{vec, vec::Vec};
 use codec::Decode;
 
-#[maybe_async::maybe_async(?Send)]
+#[ac_async_macros::maybe_async]
 pub trait AuthorityDiscoveryApi: RuntimeApi {
 	/// Retrieve authority identifiers of the current and next authority set.
 	async fn authorities<AuthorityId: Decode>(
@@ -28,7 +28,7 @@ pub trait AuthorityDiscoveryApi: RuntimeApi {
 	) -> Result<Vec<AuthorityId>>;
 }
 
-#[maybe_async::maybe_async(?Send)]
+#[ac_async_macros::maybe_async]
 impl<T, Client> AuthorityDiscoveryApi for RuntimeApiClient<T, Client>
 where
 	T: Config,
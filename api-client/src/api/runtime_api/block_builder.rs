@@ -13,7 +13,7 @@
 
 use super::{RuntimeApi, RuntimeApiClient};
 use crate::{api::Result, rpc::Request};
-use ac_primitives::{UncheckedExtrinsic, config::Config};
+use ac_primitives::{MaybeSend, UncheckedExtrinsic, config::Config};
 #[cfg(all(not(feature = "sync-api"), not(feature = "std")))]
 use alloc::boxed::Box;
 use alloc::{vec, vec::Vec};
@@ -21,7 +21,7 @@ use sp_core::{Bytes, Encode};
 use sp_inherents::{CheckInherentsResult, InherentData};
 use sp_runtime::ApplyExtrinsicResult;
 
-#[maybe_async::maybe_async(?Send)]
+#[ac_async_macros::maybe_async]
 pub trait BlockBuilderApi: RuntimeApi {
 	type ApplyExtrinsicResult;
 	type Block;
@@ -36,10 +36,10 @@ pub trait BlockBuilderApi: RuntimeApi {
 		at_block: Option<Self::Hash>,
 	) -> Result<Self::ApplyExtrinsicResult>
 	where
-		Address: Encode,
-		Call: Encode,
-		Signature: Encode,
-		TransactionExtension: Encode;
+		Address: Encode + MaybeSend,
+		Call: Encode + MaybeSend,
+		Signature: Encode + MaybeSend,
+		TransactionExtension: Encode + MaybeSend;
 
 	/// Apply the given opaque extrinsic.
 	async fn apply_opaque_extrinsic(
@@ -67,7 +67,7 @@ pub trait BlockBuilderApi: RuntimeApi {
 	) -> Result<Vec<Bytes>>;
 }
 
-#[maybe_async::maybe_async(?Send)]
+#[ac_async_macros::maybe_async]
 impl<T, Client> BlockBuilderApi for RuntimeApiClient<T, Client>
 where
 	T: Config,
@@ -85,10 +85,10 @@ where
 		at_block: Option<Self::Hash>,
 	) -> Result<Self::ApplyExtrinsicResult>
 	where
-		Address: Encode,
-		Call: Encode,
-		Signature: Encode,
-		TransactionExtension: Encode,
+		Address: Encode + MaybeSend,
+		Call: Encode + MaybeSend,
+		Signature: Encode + MaybeSend,
+		TransactionExtension: Encode + MaybeSend,
 	{
 		self.apply_opaque_extrinsic(extrinsic.encode(), at_block).await
 	}
@@ -25,7 +25,7 @@ use alloc::{
 use codec::Decode;
 use sp_core::{Encode, OpaqueMetadata};
 
-#[maybe_async::maybe_async(?Send)]
+#[ac_async_macros::maybe_async]
 pub trait MetadataApi: RuntimeApi {
 	type OpaqueMetadata;
 
@@ -63,7 +63,7 @@ pub trait MetadataApi: RuntimeApi {
 	) -> Result<Vec<String>>;
 }
 
-#[maybe_async::maybe_async(?Send)]
+#[ac_async_macros::maybe_async]
 impl<T, Client> MetadataApi for RuntimeApiClient<T, Client>
 where
 	T: Config,
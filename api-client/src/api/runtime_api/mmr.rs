@@ -20,7 +20,7 @@ use alloc::{vec, vec::Vec};
 use core::result::Result as StdResult;
 use sp_core::Encode;
 
-#[maybe_async::maybe_async(?Send)]
+#[ac_async_macros::maybe_async]
 pub trait MmrApi: RuntimeApi {
 	type Error;
 	type BlockNumber;
@@ -60,7 +60,7 @@ pub trait MmrApi: RuntimeApi {
 	) -> Result<StdResult<(), Self::Error>>;
 }
 
-#[maybe_async::maybe_async(?Send)]
+#[ac_async_macros::maybe_async]
 impl<T, Client> MmrApi for RuntimeApiClient<T, Client>
 where
 	T: Config,
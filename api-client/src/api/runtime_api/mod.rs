@@ -49,7 +49,7 @@ impl<T, Client> RuntimeApiClient<T, Client> {
 	}
 }
 
-#[maybe_async::maybe_async(?Send)]
+#[ac_async_macros::maybe_async]
 pub trait RuntimeApi {
 	type Hash;
 
@@ -78,7 +78,7 @@ pub trait RuntimeApi {
 	) -> Result<Bytes>;
 }
 
-#[maybe_async::maybe_async(?Send)]
+#[ac_async_macros::maybe_async]
 impl<T, Client> RuntimeApi for RuntimeApiClient<T, Client>
 where
 	T: Config,
@@ -20,7 +20,7 @@ use alloc::{vec, vec::Vec};
 use codec::Encode;
 use sp_core::{Bytes, crypto::KeyTypeId};
 
-#[maybe_async::maybe_async(?Send)]
+#[ac_async_macros::maybe_async]
 pub trait SessionKeysApi: RuntimeApi {
 	type KeyTypeId;
 
@@ -40,7 +40,7 @@ pub trait SessionKeysApi: RuntimeApi {
 	) -> Result<Bytes>;
 }
 
-#[maybe_async::maybe_async(?Send)]
+#[ac_async_macros::maybe_async]
 impl<T, Client> SessionKeysApi for RuntimeApiClient<T, Client>
 where
 	T: Config,
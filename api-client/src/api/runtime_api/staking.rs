@@ -19,7 +19,7 @@ use alloc::boxed::Box;
 use alloc::vec;
 use sp_core::Encode;
 
-#[maybe_async::maybe_async(?Send)]
+#[ac_async_macros::maybe_async]
 pub trait StakingApi: RuntimeApi {
 	type Balance;
 
@@ -31,7 +31,7 @@ pub trait StakingApi: RuntimeApi {
 	) -> Result<u32>;
 }
 
-#[maybe_async::maybe_async(?Send)]
+#[ac_async_macros::maybe_async]
 impl<T, Client> StakingApi for RuntimeApiClient<T, Client>
 where
 	T: Config,
@@ -13,13 +13,15 @@
 
 use super::{RuntimeApi, RuntimeApiClient};
 use crate::{api::Result, rpc::Request};
-use ac_primitives::{FeeDetails, RuntimeDispatchInfo, UncheckedExtrinsic, Weight, config::Config};
+use ac_primitives::{
+	FeeDetails, MaybeSend, RuntimeDispatchInfo, UncheckedExtrinsic, Weight, config::Config,
+};
 #[cfg(all(not(feature = "sync-api"), not(feature = "std")))]
 use alloc::boxed::Box;
 use alloc::{vec, vec::Vec};
 use sp_core::Encode;
 
-#[maybe_async::maybe_async(?Send)]
+#[ac_async_macros::maybe_async]
 pub trait TransactionPaymentApi: RuntimeApi {
 	type FeeDetails;
 	type RuntimeDispatchInfo;
@@ -34,10 +36,10 @@ pub trait TransactionPaymentApi: RuntimeApi {
 		at_block: Option<Self::Hash>,
 	) -> Result<Self::FeeDetails>
 	where
-		Address: Encode,
-		Call: Encode,
-		Signature: Encode,
-		TransactionExtension: Encode;
+		Address: Encode + MaybeSend,
+		Call: Encode + MaybeSend,
+		Signature: Encode + MaybeSend,
+		TransactionExtension: Encode + MaybeSend;
 
 	/// Query the transaction fee details of opaque extrinsic.
 	async fn query_fee_details_opaque(
@@ -55,10 +57,10 @@ pub trait TransactionPaymentApi: RuntimeApi {
 		at_block: Option<Self::Hash>,
 	) -> Result<Self::RuntimeDispatchInfo>
 	where
-		Address: Encode,
-		Call: Encode,
-		Signature: Encode,
-		TransactionExtension: Encode;
+		Address: Encode + MaybeSend,
+		Call: Encode + MaybeSend,
+		Signature: Encode + MaybeSend,
+		TransactionExtension: Encode + MaybeSend;
 
 	/// Query the transaction info of opaque extrinsic.
 	async fn query_info_opaque(
@@ -83,7 +85,7 @@ pub trait TransactionPaymentApi: RuntimeApi {
 	) -> Result<Self::Balance>;
 }
 
-#[maybe_async::maybe_async(?Send)]
+#[ac_async_macros::maybe_async]
 impl<T, Client> TransactionPaymentApi for RuntimeApiClient<T, Client>
 where
 	T: Config,
@@ -101,10 +103,10 @@ where
 		at_block: Option<Self::Hash>,
 	) -> Result<Self::FeeDetails>
 	where
-		Address: Encode,
-		Call: Encode,
-		Signature: Encode,
-		TransactionExtension: Encode,
+		Address: Encode + MaybeSend,
+		Call: Encode + MaybeSend,
+		Signature: Encode + MaybeSend,
+		TransactionExtension: Encode + MaybeSend,
 	{
 		self.query_fee_details_opaque(extrinsic.encode(), length, at_block).await
 	}
@@ -130,10 +132,10 @@ where
 		at_block: Option<Self::Hash>,
 	) -> Result<Self::RuntimeDispatchInfo>
 	where
-		Address: Encode,
-		Call: Encode,
-		Signature: Encode,
-		TransactionExtension: Encode,
+		Address: Encode + MaybeSend,
+		Call: Encode + MaybeSend,
+		Signature: Encode + MaybeSend,
+		TransactionExtension: Encode + MaybeSend,
 	{
 		self.query_info_opaque(extrinsic.encode(), length, at_block).await
 	}
@@ -13,13 +13,13 @@
 
 use super::{RuntimeApi, RuntimeApiClient};
 use crate::{api::Result, rpc::Request};
-use ac_primitives::{FeeDetails, RuntimeDispatchInfo, Weight, config::Config};
+use ac_primitives::{FeeDetails, MaybeSend, RuntimeDispatchInfo, Weight, config::Config};
 #[cfg(all(not(feature = "sync-api"), not(feature = "std")))]
 use alloc::boxed::Box;
 use alloc::vec;
 use sp_core::Encode;
 
-#[maybe_async::maybe_async(?Send)]
+#[ac_async_macros::maybe_async]
 pub trait TransactionPaymentCallApi: RuntimeApi {
 	type FeeDetails;
 	type RuntimeDispatchInfo;
@@ -27,7 +27,7 @@ pub trait TransactionPaymentCallApi: RuntimeApi {
 	type Weight;
 
 	/// Query the call fee details.
-	async fn query_call_fee_details<Call: Encode>(
+	async fn query_call_fee_details<Call: Encode + MaybeSend>(
 		&self,
 		call: Call,
 		length: u32,
@@ -35,7 +35,7 @@ pub trait TransactionPaymentCallApi: RuntimeApi {
 	) -> Result<Self::FeeDetails>;
 
 	/// Query the call info
-	async fn query_call_info<Call: Encode>(
+	async fn query_call_info<Call: Encode + MaybeSend>(
 		&self,
 		call: Call,
 		length: u32,
@@ -57,7 +57,7 @@ pub trait TransactionPaymentCallApi: RuntimeApi {
 	) -> Result<Self::Balance>;
 }
 
-#[maybe_async::maybe_async(?Send)]
+#[ac_async_macros::maybe_async]
 impl<T, Client> TransactionPaymentCallApi for RuntimeApiClient<T, Client>
 where
 	T: Config,
@@ -68,7 +68,7 @@ where
 	type Balance = T::Balance;
 	type Weight = Weight;
 
-	async fn query_call_fee_details<Call: Encode>(
+	async fn query_call_fee_details<Call: Encode + MaybeSend>(
 		&self,
 		call: Call,
 		length: u32,
@@ -82,7 +82,7 @@ where
 		.await
 	}
 
-	async fn query_call_info<Call: Encode>(
+	async fn query_call_info<Call: Encode + MaybeSend>(
 		&self,
 		call: Call,
 		length: u32,
@@ -0,0 +1,244 @@
+/*
+   Copyright 2019 Supercomputing Systems AG
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+	   http://www.apache.org/licenses/LICENSE-2.0
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Metadata and runtime version shared between api instances, which are kept up to date
+//! upon runtime upgrades in the background.
+
+use crate::{
+	api::{Api, MetadataCache, Result, api_client::decode_metadata},
+	rpc::{HandleSubscription, Request, Subscribe},
+};
+use ac_compose_macros::rpc_params;
+use ac_node_api::metadata::Metadata;
+use ac_primitives::Config;
+use log::*;
+use sp_version::RuntimeVersion;
+use std::sync::{Arc, RwLock, RwLockReadGuard};
+
+/// Metadata and runtime version of the node, shared by all clones of an api and the event
+/// subscriptions created from them.
+///
+/// Both are swapped in together, so a reader never sees the metadata of one runtime together
+/// with the version of another.
+#[derive(Debug, Clone)]
+pub struct SharedRuntime {
+	runtime: Arc<RwLock<(Arc<Metadata>, RuntimeVersion)>>,
+}
+
+impl SharedRuntime {
+	pub fn new(metadata: Arc<Metadata>, runtime_version: RuntimeVersion) -> Self {
+		Self { runtime: Arc::new(RwLock::new((metadata, runtime_version))) }
+	}
+
+	/// Get the current metadata.
+	pub fn metadata(&self) -> Arc<Metadata> {
+		self.read().0.clone()
+	}
+
+	/// Get the current runtime version.
+	pub fn runtime_version(&self) -> RuntimeVersion {
+		self.read().1.clone()
+	}
+
+	/// Get the current metadata together with the runtime version it belongs to.
+	pub fn snapshot(&self) -> (Arc<Metadata>, RuntimeVersion) {
+		self.read().clone()
+	}
+
+	/// Replace the metadata and runtime version of all api instances sharing the runtime.
+	pub fn update(&self, metadata: Metadata, runtime_version: RuntimeVersion) {
+		self.set(Arc::new(metadata), runtime_version)
+	}
+
+	pub(crate) fn set(&self, metadata: Arc<Metadata>, runtime_version: RuntimeVersion) {
+		// Readers only ever clone the content, so it stays consistent even if one panicked.
+		let mut runtime = self.runtime.write().unwrap_or_else(|poisoned| poisoned.into_inner());
+		*runtime = (metadata, runtime_version);
+	}
+
+	fn read(&self) -> RwLockReadGuard<'_, (Arc<Metadata>, RuntimeVersion)> {
+		self.runtime.read().unwrap_or_else(|poisoned| poisoned.into_inner())
+	}
+}
+
+/// Task updating a [`SharedRuntime`] whenever the node reports a runtime upgrade via
+/// `state_subscribeRuntimeVersion`. Created with [`Api::runtime_updater`].
+///
+/// The task runs until the subscription ends or fails. It is meant to be run in the
+/// background, e.g. in a separate thread in sync mode, or with `tokio::task::spawn_local`
+/// in async mode. With the `send-futures` feature, `tokio::spawn` works as well.
+pub struct RuntimeUpdater<T: Config, Client> {
+	client: Arc<Client>,
+	shared_runtime: SharedRuntime,
+	genesis_hash: T::Hash,
+	metadata_cache: Option<MetadataCache>,
+}
+
+impl<T: Config, Client> RuntimeUpdater<T, Client> {
+	pub(crate) fn new(
+		client: Arc<Client>,
+		shared_runtime: SharedRuntime,
+		genesis_hash: T::Hash,
+		metadata_cache: Option<MetadataCache>,
+	) -> Self {
+		Self { client, shared_runtime, genesis_hash, metadata_cache }
+	}
+
+	/// The runtime kept up to date by the updater.
+	pub fn shared_runtime(&self) -> &SharedRuntime {
+		&self.shared_runtime
+	}
+}
+
+impl<T, Client> RuntimeUpdater<T, Client>
+where
+	T: Config,
+	Client: Request + Subscribe,
+{
+	/// Subscribe to the runtime version and swap in the new metadata upon every upgrade.
+	/// Returns once the node ends the subscription, or with the first error encountered.
+	#[maybe_async::maybe_async(?Send)]
+	pub async fn run(self) -> Result<()> {
+		let mut subscription: Client::Subscription<RuntimeVersion> = self
+			.client
+			.subscribe(
+				"state_subscribeRuntimeVersion",
+				rpc_params![],
+				"state_unsubscribeRuntimeVersion",
+			)
+			.await?;
+		while let Some(runtime_version) = subscription.next().await {
+			self.update_runtime(runtime_version?).await?;
+		}
+		Ok(())
+	}
+
+	#[maybe_async::maybe_async(?Send)]
+	async fn update_runtime(&self, runtime_version: RuntimeVersion) -> Result<()> {
+		// The subscription starts with the current runtime version.
+		if self.shared_runtime.runtime_version() == runtime_version {
+			return Ok(())
+		}
+		let (metadata, runtime_version) = match &self.metadata_cache {
+			Some(metadata_cache) =>
+				Api::<T, Client>::get_cached_metadata(
+					&self.client,
+					metadata_cache,
+					self.genesis_hash,
+					&runtime_version,
+				)
+				.await?,
+			None => {
+				let (metadata_bytes, runtime_version) =
+					Api::<T, Client>::get_latest_runtime(&self.client).await?;
+				(decode_metadata(&metadata_bytes)?, runtime_version)
+			},
+		};
+		info!("Runtime upgraded to version: {runtime_version:?}");
+		self.shared_runtime.update(metadata, runtime_version);
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		api::{api_client::KSM_V14_METADATA_PATH, rpc_api::SubscribeEvents},
+		rpc::mocks::{RpcClientMock, create_mock_api_with_metadata},
+	};
+	use ac_primitives::DefaultRuntimeConfig;
+	use codec::Decode;
+	use frame_metadata::{RuntimeMetadata, RuntimeMetadataPrefixed, v14::ExtrinsicMetadata};
+	use scale_info::form::PortableForm;
+	use sp_core::{Bytes, H256};
+	use sp_storage::StorageChangeSet;
+	use std::fs;
+
+	fn encoded_metadata() -> Bytes {
+		fs::read(KSM_V14_METADATA_PATH).unwrap().into()
+	}
+
+	fn metadata() -> Metadata {
+		let metadata = RuntimeMetadataPrefixed::decode(&mut encoded_metadata().as_ref()).unwrap();
+		Metadata::try_from(metadata).unwrap()
+	}
+
+	// Metadata of a runtime without signed extensions, to tell it apart from `metadata()`.
+	fn previous_metadata() -> Metadata {
+		let mut metadata =
+			RuntimeMetadataPrefixed::decode(&mut encoded_metadata().as_ref()).unwrap();
+		if let RuntimeMetadata::V14(ref mut runtime_metadata) = metadata.1 {
+			runtime_metadata.extrinsic = ExtrinsicMetadata::<PortableForm> {
+				ty: runtime_metadata.extrinsic.ty,
+				version: 0,
+				signed_extensions: Vec::new(),
+			};
+		}
+		Metadata::try_from(metadata).unwrap()
+	}
+
+	fn create_shared_api(client: RpcClientMock) -> Api<DefaultRuntimeConfig, RpcClientMock> {
+		let mut api = create_mock_api_with_metadata(previous_metadata(), client);
+		api.enable_shared_runtime();
+		api
+	}
+
+	#[test]
+	fn runtime_updater_swaps_in_upgraded_runtime() {
+		let upgraded_version = RuntimeVersion { spec_version: 10, ..Default::default() };
+		let at = H256::repeat_byte(2);
+		// The runtime version and the metadata are fetched at the same block.
+		let client = RpcClientMock::default()
+			.with_subscription(
+				"state_subscribeRuntimeVersion",
+				vec![RuntimeVersion::default(), upgraded_version.clone()],
+			)
+			.with_response_for("chain_getBlockHash", rpc_params![], Some(at))
+			.with_response_for("state_getRuntimeVersion", rpc_params![Some(at)], &upgraded_version)
+			.with_response_for("state_getMetadata", rpc_params![Some(at)], encoded_metadata());
+		let mut api = create_shared_api(client);
+		let shared_api = api.clone();
+
+		api.runtime_updater().run().unwrap();
+
+		assert_eq!(shared_api.runtime_version_snapshot(), upgraded_version);
+		assert_eq!(shared_api.metadata_snapshot().extrinsic(), metadata().extrinsic());
+	}
+
+	#[test]
+	fn runtime_updater_ignores_current_runtime_version() {
+		// Fetching the runtime fails, because `chain_getBlockHash` is not mocked.
+		let client = RpcClientMock::default()
+			.with_subscription("state_subscribeRuntimeVersion", vec![RuntimeVersion::default()]);
+		let mut api = create_shared_api(client);
+
+		api.runtime_updater().run().unwrap();
+
+		assert_eq!(api.metadata_snapshot().extrinsic(), previous_metadata().extrinsic());
+	}
+
+	#[test]
+	fn event_subscription_picks_up_upgraded_metadata() {
+		let client = RpcClientMock::default()
+			.with_subscription("state_subscribeStorage", Vec::<StorageChangeSet<H256>>::new());
+		let api = create_shared_api(client);
+		let subscription = api.subscribe_events().unwrap();
+
+		api.shared_runtime()
+			.unwrap()
+			.update(metadata(), RuntimeVersion { spec_version: 10, ..Default::default() });
+
+		assert_eq!(subscription.current_metadata().extrinsic(), metadata().extrinsic());
+	}
+}
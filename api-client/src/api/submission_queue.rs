@@ -0,0 +1,587 @@
+/*
+   Copyright 2019 Supercomputing Systems AG
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+	   http://www.apache.org/licenses/LICENSE-2.0
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Submission of many extrinsics of the api signer, with several of them being watched at
+//! the same time.
+
+use crate::{
+	api::{
+		Api, Error, ExtrinsicReport, Result, SubmitAndWatch, TransactionStatus, UnexpectedTxStatus,
+		XtStatus, rpc_api::TransactionSubscriptionFor,
+	},
+	rpc::{HandleSubscription, Request, Subscribe},
+};
+use ac_primitives::config::Config;
+use codec::Encode;
+#[cfg(not(feature = "sync-api"))]
+use futures_util::stream::{FuturesUnordered, Stream, StreamExt};
+use log::*;
+use sp_core::Bytes;
+use sp_runtime::traits::Hash as HashTrait;
+use std::collections::{HashMap, VecDeque};
+#[cfg(feature = "sync-api")]
+use std::{
+	sync::mpsc::{Receiver, Sender, channel},
+	thread,
+};
+
+/// Outcome of a call of the [`SubmissionQueue`], together with the index of the call in the
+/// order it has been pushed to the queue.
+pub type SubmissionOutcome<Hash> = (usize, Result<ExtrinsicReport<Hash>>);
+
+/// Final status of a watched extrinsic, together with the index of its call.
+type WatchResult<Hash> = (usize, Result<TransactionStatus<Hash, Hash>>);
+
+#[cfg(all(not(feature = "sync-api"), feature = "send-futures"))]
+type WatchFuture<'a, Hash> = futures_util::future::BoxFuture<'a, WatchResult<Hash>>;
+#[cfg(all(not(feature = "sync-api"), not(feature = "send-futures")))]
+type WatchFuture<'a, Hash> = futures_util::future::LocalBoxFuture<'a, WatchResult<Hash>>;
+
+/// Subscriptions of the extrinsics watched by the [`SubmissionQueue`]. In sync mode, every
+/// extrinsic is watched in a separate thread, so its subscription needs to be sent there.
+#[cfg(feature = "sync-api")]
+pub trait WatchSubscription: Send + 'static {}
+#[cfg(feature = "sync-api")]
+impl<S: Send + 'static> WatchSubscription for S {}
+
+/// Subscriptions of the extrinsics watched by the [`SubmissionQueue`]. In async mode, they
+/// stay in the task polling the queue.
+#[cfg(not(feature = "sync-api"))]
+pub trait WatchSubscription {}
+#[cfg(not(feature = "sync-api"))]
+impl<S> WatchSubscription for S {}
+
+/// Defines which watch outcomes lead to the extrinsic being submitted again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResubmitPolicy {
+	/// How often a call is submitted again at most.
+	pub max_resubmissions: u32,
+	/// Submit the unchanged extrinsic again, if it has been dropped from the transaction pool.
+	pub on_dropped: bool,
+	/// Submit the call again with a new nonce, if another extrinsic with the same nonce has
+	/// replaced it in the transaction pool.
+	pub on_usurped: bool,
+	/// Submit the unchanged extrinsic again, if the node stopped watching it before it has
+	/// been finalized. If it has been finalized in the meantime, the node rejects it as stale.
+	pub on_finality_timeout: bool,
+}
+
+impl Default for ResubmitPolicy {
+	fn default() -> Self {
+		Self { max_resubmissions: 3, on_dropped: true, on_usurped: true, on_finality_timeout: true }
+	}
+}
+
+impl ResubmitPolicy {
+	/// Never submit an extrinsic again.
+	pub fn never() -> Self {
+		Self {
+			max_resubmissions: 0,
+			on_dropped: false,
+			on_usurped: false,
+			on_finality_timeout: false,
+		}
+	}
+
+	/// Returns true if an extrinsic that ended with `status` is to be submitted again.
+	pub fn resubmits(&self, status: UnexpectedTxStatus) -> bool {
+		match status {
+			UnexpectedTxStatus::Dropped => self.on_dropped,
+			UnexpectedTxStatus::Usurped => self.on_usurped,
+			UnexpectedTxStatus::FinalityTimeout => self.on_finality_timeout,
+			_ => false,
+		}
+	}
+}
+
+/// Queue submitting calls signed by the api signer, with nonces handed out by the
+/// [`NonceManager`](crate::NonceManager) of the api. Created with [`Api::submission_queue`].
+///
+/// Up to `max_in_flight` extrinsics are submitted and watched with
+/// `author_submitAndWatchExtrinsic` at the same time. The outcome of each call is returned by
+/// [`Self::next_outcome`] as soon as its extrinsic reaches its final status, so a later call
+/// may finish before an earlier one. In sync mode, every extrinsic is watched in a separate
+/// thread. Extrinsics that end as `Dropped`, `Usurped` or `FinalityTimeout` are submitted again
+/// according to the [`ResubmitPolicy`], before any further call. If the node rejects an
+/// extrinsic due to its nonce, the nonce is synced with the node again.
+pub struct SubmissionQueue<'a, T: Config, Client, Call> {
+	api: &'a Api<T, Client>,
+	account: T::AccountId,
+	pending: VecDeque<Submission<T::Index, Call>>,
+	// Watched extrinsics, by the index of their call.
+	in_flight: HashMap<usize, InFlight<T, Call>>,
+	#[cfg(feature = "sync-api")]
+	watches: (Sender<WatchResult<T::Hash>>, Receiver<WatchResult<T::Hash>>),
+	#[cfg(not(feature = "sync-api"))]
+	watches: FuturesUnordered<WatchFuture<'a, T::Hash>>,
+	pushed: usize,
+	max_in_flight: usize,
+	watch_until: XtStatus,
+	resubmit_policy: ResubmitPolicy,
+}
+
+struct Submission<Index, Call> {
+	index: usize,
+	call: Call,
+	resubmissions: u32,
+	// Nonce and encoding of a previous submission, which is to be submitted again unchanged.
+	extrinsic: Option<(Index, Bytes)>,
+}
+
+struct InFlight<T: Config, Call> {
+	submission: Submission<T::Index, Call>,
+	nonce: T::Index,
+	encoded_extrinsic: Bytes,
+	extrinsic_hash: T::Hash,
+}
+
+impl<T, Client> Api<T, Client>
+where
+	T: Config,
+	T::Hash: 'static,
+	Client: Subscribe + Request,
+	TransactionSubscriptionFor<Client, T::Hash>: WatchSubscription,
+{
+	/// Create a [`SubmissionQueue`] for calls signed by the api signer.
+	pub fn submission_queue<Call: Encode + Clone>(
+		&self,
+	) -> Result<SubmissionQueue<'_, T, Client, Call>> {
+		let account = self.signer_account().ok_or(Error::NoSigner)?.clone();
+		Ok(SubmissionQueue::new(self, account))
+	}
+}
+
+impl<'a, T, Client, Call> SubmissionQueue<'a, T, Client, Call>
+where
+	T: Config,
+	T::Hash: 'static,
+	Client: Subscribe + Request,
+	TransactionSubscriptionFor<Client, T::Hash>: WatchSubscription,
+	Call: Encode + Clone,
+{
+	fn new(api: &'a Api<T, Client>, account: T::AccountId) -> Self {
+		Self {
+			api,
+			account,
+			pending: VecDeque::new(),
+			in_flight: HashMap::new(),
+			#[cfg(feature = "sync-api")]
+			watches: channel(),
+			#[cfg(not(feature = "sync-api"))]
+			watches: FuturesUnordered::new(),
+			pushed: 0,
+			max_in_flight: 16,
+			watch_until: XtStatus::InBlock,
+			resubmit_policy: ResubmitPolicy::default(),
+		}
+	}
+
+	/// Set the maximum number of extrinsics watched at the same time. Defaults to 16.
+	pub fn max_in_flight(mut self, max_in_flight: usize) -> Self {
+		self.max_in_flight = max_in_flight.max(1);
+		self
+	}
+
+	/// Set the status until which the extrinsics are watched. Defaults to `InBlock`.
+	/// If watched until at least `InBlock`, the events are attached to the reports and
+	/// extrinsics that failed on chain are reported as error.
+	pub fn watch_until(mut self, watch_until: XtStatus) -> Self {
+		self.watch_until = watch_until;
+		self
+	}
+
+	/// Set the policy deciding which extrinsics are submitted again.
+	pub fn resubmit_policy(mut self, resubmit_policy: ResubmitPolicy) -> Self {
+		self.resubmit_policy = resubmit_policy;
+		self
+	}
+
+	/// Add a call to the queue. Returns its index, which identifies its outcome.
+	pub fn push(&mut self, call: Call) -> usize {
+		let index = self.pushed;
+		self.pushed += 1;
+		self.pending
+			.push_back(Submission { index, call, resubmissions: 0, extrinsic: None });
+		index
+	}
+
+	/// Number of calls whose outcome has not been returned yet.
+	pub fn len(&self) -> usize {
+		self.pending.len() + self.in_flight.len()
+	}
+
+	/// Returns true if the outcomes of all calls have been returned.
+	pub fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+
+	/// Submit pending calls until `max_in_flight` extrinsics are watched and return the next
+	/// outcome, of whichever watched extrinsic reaches its final status first. Returns `None`
+	/// once the outcomes of all calls have been returned.
+	#[maybe_async::maybe_async(?Send)]
+	pub async fn next_outcome(&mut self) -> Option<SubmissionOutcome<T::Hash>> {
+		loop {
+			while self.in_flight.len() < self.max_in_flight {
+				let Some(submission) = self.pending.pop_front() else { break };
+				if let Some(outcome) = self.submit(submission).await {
+					return Some(outcome)
+				}
+			}
+			let (index, status) = self.next_watch_result().await?;
+			let in_flight = self.in_flight.remove(&index)?;
+			if let Some(outcome) = self.handle_final_status(in_flight, status).await {
+				return Some(outcome)
+			}
+		}
+	}
+
+	/// Turn the queue into a stream of the outcomes of its calls, see [`Self::next_outcome`].
+	#[cfg(not(feature = "sync-api"))]
+	pub fn into_outcomes(self) -> impl Stream<Item = SubmissionOutcome<T::Hash>> {
+		futures_util::stream::unfold(self, |mut queue| async move {
+			let outcome = queue.next_outcome().await?;
+			Some((outcome, queue))
+		})
+	}
+
+	/// Turn the queue into an iterator over the outcomes of its calls, see
+	/// [`Self::next_outcome`].
+	#[cfg(feature = "sync-api")]
+	pub fn into_outcomes(mut self) -> impl Iterator<Item = SubmissionOutcome<T::Hash>> {
+		std::iter::from_fn(move || self.next_outcome())
+	}
+
+	/// Returns the outcome if the call has not been submitted and won't be submitted again.
+	#[maybe_async::maybe_async(?Send)]
+	async fn submit(
+		&mut self,
+		mut submission: Submission<T::Index, Call>,
+	) -> Option<SubmissionOutcome<T::Hash>> {
+		let index = submission.index;
+		let is_resubmission = submission.extrinsic.is_some();
+		let (nonce, encoded_extrinsic) = match submission.extrinsic.take() {
+			Some(extrinsic) => extrinsic,
+			None => {
+				let nonce = match self.api.get_managed_nonce_for(&self.account).await {
+					Ok(nonce) => nonce,
+					Err(e) => return Some((index, Err(e))),
+				};
+				let extrinsic = self.api.compose_extrinsic_offline(submission.call.clone(), nonce);
+				(nonce, extrinsic.encode().into())
+			},
+		};
+		let extrinsic_hash = T::Hasher::hash(&encoded_extrinsic);
+		let error = match self.api.submit_and_watch_opaque_extrinsic(&encoded_extrinsic).await {
+			Ok(subscription) => {
+				self.watch(index, extrinsic_hash, subscription);
+				self.in_flight.insert(
+					index,
+					InFlight { submission, nonce, encoded_extrinsic, extrinsic_hash },
+				);
+				return None
+			},
+			Err(e) => e,
+		};
+		if let Err(e) = self.api.release_managed_nonce(&self.account, nonce, &error).await {
+			return Some((index, Err(e)))
+		}
+		// Only nonce errors are worth another try. An unchanged extrinsic might have been
+		// rejected because it has been included in the meantime, so only freshly composed ones
+		// are composed again with the synced nonce.
+		if !error.requires_nonce_resync()
+			|| is_resubmission
+			|| submission.resubmissions >= self.resubmit_policy.max_resubmissions
+		{
+			return Some((index, Err(error)))
+		}
+		self.requeue(submission, None);
+		None
+	}
+
+	/// Watch the extrinsic in a separate thread, as waiting for its status blocks in sync mode.
+	#[cfg(feature = "sync-api")]
+	fn watch(
+		&self,
+		index: usize,
+		extrinsic_hash: T::Hash,
+		subscription: TransactionSubscriptionFor<Client, T::Hash>,
+	) {
+		let sender = self.watches.0.clone();
+		let watch_until = self.watch_until;
+		thread::spawn(move || {
+			let status = watch_extrinsic::<T, Client>(subscription, extrinsic_hash, watch_until);
+			// Fails only if the queue has been dropped in the meantime.
+			let _ = sender.send((index, status));
+		});
+	}
+
+	/// Watch the extrinsic together with all others, whenever the queue waits for an outcome.
+	#[cfg(not(feature = "sync-api"))]
+	fn watch(
+		&mut self,
+		index: usize,
+		extrinsic_hash: T::Hash,
+		subscription: TransactionSubscriptionFor<Client, T::Hash>,
+	) {
+		let watch_until = self.watch_until;
+		self.watches.push(Box::pin(async move {
+			(index, watch_extrinsic::<T, Client>(subscription, extrinsic_hash, watch_until).await)
+		}));
+	}
+
+	/// Wait for the next watched extrinsic to reach its final status.
+	#[maybe_async::sync_impl]
+	fn next_watch_result(&mut self) -> Option<WatchResult<T::Hash>> {
+		if self.in_flight.is_empty() {
+			return None
+		}
+		// The queue holds a sender itself, so receiving can't fail.
+		self.watches.1.recv().ok()
+	}
+
+	/// Wait for the next watched extrinsic to reach its final status.
+	#[maybe_async::async_impl(?Send)]
+	async fn next_watch_result(&mut self) -> Option<WatchResult<T::Hash>> {
+		self.watches.next().await
+	}
+
+	/// Returns the outcome if the watch ended with a final result.
+	#[maybe_async::maybe_async(?Send)]
+	async fn handle_final_status(
+		&mut self,
+		in_flight: InFlight<T, Call>,
+		status: Result<TransactionStatus<T::Hash, T::Hash>>,
+	) -> Option<SubmissionOutcome<T::Hash>> {
+		let index = in_flight.submission.index;
+		let status = match status {
+			Ok(status) => status,
+			Err(e) => return Some((index, Err(e))),
+		};
+		let unexpected_status = match status.is_expected() {
+			Ok(()) => return Some((index, self.report(in_flight.extrinsic_hash, status).await)),
+			Err(Error::UnexpectedTxStatus(unexpected_status)) => unexpected_status,
+			Err(e) => return Some((index, Err(e))),
+		};
+		let error = Error::UnexpectedTxStatus(unexpected_status);
+		if !self.resubmit_policy.resubmits(unexpected_status)
+			|| in_flight.submission.resubmissions >= self.resubmit_policy.max_resubmissions
+		{
+			if let Err(e) =
+				self.api.release_managed_nonce(&self.account, in_flight.nonce, &error).await
+			{
+				return Some((index, Err(e)))
+			}
+			return Some((index, Err(error)))
+		}
+		if error.requires_nonce_resync() {
+			if let Err(e) = self.api.resync_nonce(&self.account).await {
+				return Some((index, Err(e)))
+			}
+		}
+		debug!("Submitting extrinsic {index} again, because it ended as {unexpected_status:?}");
+		let extrinsic = match unexpected_status {
+			// The nonce has been used by another extrinsic.
+			UnexpectedTxStatus::Usurped => None,
+			_ => Some((in_flight.nonce, in_flight.encoded_extrinsic)),
+		};
+		self.requeue(in_flight.submission, extrinsic);
+		None
+	}
+
+	#[maybe_async::maybe_async(?Send)]
+	async fn report(
+		&self,
+		extrinsic_hash: T::Hash,
+		status: TransactionStatus<T::Hash, T::Hash>,
+	) -> Result<ExtrinsicReport<T::Hash>> {
+		let block_hash = status.get_maybe_block_hash().copied();
+		let mut report = ExtrinsicReport::new(extrinsic_hash, block_hash, status, None);
+		if self.watch_until >= XtStatus::InBlock {
+			self.api.populate_events(&mut report).await?;
+			report.check_events_for_dispatch_error(&self.api.metadata_snapshot())?;
+		}
+		Ok(report)
+	}
+
+	// Resubmissions go first, so the nonces are used in order as far as possible.
+	fn requeue(
+		&mut self,
+		mut submission: Submission<T::Index, Call>,
+		extrinsic: Option<(T::Index, Bytes)>,
+	) {
+		submission.resubmissions += 1;
+		submission.extrinsic = extrinsic;
+		self.pending.push_front(submission);
+	}
+}
+
+/// Watch the extrinsic until its final status and stop watching it then.
+#[maybe_async::maybe_async(?Send)]
+async fn watch_extrinsic<T: Config, Client: Subscribe>(
+	mut subscription: TransactionSubscriptionFor<Client, T::Hash>,
+	extrinsic_hash: T::Hash,
+	watch_until: XtStatus,
+) -> Result<TransactionStatus<T::Hash, T::Hash>> {
+	let status = next_final_status::<T, Client>(&mut subscription, watch_until).await;
+	if let Err(e) = subscription.unsubscribe().await {
+		debug!("Could not unsubscribe from extrinsic {extrinsic_hash:?}: {e:?}");
+	}
+	status
+}
+
+/// Wait for the status the extrinsic is watched until, or for an unexpected one.
+#[maybe_async::maybe_async(?Send)]
+async fn next_final_status<T: Config, Client: Subscribe>(
+	subscription: &mut TransactionSubscriptionFor<Client, T::Hash>,
+	watch_until: XtStatus,
+) -> Result<TransactionStatus<T::Hash, T::Hash>> {
+	while let Some(status) = subscription.next().await {
+		let status = status?;
+		if status.is_expected().is_err() || status.reached_status(watch_until) {
+			return Ok(status)
+		}
+	}
+	Err(Error::NoStream)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::rpc::mocks::{RpcClientMock, create_mock_api};
+	use ac_primitives::{DefaultRuntimeConfig, ExtrinsicSigner};
+	use sp_core::{H256, Pair, sr25519};
+
+	type Status = TransactionStatus<H256, H256>;
+
+	fn create_signed_api(client: RpcClientMock) -> Api<DefaultRuntimeConfig, RpcClientMock> {
+		let mut api = create_mock_api(client);
+		let signer = sr25519::Pair::from_string("//Alice", None).unwrap();
+		api.set_signer(ExtrinsicSigner::<DefaultRuntimeConfig>::new(signer));
+		api
+	}
+
+	fn watch_mock(statuses: Vec<Status>) -> RpcClientMock {
+		RpcClientMock::default()
+			.with_response("system_accountNextIndex", 3u32)
+			.with_subscription("author_submitAndWatchExtrinsic", statuses)
+	}
+
+	fn collect_outcomes<Call: Encode + Clone>(
+		queue: SubmissionQueue<'_, DefaultRuntimeConfig, RpcClientMock, Call>,
+	) -> Vec<SubmissionOutcome<H256>> {
+		queue.into_outcomes().collect()
+	}
+
+	#[test]
+	fn calls_are_submitted_with_managed_nonces() {
+		let api = create_signed_api(watch_mock(vec![Status::Ready]));
+		let mut queue = api.submission_queue().unwrap().watch_until(XtStatus::Ready);
+		for call in 0..3u8 {
+			queue.push(call);
+		}
+
+		let outcomes = collect_outcomes(queue);
+
+		// The extrinsics are watched at the same time, so their outcomes may come in any order.
+		let mut indices: Vec<_> = outcomes.iter().map(|(index, _)| *index).collect();
+		indices.sort();
+		assert_eq!(indices, vec![0, 1, 2]);
+		let reports: Vec<_> = outcomes.into_iter().map(|(_, report)| report.unwrap()).collect();
+		assert!(reports.iter().all(|report| report.status == Status::Ready));
+		assert_ne!(reports[0].extrinsic_hash, reports[1].extrinsic_hash);
+		let signer_account = api.signer_account().unwrap();
+		assert_eq!(api.nonce_manager().peek_nonce(signer_account), Some(6));
+	}
+
+	#[test]
+	fn dropped_extrinsic_is_resubmitted_and_its_nonce_reclaimed() {
+		let api = create_signed_api(watch_mock(vec![Status::Dropped]));
+		let policy = ResubmitPolicy { max_resubmissions: 2, ..Default::default() };
+		let mut queue = api.submission_queue().unwrap().resubmit_policy(policy);
+		queue.push(0u8);
+
+		let outcomes = collect_outcomes(queue);
+
+		assert_eq!(outcomes.len(), 1);
+		assert!(matches!(
+			outcomes[0].1,
+			Err(Error::UnexpectedTxStatus(UnexpectedTxStatus::Dropped))
+		));
+		let signer_account = api.signer_account().unwrap();
+		assert_eq!(api.nonce_manager().peek_nonce(signer_account), Some(3));
+	}
+
+	#[test]
+	fn usurped_extrinsic_is_not_resubmitted_without_policy() {
+		let api = create_signed_api(watch_mock(vec![Status::Ready, Status::Usurped(H256::zero())]));
+		let mut queue = api
+			.submission_queue()
+			.unwrap()
+			.resubmit_policy(ResubmitPolicy::never())
+			.watch_until(XtStatus::InBlock);
+		queue.push(0u8);
+		queue.push(1u8);
+		assert_eq!(queue.len(), 2);
+
+		let outcomes = collect_outcomes(queue);
+
+		assert_eq!(outcomes.len(), 2);
+		assert!(outcomes.iter().all(|(_, outcome)| matches!(
+			outcome,
+			Err(Error::UnexpectedTxStatus(UnexpectedTxStatus::Usurped))
+		)));
+	}
+
+	#[test]
+	fn rejected_extrinsic_is_reported() {
+		let client = RpcClientMock::default()
+			.with_response("system_accountNextIndex", 0u32)
+			.with_error(
+				"author_submitAndWatchExtrinsic",
+				1012,
+				"Transaction is temporarily banned",
+			);
+		let api = create_signed_api(client);
+		let mut queue = api.submission_queue().unwrap();
+		queue.push(0u8);
+
+		let outcomes = collect_outcomes(queue);
+
+		assert_eq!(outcomes.len(), 1);
+		assert!(matches!(outcomes[0].1, Err(Error::RpcClient(_))));
+		let signer_account = api.signer_account().unwrap();
+		assert_eq!(api.nonce_manager().peek_nonce(signer_account), Some(0));
+	}
+
+	#[test]
+	fn nonce_is_resynced_if_the_node_might_have_received_the_extrinsic() {
+		for (code, message) in [(-32603, "Internal error"), (1013, "Transaction Already Imported")]
+		{
+			let client = RpcClientMock::default()
+				.with_response("system_accountNextIndex", 3u32)
+				.with_error("author_submitAndWatchExtrinsic", code, message);
+			let api = create_signed_api(client);
+			let signer_account = api.signer_account().unwrap();
+			api.nonce_manager().sync(signer_account, 10);
+			let mut queue = api.submission_queue().unwrap();
+			queue.push(0u8);
+
+			let outcomes = collect_outcomes(queue);
+
+			assert_eq!(outcomes.len(), 1);
+			assert!(matches!(outcomes[0].1, Err(Error::RpcClient(_))));
+			// A reclaimed nonce would be handed out again instead.
+			assert_eq!(api.nonce_manager().peek_nonce(signer_account), Some(3));
+		}
+	}
+}
@@ -0,0 +1,360 @@
+/*
+   Copyright 2019 Supercomputing Systems AG
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+	   http://www.apache.org/licenses/LICENSE-2.0
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Builder of a single extrinsic, with the signer, nonce and additional params chosen per
+//! extrinsic instead of per api.
+
+use crate::{
+	api::{
+		Api, Error, ExtrinsicReport, GetAccountInformation, Result, SubmitAndWatch,
+		SubmitExtrinsic, XtStatus,
+	},
+	extrinsic::offline_extrinsic::{ExtrinsicAddress, Signature, TxExtension},
+	rpc::{Request, Subscribe},
+};
+use ac_compose_macros::compose_extrinsic_offline;
+use ac_primitives::{
+	GenericAdditionalParams, Mortality, SignExtrinsic, UncheckedExtrinsic, config::Config,
+	extrinsic_params::ExtrinsicParams,
+};
+use codec::Encode;
+#[cfg(feature = "std")]
+use log::*;
+use sp_runtime::generic::Era;
+
+type AdditionalParams<T> = <<T as Config>::ExtrinsicParams as ExtrinsicParams<
+	<T as Config>::Index,
+	<T as Config>::Hash,
+>>::AdditionalParams;
+type GenericParams<T, Tip> = GenericAdditionalParams<Tip, <T as Config>::Hash>;
+
+/// Signed extrinsic as built by the [`TransactionBuilder`].
+pub type SignedExtrinsic<T, Call> =
+	UncheckedExtrinsic<ExtrinsicAddress<T>, Call, Signature<T>, TxExtension<T>>;
+
+/// Builds and submits a single signed extrinsic. Created with [`Api::transaction`].
+///
+/// By default, the extrinsic is signed by the api signer, with its nonce as stored on chain
+/// and the additional params set in the api. Each of them can be overridden for this extrinsic
+/// only, so one api can sign with many accounts without being cloned per signer.
+pub struct TransactionBuilder<'a, T: Config, Client, Call> {
+	api: &'a Api<T, Client>,
+	call: Call,
+	signer: Option<T::ExtrinsicSigner>,
+	nonce: NonceSource<T::Index>,
+	additional_params: AdditionalParams<T>,
+}
+
+enum NonceSource<Index> {
+	Chain,
+	Explicit(Index),
+	#[cfg(feature = "std")]
+	Managed,
+}
+
+impl<T: Config, Client> Api<T, Client> {
+	/// Create a [`TransactionBuilder`] for an extrinsic of the `call`.
+	pub fn transaction<Call: Encode + Clone>(
+		&self,
+		call: Call,
+	) -> TransactionBuilder<'_, T, Client, Call> {
+		TransactionBuilder {
+			api: self,
+			call,
+			signer: None,
+			nonce: NonceSource::Chain,
+			additional_params: self.additional_params().cloned().unwrap_or_default(),
+		}
+	}
+}
+
+impl<T, Client, Call> TransactionBuilder<'_, T, Client, Call>
+where
+	T: Config,
+	Call: Encode + Clone,
+{
+	/// Sign the extrinsic with `signer` instead of the api signer.
+	pub fn signer(mut self, signer: T::ExtrinsicSigner) -> Self {
+		self.signer = Some(signer);
+		self
+	}
+
+	/// Use the given `nonce` instead of the nonce of the signer stored on chain.
+	pub fn nonce(mut self, nonce: T::Index) -> Self {
+		self.nonce = NonceSource::Explicit(nonce);
+		self
+	}
+
+	/// Use the next nonce of the signer handed out by the [`NonceManager`] of the api instead
+	/// of the nonce stored on chain, see [`Api::get_managed_nonce_for`]. If submitting the
+	/// extrinsic fails, the nonce is reclaimed or synced with the node again.
+	///
+	/// [`NonceManager`]: crate::NonceManager
+	#[cfg(feature = "std")]
+	pub fn managed_nonce(mut self) -> Self {
+		self.nonce = NonceSource::Managed;
+		self
+	}
+
+	/// Use the given `additional_params` instead of the ones set in the api.
+	pub fn additional_params(mut self, additional_params: AdditionalParams<T>) -> Self {
+		self.additional_params = additional_params;
+		self
+	}
+
+	/// Sign the extrinsic and return it without submitting it. A managed nonce needs to be
+	/// reclaimed if the extrinsic is not going to be submitted.
+	#[maybe_async::maybe_async(?Send)]
+	pub async fn build(self) -> Result<SignedExtrinsic<T, Call>>
+	where
+		Client: Request,
+	{
+		let signer = self.signer_or_default()?;
+		let nonce = self.next_nonce(signer.public_account_id()).await?;
+		Ok(self.sign(signer, nonce))
+	}
+
+	/// Sign the extrinsic and submit it, without watching it. Returns the extrinsic hash.
+	#[maybe_async::maybe_async(?Send)]
+	pub async fn submit(self) -> Result<T::Hash>
+	where
+		Client: Request,
+	{
+		let signer = self.signer_or_default()?;
+		let account = signer.public_account_id();
+		let nonce = self.next_nonce(account).await?;
+		let extrinsic = self.sign(signer, nonce);
+		let result = self.api.submit_opaque_extrinsic(&extrinsic.encode().into()).await;
+		if let Err(e) = &result {
+			self.release_nonce(account, nonce, e).await;
+		}
+		result
+	}
+
+	/// Sign the extrinsic, submit it and watch it until `watch_until` is reached.
+	/// See [`SubmitAndWatch::submit_and_watch_opaque_extrinsic_until`].
+	#[maybe_async::maybe_async(?Send)]
+	pub async fn submit_and_watch_until(
+		self,
+		watch_until: XtStatus,
+	) -> Result<ExtrinsicReport<T::Hash>>
+	where
+		Client: Subscribe + Request,
+	{
+		let signer = self.signer_or_default()?;
+		let account = signer.public_account_id();
+		let nonce = self.next_nonce(account).await?;
+		let extrinsic = self.sign(signer, nonce);
+		let result = self
+			.api
+			.submit_and_watch_opaque_extrinsic_until(&extrinsic.encode().into(), watch_until)
+			.await;
+		if let Err(e) = &result {
+			self.release_nonce(account, nonce, e).await;
+		}
+		result
+	}
+
+	fn signer_or_default(&self) -> Result<&T::ExtrinsicSigner> {
+		self.signer.as_ref().or(self.api.signer()).ok_or(Error::NoSigner)
+	}
+
+	#[maybe_async::maybe_async(?Send)]
+	async fn next_nonce(&self, account: &T::AccountId) -> Result<T::Index>
+	where
+		Client: Request,
+	{
+		match self.nonce {
+			NonceSource::Chain => self.api.get_account_nonce(account).await,
+			NonceSource::Explicit(nonce) => Ok(nonce),
+			#[cfg(feature = "std")]
+			NonceSource::Managed => self.api.get_managed_nonce_for(account).await,
+		}
+	}
+
+	fn sign(&self, signer: &T::ExtrinsicSigner, nonce: T::Index) -> SignedExtrinsic<T, Call> {
+		let params = self.api.extrinsic_params_with(nonce, self.additional_params.clone());
+		compose_extrinsic_offline!(signer, self.call.clone(), params)
+	}
+
+	/// Release the managed nonce after the submission failed, see [`Api::release_managed_nonce`].
+	#[cfg(feature = "std")]
+	#[maybe_async::maybe_async(?Send)]
+	async fn release_nonce(&self, account: &T::AccountId, nonce: T::Index, error: &Error)
+	where
+		Client: Request,
+	{
+		if !matches!(self.nonce, NonceSource::Managed) {
+			return
+		}
+		if let Err(e) = self.api.release_managed_nonce(account, nonce, error).await {
+			warn!("Could not resync the nonce of {account:?}: {e:?}");
+		}
+	}
+
+	/// Without std, there are no managed nonces to release.
+	#[cfg(not(feature = "std"))]
+	#[maybe_async::maybe_async(?Send)]
+	async fn release_nonce(&self, _account: &T::AccountId, _nonce: T::Index, _error: &Error)
+	where
+		Client: Request,
+	{
+	}
+}
+
+impl<T, Client, Call, Tip> TransactionBuilder<'_, T, Client, Call>
+where
+	T: Config,
+	T::ExtrinsicParams:
+		ExtrinsicParams<T::Index, T::Hash, AdditionalParams = GenericParams<T, Tip>>,
+	Tip: Default,
+{
+	/// Set the tip given to the block author for this extrinsic.
+	pub fn tip(mut self, tip: impl Into<Tip>) -> Self {
+		self.additional_params = self.additional_params.tip(tip);
+		self
+	}
+
+	/// Set the era of this extrinsic, together with the hash of its checkpoint block.
+	pub fn era(mut self, era: Era, checkpoint: T::Hash) -> Self {
+		self.additional_params = self.additional_params.era(era, checkpoint);
+		self
+	}
+
+	/// Set the era of this extrinsic as created by [`Api::mortality`].
+	pub fn mortality(mut self, mortality: &Mortality<T::Hash>) -> Self {
+		self.additional_params = self.additional_params.mortality(mortality);
+		self
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::rpc::{
+		JsonRpcError,
+		mocks::{RpcClientMock, create_mock_api},
+	};
+	use ac_primitives::{DefaultRuntimeConfig, ExtrinsicSigner, PlainTip, Preamble};
+	use codec::Compact;
+	use sp_core::{H256, Pair, sr25519};
+
+	fn signer(seed: &str) -> ExtrinsicSigner<DefaultRuntimeConfig> {
+		ExtrinsicSigner::new(sr25519::Pair::from_string(seed, None).unwrap())
+	}
+
+	#[test]
+	fn extrinsic_is_built_with_overridden_params() {
+		let mut api = create_mock_api(RpcClientMock::default());
+		api.set_signer(signer("//Alice"));
+		let bob = signer("//Bob");
+		let era = Era::mortal(64, 1000);
+		let checkpoint = H256::repeat_byte(1);
+
+		let extrinsic = api
+			.transaction(Compact(7u32))
+			.signer(bob.clone())
+			.nonce(5)
+			.tip(100u128)
+			.era(era, checkpoint)
+			.build()
+			.unwrap();
+
+		let Preamble::Signed(address, _, extension) = extrinsic.preamble else {
+			panic!("Expected a signed extrinsic")
+		};
+		assert_eq!(address, bob.extrinsic_address());
+		assert_eq!(extension.nonce, 5);
+		assert_eq!(extension.tip, PlainTip::new(100));
+		assert_eq!(extension.era, era);
+		assert_eq!(extrinsic.function, Compact(7u32));
+	}
+
+	#[test]
+	fn building_without_any_signer_fails() {
+		let api = create_mock_api(RpcClientMock::default());
+
+		let result = api.transaction(0u8).nonce(0).build();
+
+		assert!(matches!(result, Err(Error::NoSigner)));
+	}
+
+	#[test]
+	fn managed_nonce_is_reclaimed_upon_rejection() {
+		let client = RpcClientMock::default()
+			.with_response("system_accountNextIndex", 3u32)
+			.with_error("author_submitExtrinsic", 1010, "Invalid Transaction");
+		let api = create_mock_api(client);
+		let bob = signer("//Bob");
+
+		let result = api.transaction(0u8).signer(bob.clone()).managed_nonce().submit();
+
+		assert!(matches!(result, Err(Error::RpcClient(_))));
+		assert_eq!(api.nonce_manager().peek_nonce(bob.public_account_id()), Some(3));
+	}
+
+	#[test]
+	fn managed_nonce_is_handed_out_per_signer() {
+		let client = RpcClientMock::default()
+			.with_response("system_accountNextIndex", 3u32)
+			.with_response("author_submitExtrinsic", H256::repeat_byte(1));
+		let api = create_mock_api(client);
+		let alice = signer("//Alice");
+		let bob = signer("//Bob");
+
+		for signer in [&alice, &bob, &alice] {
+			let hash = api.transaction(0u8).signer(signer.clone()).managed_nonce().submit();
+			assert_eq!(hash.unwrap(), H256::repeat_byte(1));
+		}
+
+		let nonce_manager = api.nonce_manager();
+		assert_eq!(nonce_manager.peek_nonce(alice.public_account_id()), Some(5));
+		assert_eq!(nonce_manager.peek_nonce(bob.public_account_id()), Some(4));
+	}
+
+	#[test]
+	fn stale_managed_nonce_is_resynced() {
+		let stale =
+			JsonRpcError::new(1010, "Invalid Transaction", Some("Transaction is outdated".into()));
+		let client = RpcClientMock::default()
+			.with_response("system_accountNextIndex", 3u32)
+			.with_json_rpc_error("author_submitExtrinsic", stale);
+		let api = create_mock_api(client);
+		let bob = signer("//Bob");
+		api.nonce_manager().sync(bob.public_account_id(), 1);
+
+		let result = api.transaction(0u8).signer(bob.clone()).managed_nonce().submit();
+
+		assert!(result.unwrap_err().requires_nonce_resync());
+		assert_eq!(api.nonce_manager().peek_nonce(bob.public_account_id()), Some(3));
+	}
+
+	#[test]
+	fn managed_nonce_is_resynced_if_the_node_might_have_received_the_extrinsic() {
+		let internal_error = JsonRpcError::new(-32603, "Internal error", None);
+		let already_imported = JsonRpcError::new(1013, "Transaction Already Imported", None);
+		for error in [internal_error, already_imported] {
+			let client = RpcClientMock::default()
+				.with_response("system_accountNextIndex", 3u32)
+				.with_json_rpc_error("author_submitExtrinsic", error);
+			let api = create_mock_api(client);
+			let bob = signer("//Bob");
+			api.nonce_manager().sync(bob.public_account_id(), 1);
+
+			let result = api.transaction(0u8).signer(bob.clone()).managed_nonce().submit();
+
+			assert!(matches!(result, Err(Error::RpcClient(_))));
+			assert_eq!(api.nonce_manager().peek_nonce(bob.public_account_id()), Some(3));
+		}
+	}
+}
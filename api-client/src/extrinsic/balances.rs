@@ -42,7 +42,7 @@ pub type TransferKeepAliveCall<Address, Balance> = (CallIndex, Address, Compact<
 /// Call to the balance of an account.
 pub type ForceSetBalanceCall<Address, Balance> = (CallIndex, Address, Compact<Balance>);
 
-#[maybe_async::maybe_async(?Send)]
+#[ac_async_macros::maybe_async]
 pub trait BalancesExtrinsics {
 	type Balance;
 	type Address;
@@ -73,7 +73,7 @@ pub trait BalancesExtrinsics {
 	) -> Option<Self::Extrinsic<ForceSetBalanceCall<Self::Address, Self::Balance>>>;
 }
 
-#[maybe_async::maybe_async(?Send)]
+#[ac_async_macros::maybe_async]
 impl<T, Client> BalancesExtrinsics for Api<T, Client>
 where
 	T: Config,
@@ -85,7 +85,7 @@ pub type SaltFor<P> = <P as ContractsExtrinsics>::Salt;
 pub type CodeHashFor<P> = <P as ContractsExtrinsics>::CodeHash;
 pub type AddressFor<P> = <P as ContractsExtrinsics>::Address;
 pub type CurrencyFor<P> = Compact<<P as ContractsExtrinsics>::Currency>;
-#[maybe_async::maybe_async(?Send)]
+#[ac_async_macros::maybe_async]
 pub trait ContractsExtrinsics {
 	type Weight;
 	type Currency;
@@ -235,7 +235,7 @@ pub trait ContractsExtrinsics {
 }
 
 #[cfg(feature = "std")]
-#[maybe_async::maybe_async(?Send)]
+#[ac_async_macros::maybe_async]
 impl<T, Client> ContractsExtrinsics for Api<T, Client>
 where
 	T: Config,
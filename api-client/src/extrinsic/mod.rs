@@ -27,6 +27,7 @@ pub use utility::UtilityExtrinsics;
 pub mod balances;
 #[cfg(feature = "contracts-xt")]
 pub mod contracts;
+pub mod mortality;
 pub mod offline_extrinsic;
 #[cfg(feature = "staking-xt")]
 pub mod staking;
@@ -0,0 +1,134 @@
+/*
+   Copyright 2019 Supercomputing Systems AG
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+	   http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+
+*/
+
+//! Mortal eras anchored to the latest finalized block, so extrinsics can't be replayed once
+//! they have expired.
+
+use crate::{
+	Api, GetChainInfo,
+	api::{Error, Result},
+	rpc::Request,
+};
+use ac_primitives::{Mortality, config::Config};
+use sp_runtime::{generic::Era, traits::Header as HeaderTrait};
+
+impl<T, Client> Api<T, Client>
+where
+	T: Config,
+	Client: Request,
+{
+	/// Create the mortality of an extrinsic that is valid for `period` blocks, starting at the
+	/// latest finalized block. The period is rounded up to a power of two between 4 and 65536.
+	///
+	/// Set it with [`GenericAdditionalParams::mortality`], either per extrinsic or as additional
+	/// params of the api. Extrinsics created with it can't be included anymore once `period`
+	/// blocks have passed, so the mortality needs to be renewed regularly.
+	///
+	/// [`GenericAdditionalParams::mortality`]: ac_primitives::GenericAdditionalParams::mortality
+	#[maybe_async::maybe_async(?Send)]
+	pub async fn mortality(&self, period: u64) -> Result<Mortality<T::Hash>> {
+		let finalized_hash = self.get_finalized_head().await?.ok_or(Error::BlockHashNotFound)?;
+		let finalized_header =
+			self.get_header(Some(finalized_hash)).await?.ok_or(Error::BlockNotFound)?;
+		let finalized_number: u64 = (*finalized_header.number()).into();
+		let era = Era::mortal(period, finalized_number);
+		// For long periods, the birth block is quantized and may precede the finalized block.
+		let birth_number = era.birth(finalized_number);
+		let birth_hash = if birth_number == finalized_number {
+			finalized_hash
+		} else {
+			let block_number =
+				T::BlockNumber::try_from(birth_number).map_err(|_| Error::TryFromIntError)?;
+			self.get_block_hash(Some(block_number)).await?.ok_or(Error::BlockHashNotFound)?
+		};
+		Ok(Mortality::new(era, birth_number, birth_hash))
+	}
+
+	/// Returns true if extrinsics with the `mortality` can't be included in the next block
+	/// anymore, because they have expired.
+	#[maybe_async::maybe_async(?Send)]
+	pub async fn is_expired(&self, mortality: &Mortality<T::Hash>) -> Result<bool> {
+		let best_header = self.get_header(None).await?.ok_or(Error::BlockNotFound)?;
+		let best_number: u64 = (*best_header.number()).into();
+		Ok(mortality.is_expired_at(best_number.saturating_add(1)))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::rpc::mocks::{RpcClientMock, create_mock_api};
+	use ac_compose_macros::rpc_params;
+	use ac_primitives::{DefaultRuntimeConfig, GenericAdditionalParams, PlainTip};
+	use sp_core::H256;
+
+	type Header = <DefaultRuntimeConfig as Config>::Header;
+
+	fn header(number: u32) -> Header {
+		Header::new(number, H256::zero(), H256::zero(), H256::zero(), Default::default())
+	}
+
+	#[test]
+	fn mortality_is_anchored_to_finalized_block() {
+		let finalized_hash = H256::repeat_byte(1);
+		let client = RpcClientMock::default()
+			.with_response("chain_getFinalizedHead", finalized_hash)
+			.with_response_for("chain_getHeader", rpc_params![Some(finalized_hash)], header(1000))
+			.with_response_for("chain_getHeader", rpc_params![None::<H256>], header(1063));
+		let api = create_mock_api(client);
+
+		let mortality = api.mortality(64).unwrap();
+
+		assert_eq!(mortality.era(), Era::mortal(64, 1000));
+		assert_eq!(mortality.checkpoint(), finalized_hash);
+		assert_eq!(mortality.death_block(), 1064);
+		// The next block is the last one the extrinsic can be included in.
+		assert!(!api.is_expired(&mortality).unwrap());
+
+		let additional_params =
+			GenericAdditionalParams::<PlainTip<u128>, H256>::new().mortality(&mortality);
+		assert_eq!(additional_params.era, mortality.era());
+		assert_eq!(additional_params.mortality_checkpoint, Some(finalized_hash));
+	}
+
+	#[test]
+	fn quantized_mortality_is_anchored_to_birth_block() {
+		let finalized_hash = H256::repeat_byte(1);
+		let birth_hash = H256::repeat_byte(2);
+		let client = RpcClientMock::default()
+			.with_response("chain_getFinalizedHead", finalized_hash)
+			.with_response("chain_getHeader", header(1001))
+			.with_response_for("chain_getBlockHash", rpc_params![Some(1000u32)], birth_hash);
+		let api = create_mock_api(client);
+
+		let mortality = api.mortality(8192).unwrap();
+
+		assert_eq!(mortality.birth_block(), 1000);
+		assert_eq!(mortality.checkpoint(), birth_hash);
+	}
+
+	#[test]
+	fn mortality_expires_after_period() {
+		let client = RpcClientMock::default().with_response("chain_getHeader", header(1064));
+		let api = create_mock_api(client);
+		let era = Era::mortal(64, 1000);
+
+		let mortality = Mortality::new(era, 1000, H256::zero());
+
+		assert!(api.is_expired(&mortality).unwrap());
+	}
+}
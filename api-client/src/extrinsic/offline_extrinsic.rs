@@ -24,11 +24,11 @@ use ac_primitives::{
 };
 use codec::Encode;
 
-type ExtrinsicAddress<T> =
+pub(crate) type ExtrinsicAddress<T> =
 	<<T as Config>::ExtrinsicSigner as SignExtrinsic<<T as Config>::AccountId>>::ExtrinsicAddress;
-type Signature<T> =
+pub(crate) type Signature<T> =
 	<<T as Config>::ExtrinsicSigner as SignExtrinsic<<T as Config>::AccountId>>::Signature;
-type TxExtension<T> = <<T as Config>::ExtrinsicParams as ExtrinsicParams<
+pub(crate) type TxExtension<T> = <<T as Config>::ExtrinsicParams as ExtrinsicParams<
 	<T as Config>::Index,
 	<T as Config>::Hash,
 >>::TxExtension;
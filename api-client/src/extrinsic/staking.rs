@@ -64,7 +64,7 @@ pub type ForceNoEraCall = CallIndex;
 pub type SetPayeeCall<Address> = (CallIndex, Address);
 pub type SetValidatorCountCall = (CallIndex, u32);
 
-#[maybe_async::maybe_async(?Send)]
+#[ac_async_macros::maybe_async]
 pub trait StakingExtrinsics {
 	type Balance;
 	type RewardDestination;
@@ -156,7 +156,7 @@ pub trait StakingExtrinsics {
 	) -> Option<Self::Extrinsic<SetValidatorCountCall>>;
 }
 
-#[maybe_async::maybe_async(?Send)]
+#[ac_async_macros::maybe_async]
 impl<T, Client> StakingExtrinsics for Api<T, Client>
 where
 	T: Config,
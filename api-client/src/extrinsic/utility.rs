@@ -21,8 +21,8 @@
 use crate::{Api, rpc::Request};
 use ac_compose_macros::compose_extrinsic;
 use ac_primitives::{
-	SignExtrinsic, UncheckedExtrinsic, config::Config, extrinsic_params::ExtrinsicParams,
-	extrinsics::CallIndex,
+	MaybeSend, SignExtrinsic, UncheckedExtrinsic, config::Config,
+	extrinsic_params::ExtrinsicParams, extrinsics::CallIndex,
 };
 #[cfg(all(not(feature = "sync-api"), not(feature = "std")))]
 use alloc::boxed::Box;
@@ -40,24 +40,24 @@ pub struct Batch<Call> {
 
 pub type BatchCall<Call> = (CallIndex, Batch<Call>);
 
-#[maybe_async::maybe_async(?Send)]
+#[ac_async_macros::maybe_async]
 pub trait UtilityExtrinsics {
 	type Extrinsic<Call>;
 
 	// Send a batch of dispatch calls.
-	async fn batch<Call: Encode + Clone>(
+	async fn batch<Call: Encode + Clone + MaybeSend>(
 		&self,
 		calls: Vec<Call>,
 	) -> Option<Self::Extrinsic<BatchCall<Call>>>;
 
 	// Send a batch of dispatch calls. Unlike batch, it allows errors and won't interrupt.
-	async fn force_batch<Call: Encode + Clone>(
+	async fn force_batch<Call: Encode + Clone + MaybeSend>(
 		&self,
 		calls: Vec<Call>,
 	) -> Option<Self::Extrinsic<BatchCall<Call>>>;
 }
 
-#[maybe_async::maybe_async(?Send)]
+#[ac_async_macros::maybe_async]
 impl<T, Client> UtilityExtrinsics for Api<T, Client>
 where
 	T: Config,
@@ -70,7 +70,7 @@ where
 		<T::ExtrinsicParams as ExtrinsicParams<T::Index, T::Hash>>::TxExtension,
 	>;
 
-	async fn batch<Call: Encode + Clone>(
+	async fn batch<Call: Encode + Clone + MaybeSend>(
 		&self,
 		calls: Vec<Call>,
 	) -> Option<Self::Extrinsic<BatchCall<Call>>> {
@@ -78,7 +78,7 @@ where
 		compose_extrinsic!(self, UTILITY_MODULE, BATCH, calls)
 	}
 
-	async fn force_batch<Call: Encode + Clone>(
+	async fn force_batch<Call: Encode + Clone + MaybeSend>(
 		&self,
 		calls: Vec<Call>,
 	) -> Option<Self::Extrinsic<BatchCall<Call>>> {
@@ -0,0 +1,108 @@
+/*
+   Copyright 2019 Supercomputing Systems AG
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+	   http://www.apache.org/licenses/LICENSE-2.0
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use core::time::Duration;
+
+/// Defines how long an rpc client waits between two connection attempts
+/// and how often it tries before giving up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BackoffPolicy {
+	initial_delay: Duration,
+	max_delay: Duration,
+	multiplier: u32,
+	max_attempts: u8,
+}
+
+impl BackoffPolicy {
+	/// Wait the same `delay` before every new attempt.
+	pub fn constant(delay: Duration, max_attempts: u8) -> Self {
+		Self { initial_delay: delay, max_delay: delay, multiplier: 1, max_attempts }
+	}
+
+	/// Double the wait time after every failed attempt, starting with `initial_delay`
+	/// and never waiting longer than `max_delay`.
+	pub fn exponential(initial_delay: Duration, max_delay: Duration, max_attempts: u8) -> Self {
+		Self { initial_delay, max_delay, multiplier: 2, max_attempts }
+	}
+
+	/// Set the number of attempts after the initial one failed.
+	pub fn with_max_attempts(mut self, max_attempts: u8) -> Self {
+		self.max_attempts = max_attempts;
+		self
+	}
+
+	/// Number of attempts after the initial one failed.
+	pub fn max_attempts(&self) -> u8 {
+		self.max_attempts
+	}
+
+	/// Returns the time to wait after `failed_attempts` unsuccessful attempts.
+	/// Returns `None` if the maximum number of attempts has been reached.
+	pub fn delay(&self, failed_attempts: u8) -> Option<Duration> {
+		if failed_attempts > self.max_attempts {
+			return None
+		}
+		let factor = self.multiplier.saturating_pow(failed_attempts.saturating_sub(1).into());
+		Some(self.initial_delay.saturating_mul(factor).min(self.max_delay))
+	}
+}
+
+impl Default for BackoffPolicy {
+	fn default() -> Self {
+		Self::exponential(Duration::from_millis(100), Duration::from_secs(5), 5)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn constant_backoff_returns_same_delay() {
+		let delay = Duration::from_secs(5);
+		let backoff = BackoffPolicy::constant(delay, 3);
+
+		assert_eq!(backoff.delay(1), Some(delay));
+		assert_eq!(backoff.delay(2), Some(delay));
+		assert_eq!(backoff.delay(3), Some(delay));
+		assert_eq!(backoff.delay(4), None);
+	}
+
+	#[test]
+	fn exponential_backoff_doubles_delay_up_to_max() {
+		let backoff =
+			BackoffPolicy::exponential(Duration::from_millis(100), Duration::from_millis(500), 10);
+
+		assert_eq!(backoff.delay(1), Some(Duration::from_millis(100)));
+		assert_eq!(backoff.delay(2), Some(Duration::from_millis(200)));
+		assert_eq!(backoff.delay(3), Some(Duration::from_millis(400)));
+		assert_eq!(backoff.delay(4), Some(Duration::from_millis(500)));
+		assert_eq!(backoff.delay(10), Some(Duration::from_millis(500)));
+		assert_eq!(backoff.delay(11), None);
+	}
+
+	#[test]
+	fn exponential_backoff_does_not_overflow() {
+		let backoff =
+			BackoffPolicy::exponential(Duration::from_secs(1), Duration::from_secs(30), 255);
+
+		assert_eq!(backoff.delay(255), Some(Duration::from_secs(30)));
+	}
+
+	#[test]
+	fn zero_max_attempts_allows_no_retry() {
+		let backoff = BackoffPolicy::default().with_max_attempts(0);
+
+		assert_eq!(backoff.delay(1), None);
+	}
+}
@@ -17,6 +17,9 @@
 
 use alloc::{boxed::Box, string::String};
 use core::error::Error as ErrorT;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sp_runtime::transaction_validity::{InvalidTransaction, UnknownTransaction};
 
 pub type Result<T> = core::result::Result<T, Error>;
 
@@ -30,15 +33,205 @@ pub enum Error {
 	Io(String),
 	MaxConnectionAttemptsExceeded,
 	ConnectionClosed,
+	SubscriptionNotSupported(String),
+	UnexpectedResponse(String),
+	/// The subscription has been renewed after it was closed unexpectedly, e.g. due to a lost
+	/// connection. Notifications sent in the meantime might have been missed.
+	SubscriptionRenewed,
+	/// The node did not answer within the configured time.
+	RequestTimeout,
+	/// The node answered with a json-rpc error response.
+	JsonRpc(JsonRpcError),
 	Client(Box<dyn ErrorT + Send + Sync + 'static>),
 }
 
+impl Error {
+	/// Returns true if the error has been caused by the connection to the node rather than by
+	/// the request itself, so sending the same request to another node might succeed.
+	pub fn is_connection_error(&self) -> bool {
+		match self {
+			Self::MaxConnectionAttemptsExceeded
+			| Self::ConnectionClosed
+			| Self::RequestTimeout
+			| Self::Io(_) => true,
+			Self::Client(error) => is_connection_client_error(error.as_ref()),
+			_ => false,
+		}
+	}
+
+	/// Returns the transaction pool error, if the node rejected a submitted extrinsic.
+	pub fn pool_error(&self) -> Option<TransactionPoolError> {
+		match self {
+			Self::JsonRpc(error) => error.pool_error(),
+			_ => None,
+		}
+	}
+}
+
+/// Error object of a json-rpc error response.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JsonRpcError {
+	pub code: i32,
+	pub message: String,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub data: Option<Value>,
+}
+
+impl JsonRpcError {
+	pub fn new(code: i32, message: impl Into<String>, data: Option<Value>) -> Self {
+		Self { code, message: message.into(), data }
+	}
+
+	/// Classifies the error, if it has been returned by the transaction pool of a Substrate
+	/// node upon submitting an extrinsic.
+	pub fn pool_error(&self) -> Option<TransactionPoolError> {
+		let data = self.data.as_ref().and_then(Value::as_str);
+		let error = match self.code {
+			1010 => TransactionPoolError::InvalidTransaction(data.and_then(invalid_transaction)),
+			1011 => TransactionPoolError::UnknownTransaction(data.and_then(unknown_transaction)),
+			1012 => TransactionPoolError::TemporarilyBanned,
+			1013 => TransactionPoolError::AlreadyImported,
+			1014 => TransactionPoolError::TooLowPriority,
+			1015 => TransactionPoolError::CycleDetected,
+			1016 => TransactionPoolError::ImmediatelyDropped,
+			1017 => TransactionPoolError::Unactionable,
+			1018 => TransactionPoolError::NoTagsProvided,
+			1019 => TransactionPoolError::InvalidBlockId,
+			1020 => TransactionPoolError::RejectedFutureTransaction,
+			_ => return None,
+		};
+		Some(error)
+	}
+}
+
+/// Errors of the transaction pool of a Substrate node, as returned by the `author_*` rpc methods.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransactionPoolError {
+	/// 1010: The transaction is invalid, e.g. due to a stale nonce ([`InvalidTransaction::Stale`])
+	/// or a bad signature ([`InvalidTransaction::BadProof`]). `None` if the node sent an unknown
+	/// reason.
+	InvalidTransaction(Option<InvalidTransaction>),
+	/// 1011: The validity of the transaction could not be determined.
+	UnknownTransaction(Option<UnknownTransaction>),
+	/// 1012: The transaction is temporarily banned, e.g. because it has recently been included
+	/// or dropped.
+	TemporarilyBanned,
+	/// 1013: The same transaction is already in the pool.
+	AlreadyImported,
+	/// 1014: A transaction with the same nonce and a higher or equal priority is already in the
+	/// pool.
+	TooLowPriority,
+	/// 1015: The transaction depends on itself.
+	CycleDetected,
+	/// 1016: The transaction could not enter the pool, because the pool is full.
+	ImmediatelyDropped,
+	/// 1017: The transaction can never be included in a block.
+	Unactionable,
+	/// 1018: The transaction does not provide any tags, so it can not be imported.
+	NoTagsProvided,
+	/// 1019: The block the transaction has been validated against is unknown.
+	InvalidBlockId,
+	/// 1020: The pool does not accept transactions that are only valid in the future.
+	RejectedFutureTransaction,
+}
+
+/// Parses the reason the node sends along with an invalid transaction error.
+fn invalid_transaction(reason: &str) -> Option<InvalidTransaction> {
+	if let Some(code) = reason.strip_prefix("Custom error: ") {
+		return code.parse().ok().map(InvalidTransaction::Custom)
+	}
+	[
+		InvalidTransaction::Call,
+		InvalidTransaction::Payment,
+		InvalidTransaction::Future,
+		InvalidTransaction::Stale,
+		InvalidTransaction::BadProof,
+		InvalidTransaction::AncientBirthBlock,
+		InvalidTransaction::ExhaustsResources,
+		InvalidTransaction::BadMandatory,
+		InvalidTransaction::MandatoryValidation,
+		InvalidTransaction::BadSigner,
+		InvalidTransaction::IndeterminateImplicit,
+		InvalidTransaction::UnknownOrigin,
+	]
+	.into_iter()
+	.find(|invalid| <&'static str>::from(*invalid) == reason)
+}
+
+/// Parses the reason the node sends along with an unknown transaction error. It is the
+/// debug representation of [`UnknownTransaction`].
+fn unknown_transaction(reason: &str) -> Option<UnknownTransaction> {
+	match reason {
+		"CannotLookup" => Some(UnknownTransaction::CannotLookup),
+		"NoUnsignedValidator" => Some(UnknownTransaction::NoUnsignedValidator),
+		_ => reason
+			.strip_prefix("Custom(")
+			.and_then(|code| code.strip_suffix(')'))
+			.and_then(|code| code.parse().ok())
+			.map(UnknownTransaction::Custom),
+	}
+}
+
+#[allow(unused_variables)]
+fn is_connection_client_error(error: &(dyn ErrorT + Send + Sync + 'static)) -> bool {
+	#[cfg(feature = "jsonrpsee-client")]
+	if let Some(error) = error.downcast_ref::<jsonrpsee::core::client::Error>() {
+		use jsonrpsee::core::client::Error as JsonrpseeError;
+		return matches!(
+			error,
+			JsonrpseeError::Transport(_)
+				| JsonrpseeError::RestartNeeded(_)
+				| JsonrpseeError::RequestTimeout
+		)
+	}
+	#[cfg(feature = "tungstenite-client")]
+	if let Some(error) = error.downcast_ref::<tungstenite::Error>() {
+		return matches!(
+			error,
+			tungstenite::Error::ConnectionClosed
+				| tungstenite::Error::AlreadyClosed
+				| tungstenite::Error::Io(_)
+				| tungstenite::Error::Tls(_)
+		)
+	}
+	#[cfg(feature = "http-client")]
+	if let Some(error) = error.downcast_ref::<ureq::Error>() {
+		return match error {
+			ureq::Error::StatusCode(code) => *code >= 500,
+			ureq::Error::Io(_)
+			| ureq::Error::Timeout(_)
+			| ureq::Error::HostNotFound
+			| ureq::Error::ConnectionFailed => true,
+			_ => false,
+		}
+	}
+	false
+}
+
 impl From<serde_json::error::Error> for Error {
 	fn from(error: serde_json::error::Error) -> Self {
 		Self::SerdeJson(error)
 	}
 }
 
+#[cfg(feature = "jsonrpsee-client")]
+impl From<jsonrpsee::core::client::Error> for Error {
+	fn from(error: jsonrpsee::core::client::Error) -> Self {
+		match error {
+			jsonrpsee::core::client::Error::Call(error) => Self::JsonRpc(error.into()),
+			error => Self::Client(Box::new(error)),
+		}
+	}
+}
+
+#[cfg(feature = "jsonrpsee-client")]
+impl From<jsonrpsee::types::ErrorObject<'_>> for JsonRpcError {
+	fn from(error: jsonrpsee::types::ErrorObject<'_>) -> Self {
+		let data = error.data().and_then(|data| serde_json::from_str(data.get()).ok());
+		Self::new(error.code(), error.message(), data)
+	}
+}
+
 #[cfg(feature = "tungstenite-client")]
 impl From<tungstenite::Error> for Error {
 	fn from(error: tungstenite::Error) -> Self {
@@ -46,6 +239,13 @@ impl From<tungstenite::Error> for Error {
 	}
 }
 
+#[cfg(feature = "http-client")]
+impl From<ureq::Error> for Error {
+	fn from(error: ureq::Error) -> Self {
+		Self::Client(Box::new(error))
+	}
+}
+
 #[cfg(feature = "std")]
 #[allow(unused_imports)]
 pub use std_only::*;
@@ -78,3 +278,74 @@ mod std_only {
 		}
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use serde_json::json;
+
+	fn pool_error(code: i32, data: &str) -> Option<TransactionPoolError> {
+		JsonRpcError::new(code, "Invalid Transaction", Some(data.into())).pool_error()
+	}
+
+	#[test]
+	fn invalid_transaction_reason_is_classified() {
+		assert_eq!(
+			pool_error(1010, "Transaction is outdated"),
+			Some(TransactionPoolError::InvalidTransaction(Some(InvalidTransaction::Stale)))
+		);
+		assert_eq!(
+			pool_error(1010, "Transaction has a bad signature"),
+			Some(TransactionPoolError::InvalidTransaction(Some(InvalidTransaction::BadProof)))
+		);
+		assert_eq!(
+			pool_error(1010, "Custom error: 3"),
+			Some(TransactionPoolError::InvalidTransaction(Some(InvalidTransaction::Custom(3))))
+		);
+		assert_eq!(
+			pool_error(1010, "Something new"),
+			Some(TransactionPoolError::InvalidTransaction(None))
+		);
+	}
+
+	#[test]
+	fn unknown_transaction_reason_is_classified() {
+		assert_eq!(
+			pool_error(1011, "CannotLookup"),
+			Some(TransactionPoolError::UnknownTransaction(Some(UnknownTransaction::CannotLookup)))
+		);
+		assert_eq!(
+			pool_error(1011, "Custom(7)"),
+			Some(TransactionPoolError::UnknownTransaction(Some(UnknownTransaction::Custom(7))))
+		);
+	}
+
+	#[test]
+	fn other_pool_errors_are_classified() {
+		let error =
+			Error::JsonRpc(JsonRpcError::new(1012, "Transaction is temporarily banned", None));
+		assert_eq!(error.pool_error(), Some(TransactionPoolError::TemporarilyBanned));
+
+		let error = JsonRpcError::new(1014, "Priority is too low: (0 vs 0)", None);
+		assert_eq!(error.pool_error(), Some(TransactionPoolError::TooLowPriority));
+	}
+
+	#[test]
+	fn non_pool_errors_are_not_classified() {
+		let error = JsonRpcError::new(-32601, "Method not found", None);
+		assert_eq!(error.pool_error(), None);
+		assert_eq!(Error::ConnectionClosed.pool_error(), None);
+	}
+
+	#[test]
+	fn json_rpc_error_deserializes_from_error_object() {
+		let value = json!({ "code": 1010, "message": "Invalid Transaction", "data": "Transaction is outdated" });
+
+		let error: JsonRpcError = serde_json::from_value(value).unwrap();
+
+		assert_eq!(
+			error,
+			JsonRpcError::new(1010, "Invalid Transaction", Some("Transaction is outdated".into()))
+		);
+	}
+}
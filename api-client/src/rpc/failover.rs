@@ -0,0 +1,353 @@
+/*
+   Copyright 2019 Supercomputing Systems AG
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+	   http://www.apache.org/licenses/LICENSE-2.0
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Rpc client distributing its requests over several nodes of the same network.
+
+use crate::rpc::{BatchRequest, Error, Request, Result, Subscribe};
+use ac_primitives::{MaybeSend, RpcParams};
+use core::{
+	fmt::Debug,
+	sync::atomic::{AtomicUsize, Ordering},
+	time::Duration,
+};
+use log::*;
+use serde::de::DeserializeOwned;
+use std::{sync::Mutex, time::Instant};
+
+/// Defines which endpoint of the [`FailoverClient`] receives the next request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SelectionStrategy {
+	/// Always use the first healthy endpoint in the given order.
+	#[default]
+	Priority,
+	/// Take turns between all healthy endpoints.
+	RoundRobin,
+	/// Use the healthy endpoint with the lowest average response time.
+	LowestLatency,
+}
+
+/// Rpc client wrapping several clients connected to different nodes of the same network.
+///
+/// Every request is sent to one endpoint, chosen by the [`SelectionStrategy`] among the healthy
+/// endpoints. If it fails due to a connection error (see [`Error::is_connection_error`]), the
+/// endpoint is marked as unhealthy and the request is sent to the next endpoint. Unhealthy
+/// endpoints are only used again after the `recheck_interval` has passed, or if no healthy
+/// endpoint is left.
+///
+/// Subscriptions stay with the endpoint they have been established with.
+#[derive(Debug)]
+pub struct FailoverClient<Client> {
+	clients: Vec<Client>,
+	strategy: SelectionStrategy,
+	recheck_interval: Duration,
+	health: Mutex<Vec<Health>>,
+	next_endpoint: AtomicUsize,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Health {
+	unhealthy_until: Option<Instant>,
+	average_latency: Option<Duration>,
+}
+
+impl Health {
+	fn is_healthy(&self, now: Instant) -> bool {
+		self.unhealthy_until.is_none_or(|until| until <= now)
+	}
+}
+
+impl<Client> FailoverClient<Client> {
+	/// Create a new client sending its requests to the given `clients`, which should
+	/// all be connected to different nodes of the same network.
+	pub fn new(clients: Vec<Client>) -> Self {
+		let health = vec![Health::default(); clients.len()];
+		Self {
+			clients,
+			strategy: Default::default(),
+			recheck_interval: Duration::from_secs(30),
+			health: Mutex::new(health),
+			next_endpoint: AtomicUsize::new(0),
+		}
+	}
+
+	/// Set the strategy which defines the endpoint that receives the next request.
+	pub fn with_strategy(mut self, strategy: SelectionStrategy) -> Self {
+		self.strategy = strategy;
+		self
+	}
+
+	/// Set the time an endpoint is skipped after a connection error.
+	pub fn with_recheck_interval(mut self, recheck_interval: Duration) -> Self {
+		self.recheck_interval = recheck_interval;
+		self
+	}
+
+	/// The wrapped clients, in the order they have been given.
+	pub fn clients(&self) -> &[Client] {
+		&self.clients
+	}
+
+	/// Returns true if the endpoint at `index` is currently considered healthy.
+	pub fn is_healthy(&self, index: usize) -> bool {
+		self.health().get(index).is_some_and(|health| health.is_healthy(Instant::now()))
+	}
+
+	/// Returns the indices of all endpoints in the order they should be tried.
+	/// Healthy endpoints are ordered by the strategy, followed by the unhealthy ones.
+	fn endpoint_order(&self) -> Vec<usize> {
+		let health = self.health();
+		let now = Instant::now();
+		let (mut healthy, unhealthy): (Vec<usize>, Vec<usize>) =
+			(0..self.clients.len()).partition(|&index| health[index].is_healthy(now));
+		match self.strategy {
+			SelectionStrategy::Priority => {},
+			SelectionStrategy::RoundRobin =>
+				if !healthy.is_empty() {
+					let next = self.next_endpoint.fetch_add(1, Ordering::Relaxed);
+					healthy.rotate_left(next % healthy.len());
+				},
+			// Endpoints without measurement come first, so that every endpoint gets measured.
+			SelectionStrategy::LowestLatency =>
+				healthy.sort_by_key(|&index| health[index].average_latency.unwrap_or_default()),
+		}
+		healthy.extend(unhealthy);
+		healthy
+	}
+
+	fn report_success(&self, index: usize, latency: Duration) {
+		let mut health = self.health();
+		let health = &mut health[index];
+		health.unhealthy_until = None;
+		// Exponential moving average, giving the latest measurement a weight of 1/4.
+		health.average_latency = Some(match health.average_latency {
+			Some(average) => (average * 3 + latency) / 4,
+			None => latency,
+		});
+	}
+
+	fn report_failure(&self, index: usize, error: &Error) {
+		warn!("Endpoint {index} failed due to {error:?}, failing over to the next one");
+		self.health()[index].unhealthy_until = Some(Instant::now() + self.recheck_interval);
+	}
+
+	fn health(&self) -> std::sync::MutexGuard<'_, Vec<Health>> {
+		// A poisoned lock only means a panic happened while updating the statistics,
+		// which can not leave them in an invalid state.
+		self.health.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+	}
+}
+
+#[ac_async_macros::maybe_async]
+impl<Client: Request> Request for FailoverClient<Client> {
+	async fn request<R: DeserializeOwned + MaybeSend>(
+		&self,
+		method: &str,
+		params: RpcParams,
+	) -> Result<R> {
+		let mut last_error = Error::MaxConnectionAttemptsExceeded;
+		for index in self.endpoint_order() {
+			let start = Instant::now();
+			match self.clients[index].request(method, params.clone()).await {
+				Err(e) if e.is_connection_error() => {
+					self.report_failure(index, &e);
+					last_error = e;
+				},
+				result => {
+					self.report_success(index, start.elapsed());
+					return result
+				},
+			}
+		}
+		Err(last_error)
+	}
+}
+
+#[ac_async_macros::maybe_async]
+impl<Client: BatchRequest> BatchRequest for FailoverClient<Client> {
+	async fn batch_request<R: DeserializeOwned + Debug + MaybeSend>(
+		&self,
+		requests: Vec<(&str, RpcParams)>,
+	) -> Result<Vec<Result<R>>> {
+		let mut last_error = Error::MaxConnectionAttemptsExceeded;
+		for index in self.endpoint_order() {
+			let start = Instant::now();
+			match self.clients[index].batch_request(requests.clone()).await {
+				Err(e) if e.is_connection_error() => {
+					self.report_failure(index, &e);
+					last_error = e;
+				},
+				result => {
+					self.report_success(index, start.elapsed());
+					return result
+				},
+			}
+		}
+		Err(last_error)
+	}
+}
+
+#[ac_async_macros::maybe_async]
+impl<Client: Subscribe> Subscribe for FailoverClient<Client> {
+	type Subscription<Notification>
+		= Client::Subscription<Notification>
+	where
+		Notification: DeserializeOwned + MaybeSend;
+
+	async fn subscribe<Notification: DeserializeOwned + MaybeSend>(
+		&self,
+		sub: &str,
+		params: RpcParams,
+		unsub: &str,
+	) -> Result<Self::Subscription<Notification>> {
+		let mut last_error = Error::MaxConnectionAttemptsExceeded;
+		for index in self.endpoint_order() {
+			let start = Instant::now();
+			match self.clients[index].subscribe(sub, params.clone(), unsub).await {
+				Err(e) if e.is_connection_error() => {
+					self.report_failure(index, &e);
+					last_error = e;
+				},
+				result => {
+					self.report_success(index, start.elapsed());
+					return result
+				},
+			}
+		}
+		Err(last_error)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use core::sync::atomic::AtomicBool;
+
+	/// Client answering every request with its `name`, as long as it is `connected`.
+	struct TestClient {
+		name: &'static str,
+		connected: AtomicBool,
+		requests: AtomicUsize,
+	}
+
+	impl TestClient {
+		fn new(name: &'static str, connected: bool) -> Self {
+			Self { name, connected: AtomicBool::new(connected), requests: AtomicUsize::new(0) }
+		}
+	}
+
+	impl Request for TestClient {
+		fn request<R: DeserializeOwned + MaybeSend>(
+			&self,
+			_method: &str,
+			_params: RpcParams,
+		) -> Result<R> {
+			self.requests.fetch_add(1, Ordering::Relaxed);
+			if !self.connected.load(Ordering::Relaxed) {
+				return Err(Error::ConnectionClosed)
+			}
+			Ok(serde_json::from_value(self.name.into())?)
+		}
+	}
+
+	fn request(client: &FailoverClient<TestClient>) -> Result<String> {
+		client.request("system_name", RpcParams::new())
+	}
+
+	#[test]
+	fn priority_strategy_uses_first_endpoint() {
+		let client = FailoverClient::new(vec![
+			TestClient::new("first", true),
+			TestClient::new("second", true),
+		]);
+
+		assert_eq!(request(&client).unwrap(), "first");
+		assert_eq!(request(&client).unwrap(), "first");
+	}
+
+	#[test]
+	fn connection_error_fails_over_to_next_endpoint() {
+		let client = FailoverClient::new(vec![
+			TestClient::new("first", false),
+			TestClient::new("second", true),
+		]);
+
+		assert_eq!(request(&client).unwrap(), "second");
+		assert!(!client.is_healthy(0));
+		assert!(client.is_healthy(1));
+
+		// The unhealthy endpoint is skipped for the following requests.
+		assert_eq!(request(&client).unwrap(), "second");
+		assert_eq!(client.clients()[0].requests.load(Ordering::Relaxed), 1);
+	}
+
+	#[test]
+	fn unhealthy_endpoint_is_rechecked_after_interval() {
+		let client = FailoverClient::new(vec![
+			TestClient::new("first", false),
+			TestClient::new("second", true),
+		])
+		.with_recheck_interval(Duration::ZERO);
+
+		assert_eq!(request(&client).unwrap(), "second");
+		client.clients()[0].connected.store(true, Ordering::Relaxed);
+
+		assert_eq!(request(&client).unwrap(), "first");
+		assert!(client.is_healthy(0));
+	}
+
+	#[test]
+	fn round_robin_strategy_alternates_endpoints() {
+		let client = FailoverClient::new(vec![
+			TestClient::new("first", true),
+			TestClient::new("second", true),
+		])
+		.with_strategy(SelectionStrategy::RoundRobin);
+
+		assert_eq!(request(&client).unwrap(), "first");
+		assert_eq!(request(&client).unwrap(), "second");
+		assert_eq!(request(&client).unwrap(), "first");
+	}
+
+	#[test]
+	fn lowest_latency_strategy_prefers_fastest_endpoint() {
+		let client = FailoverClient::new(vec![
+			TestClient::new("first", true),
+			TestClient::new("second", true),
+		])
+		.with_strategy(SelectionStrategy::LowestLatency);
+		client.report_success(0, Duration::from_millis(100));
+		client.report_success(1, Duration::from_millis(10));
+
+		assert_eq!(request(&client).unwrap(), "second");
+	}
+
+	#[test]
+	fn all_endpoints_failing_returns_last_error() {
+		let client = FailoverClient::new(vec![
+			TestClient::new("first", false),
+			TestClient::new("second", false),
+		]);
+
+		assert!(matches!(request(&client), Err(Error::ConnectionClosed)));
+		// Unhealthy endpoints are still tried if there is no healthy one left.
+		assert!(matches!(request(&client), Err(Error::ConnectionClosed)));
+		assert_eq!(client.clients()[0].requests.load(Ordering::Relaxed), 2);
+	}
+
+	#[test]
+	fn no_endpoints_returns_error() {
+		let client = FailoverClient::<TestClient>::new(Vec::new());
+
+		assert!(matches!(request(&client), Err(Error::MaxConnectionAttemptsExceeded)));
+	}
+}
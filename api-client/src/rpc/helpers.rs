@@ -15,28 +15,137 @@
 
 */
 
+use crate::rpc::{Error, Result, error::JsonRpcError};
+#[cfg(any(feature = "tungstenite-client", all(feature = "ipc-client", unix)))]
+use ac_primitives::RpcParams;
 use alloc::{
 	format,
 	string::{String, ToString},
+	vec::Vec,
 };
+use serde::{Deserialize, de::DeserializeOwned};
 use serde_json::Value;
 
+/// Creates a json-rpc request without id, for clients that assign the ids themselves.
+#[cfg(any(feature = "tungstenite-client", all(feature = "ipc-client", unix)))]
+pub fn to_json_value_req(method: &str, params: RpcParams) -> Result<Value> {
+	Ok(serde_json::json!({
+		"method": method,
+		"params": params.to_json_value()?,
+		"jsonrpc": "2.0",
+	}))
+}
+
+/// Extracts the result of a json-rpc response.
+pub fn read_result<R: DeserializeOwned>(mut value: Value) -> Result<R> {
+	if let Some(error) = read_error(&value) {
+		return Err(error)
+	}
+	Ok(serde_json::from_value(value["result"].take())?)
+}
+
 pub fn read_subscription_id(value: &Value) -> Option<String> {
 	value["result"].as_str().map(|str| str.to_string())
 }
 
-pub fn read_error_message(value: &Value, msg: &str) -> String {
-	match value["error"].as_str() {
-		Some(error_message) => error_message.to_string(),
-		None => format!("Unexpected Response: {msg}"),
+/// Returns the error of a json-rpc response, if it contains one.
+pub fn read_error(value: &Value) -> Option<Error> {
+	let error = &value["error"];
+	if error.is_null() {
+		return None
+	}
+	match JsonRpcError::deserialize(error) {
+		Ok(error) => Some(Error::JsonRpc(error)),
+		Err(_) => Some(Error::UnexpectedResponse(value.to_string())),
+	}
+}
+
+/// Extracts the results of a json-rpc batch response. The node may answer in any order,
+/// so the responses are sorted by their id, which is expected to be the index of the
+/// request within the batch (see `to_json_batch_req`).
+pub fn read_batch_results(value: Value, batch_len: usize) -> Result<Vec<Result<Value>>> {
+	let responses = match value {
+		Value::Array(responses) => responses,
+		// A single response is returned if the batch as a whole was rejected.
+		_ => return Err(Error::UnexpectedResponse(value.to_string())),
+	};
+	let mut results: Vec<Option<Result<Value>>> = (0..batch_len).map(|_| None).collect();
+	for mut response in responses {
+		let index = response["id"]
+			.as_u64()
+			.map(|id| id as usize)
+			.filter(|index| *index < batch_len)
+			.ok_or_else(|| Error::UnexpectedResponse(response.to_string()))?;
+		results[index] = match read_error(&response) {
+			Some(error) => Some(Err(error)),
+			None => Some(Ok(response["result"].take())),
+		};
 	}
+	results
+		.into_iter()
+		.enumerate()
+		.map(|(index, result)| {
+			result.ok_or_else(|| {
+				Error::UnexpectedResponse(format!("Missing response for batch request {index}"))
+			})
+		})
+		.collect()
+}
+
+/// Incoming message of a client that assigns the ids of its requests itself, see
+/// [`route_message`].
+#[cfg(any(feature = "tungstenite-client", all(feature = "ipc-client", unix)))]
+#[derive(Debug, Clone, PartialEq)]
+pub enum IncomingMessage {
+	/// Response to the request with the given id.
+	Response { id: u64, value: Value },
+	/// Response to the batch whose first request has the given id. The ids of the `responses`
+	/// are already mapped back to the index within the batch, see [`read_batch_results`].
+	BatchResponse { first_id: u64, responses: Value },
+	/// Notification of the subscription with the given id, reduced to its result.
+	Notification { subscription_id: String, result: Value },
+	/// Error response without id, e.g. because the node could not parse a request or rejected a
+	/// batch as a whole. It can not be matched to a request, so the client should fail all
+	/// requests waiting for their response.
+	ErrorWithoutId(Value),
+	/// Anything else, which is dropped.
+	Unexpected(Value),
 }
 
-pub fn subscription_id_matches(value: &Value, subscription_id: &str) -> bool {
-	match value["params"]["subscription"].as_str() {
-		Some(retrieved_subscription_id) => subscription_id == retrieved_subscription_id,
-		None => false,
+/// Determines the request or subscription an incoming json-rpc message belongs to.
+/// Batch requests are expected to carry consecutive ids, so the smallest id of a batch
+/// response is the id of its first request.
+#[cfg(any(feature = "tungstenite-client", all(feature = "ipc-client", unix)))]
+pub fn route_message(mut value: Value) -> IncomingMessage {
+	if let Value::Array(responses) = &mut value {
+		let Some(first_id) = responses.iter().filter_map(|response| response["id"].as_u64()).min()
+		else {
+			return IncomingMessage::ErrorWithoutId(value)
+		};
+		for response in responses.iter_mut() {
+			if let Some(id) = response["id"].as_u64() {
+				response["id"] = (id - first_id).into();
+			}
+		}
+		return IncomingMessage::BatchResponse { first_id, responses: value }
+	}
+	if let Some(id) = value["id"].as_u64() {
+		return IncomingMessage::Response { id, value }
 	}
+	if let Some(subscription_id) = value["params"]["subscription"].as_str().map(String::from) {
+		let result = value["params"]["result"].take();
+		return IncomingMessage::Notification { subscription_id, result }
+	}
+	if !value["error"].is_null() {
+		return IncomingMessage::ErrorWithoutId(value)
+	}
+	IncomingMessage::Unexpected(value)
+}
+
+/// Returns the error of a json-rpc response that is known to have failed.
+#[cfg(any(feature = "tungstenite-client", all(feature = "ipc-client", unix)))]
+pub fn response_error(value: &Value) -> Error {
+	read_error(value).unwrap_or_else(|| Error::UnexpectedResponse(value.to_string()))
 }
 
 #[cfg(test)]
@@ -71,78 +180,158 @@ mod tests {
 	}
 
 	#[test]
-	fn read_error_message_returns_error_if_available() {
-		let error_message = "some_error_message";
+	fn read_result_returns_result_or_error() {
+		let value = json!({ "result": 42, "id": 1, "jsonrpc": "2.0" });
+		assert_eq!(read_result::<u32>(value).unwrap(), 42);
+
 		let value = json!({
-			"error": error_message,
+			"error": { "code": -32601, "message": "Method not found" },
+			"id": 1,
+			"jsonrpc": "2.0",
+		});
+		assert!(matches!(read_result::<u32>(value), Err(Error::JsonRpc(e)) if e.code == -32601));
+	}
+
+	#[test]
+	fn read_error_returns_json_rpc_error_if_available() {
+		let value = json!({
+			"error": { "code": 1014, "message": "Priority is too low", "data": "(0 vs 0)" },
 			"id": 43,
-			"and_so_on": "test",
+			"jsonrpc": "2.0",
 		});
 
-		let msg = serde_json::to_string(&value).unwrap();
+		let error = read_error(&value);
 
-		let message = read_error_message(&value, &msg);
-		assert!(message.contains(error_message));
-		assert!(message.contains("error"));
+		assert!(matches!(
+			error,
+			Some(Error::JsonRpc(JsonRpcError { code: 1014, ref message, data: Some(_) }))
+				if message == "Priority is too low"
+		));
 	}
 
 	#[test]
-	fn read_error_message_returns_full_msg_if_error_is_not_available() {
+	fn read_error_returns_full_msg_if_error_is_no_error_object() {
 		let error_message = "some_error_message";
 		let value = json!({
-			"result": error_message,
+			"error": error_message,
 			"id": 43,
 			"and_so_on": "test",
 		});
 
-		let msg = serde_json::to_string(&value).unwrap();
+		let error = read_error(&value);
 
-		let message = read_error_message(&value, &msg);
-		assert!(message.contains(&msg));
+		assert!(
+			matches!(error, Some(Error::UnexpectedResponse(msg)) if msg.contains(error_message))
+		);
 	}
 
 	#[test]
-	fn subscription_id_matches_returns_true_for_equal_id() {
-		let subcription_id = "tejkataa12124a";
-		let value = json!({
-			"params": {
-				"subscription": subcription_id,
-				"message": "Test"
-			},
-			"id": 43,
-			"and_so_on": "test",
-		});
+	fn read_error_returns_none_for_result() {
+		let value = json!({ "result": "0x00", "id": 43, "jsonrpc": "2.0" });
 
-		assert!(subscription_id_matches(&value, subcription_id));
+		assert!(read_error(&value).is_none());
 	}
 
 	#[test]
-	fn subscription_id_matches_returns_false_for_not_equal_id() {
-		let subcription_id = "tejkataa12124a";
-		let value = json!({
-			"params": {
-				"subscription": "something else",
-				"message": "Test"
-			},
-			"id": 43,
-			"and_so_on": "test",
+	fn read_batch_results_sorts_by_id() {
+		let value = json!([
+			{ "jsonrpc": "2.0", "id": 1, "result": "second" },
+			{ "jsonrpc": "2.0", "id": 0, "result": "first" },
+		]);
+
+		let results = read_batch_results(value, 2).unwrap();
+
+		assert_eq!(results.len(), 2);
+		assert_eq!(results[0].as_ref().unwrap(), &json!("first"));
+		assert_eq!(results[1].as_ref().unwrap(), &json!("second"));
+	}
+
+	#[test]
+	fn read_batch_results_keeps_single_errors() {
+		let value = json!([
+			{ "jsonrpc": "2.0", "id": 0, "result": null },
+			{ "jsonrpc": "2.0", "id": 1, "error": { "code": -32601, "message": "Method not found" } },
+		]);
+
+		let results = read_batch_results(value, 2).unwrap();
+
+		assert_eq!(results[0].as_ref().unwrap(), &Value::Null);
+		assert!(matches!(results[1], Err(Error::JsonRpc(JsonRpcError { code: -32601, .. }))));
+	}
+
+	#[test]
+	fn read_batch_results_fails_for_missing_response() {
+		let value = json!([{ "jsonrpc": "2.0", "id": 0, "result": "first" }]);
+
+		assert!(read_batch_results(value, 2).is_err());
+	}
+
+	#[test]
+	fn read_batch_results_fails_for_non_batch_response() {
+		let value = json!({ "jsonrpc": "2.0", "id": null, "error": { "code": -32600 } });
+
+		assert!(read_batch_results(value, 1).is_err());
+	}
+
+	#[cfg(any(feature = "tungstenite-client", all(feature = "ipc-client", unix)))]
+	#[test]
+	fn route_message_maps_batch_ids_to_indices() {
+		let value = json!([
+			{ "jsonrpc": "2.0", "id": 8, "result": "second" },
+			{ "jsonrpc": "2.0", "id": 7, "result": "first" },
+		]);
+
+		let IncomingMessage::BatchResponse { first_id, responses } = route_message(value) else {
+			panic!("Expected a batch response")
+		};
+
+		assert_eq!(first_id, 7);
+		let results = read_batch_results(responses, 2).unwrap();
+		assert_eq!(results[0].as_ref().unwrap(), &json!("first"));
+		assert_eq!(results[1].as_ref().unwrap(), &json!("second"));
+	}
+
+	#[cfg(any(feature = "tungstenite-client", all(feature = "ipc-client", unix)))]
+	#[test]
+	fn route_message_distinguishes_responses_and_notifications() {
+		let response = json!({ "jsonrpc": "2.0", "id": 3, "result": "0x00" });
+		assert_eq!(
+			route_message(response.clone()),
+			IncomingMessage::Response { id: 3, value: response }
+		);
+
+		let notification = json!({
+			"jsonrpc": "2.0",
+			"method": "chain_newHead",
+			"params": { "subscription": "sub", "result": 1 },
 		});
+		assert_eq!(
+			route_message(notification),
+			IncomingMessage::Notification { subscription_id: "sub".into(), result: json!(1) }
+		);
 
-		assert!(!subscription_id_matches(&value, subcription_id));
+		let unexpected = json!({ "jsonrpc": "2.0", "method": "unknown" });
+		assert_eq!(route_message(unexpected.clone()), IncomingMessage::Unexpected(unexpected));
 	}
 
+	#[cfg(any(feature = "tungstenite-client", all(feature = "ipc-client", unix)))]
 	#[test]
-	fn subscription_id_matches_returns_false_for_missing_subscription() {
-		let subcription_id = "tejkataa12124a";
-		let value = json!({
-			"params": {
-				"result": subcription_id,
-				"message": "Test"
-			},
-			"id": 43,
-			"and_so_on": "test",
+	fn route_message_detects_errors_without_id() {
+		let rejected_batch = json!({
+			"jsonrpc": "2.0",
+			"id": null,
+			"error": { "code": -32600, "message": "Invalid request" },
 		});
+		let IncomingMessage::ErrorWithoutId(value) = route_message(rejected_batch) else {
+			panic!("Expected an error without id")
+		};
+		assert!(matches!(response_error(&value), Error::JsonRpc(e) if e.code == -32600));
 
-		assert!(!subscription_id_matches(&value, subcription_id));
+		let parse_errors = json!([{
+			"jsonrpc": "2.0",
+			"id": null,
+			"error": { "code": -32700, "message": "Parse error" },
+		}]);
+		assert!(matches!(route_message(parse_errors), IncomingMessage::ErrorWithoutId(_)));
 	}
 }
@@ -0,0 +1,227 @@
+/*
+   Copyright 2019 Supercomputing Systems AG
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+	   http://www.apache.org/licenses/LICENSE-2.0
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Json-rpc client talking plain http to the node.
+//!
+//! In sync mode the requests are sent with the blocking `ureq` crate, in async mode the
+//! `jsonrpsee` http client is used. Http does not support subscriptions, so every call to
+//! [`Subscribe::subscribe`] returns [`Error::SubscriptionNotSupported`].
+
+use crate::rpc::{
+	BatchRequest, Error, HandleSubscription, MaybeSend, Request, Result, RpcParams, Subscribe,
+};
+use core::{fmt::Debug, marker::PhantomData};
+use serde::de::DeserializeOwned;
+use url::Url;
+
+#[cfg(not(feature = "sync-api"))]
+use crate::rpc::jsonrpsee_client::{RpcParamsWrapper, batch_request};
+#[cfg(not(feature = "sync-api"))]
+use jsonrpsee::{
+	core::client::ClientT,
+	http_client::{HttpClient, HttpClientBuilder},
+};
+#[cfg(not(feature = "sync-api"))]
+use std::sync::Arc;
+
+#[cfg(feature = "sync-api")]
+use crate::rpc::{helpers, to_json_batch_req, to_json_req};
+#[cfg(feature = "sync-api")]
+use log::*;
+#[cfg(feature = "sync-api")]
+use serde_json::Value;
+
+#[derive(Clone)]
+pub struct HttpRpcClient {
+	url: Url,
+	#[cfg(feature = "sync-api")]
+	agent: ureq::Agent,
+	#[cfg(not(feature = "sync-api"))]
+	inner: Arc<HttpClient>,
+}
+
+impl HttpRpcClient {
+	/// Create a new client with the given url string.
+	/// Example url input: "http://127.0.0.1:9944"
+	pub fn new(url: &str) -> Result<Self> {
+		let url: Url = Url::parse(url)?;
+
+		#[cfg(feature = "sync-api")]
+		let client = Self { agent: ureq::Agent::new_with_defaults(), url };
+
+		#[cfg(not(feature = "sync-api"))]
+		let client = {
+			let inner = HttpClientBuilder::default()
+				.build(url.as_str())
+				.map_err(|e| Error::Client(Box::new(e)))?;
+			Self { inner: Arc::new(inner), url }
+		};
+
+		Ok(client)
+	}
+
+	/// Create a new client with the given address and port.
+	/// Example input:
+	/// - address: "http://127.0.0.1"
+	/// - port: 9944
+	pub fn new_with_port(address: &str, port: u32) -> Result<Self> {
+		let url = format!("{address}:{port:?}");
+		Self::new(&url)
+	}
+
+	/// Create a new client with a local address and default Substrate node port.
+	pub fn with_default_url() -> Self {
+		// This unwrap is safe as is only regards the url parsing, which is tested.
+		Self::new("http://127.0.0.1:9944").unwrap()
+	}
+
+	/// The url of the node this client is sending its requests to.
+	pub fn url(&self) -> &Url {
+		&self.url
+	}
+}
+
+#[maybe_async::sync_impl]
+impl HttpRpcClient {
+	fn post(&self, json_req: String) -> Result<Value> {
+		let mut response = self
+			.agent
+			.post(self.url.as_str())
+			.header("Content-Type", "application/json")
+			.send(json_req)?;
+		let msg = response.body_mut().read_to_string()?;
+		trace!("Got http response {msg}");
+		Ok(serde_json::from_str(&msg)?)
+	}
+}
+
+#[maybe_async::sync_impl]
+impl Request for HttpRpcClient {
+	fn request<R: DeserializeOwned + MaybeSend>(
+		&self,
+		method: &str,
+		params: RpcParams,
+	) -> Result<R> {
+		let json_req = to_json_req(method, params)?;
+		let value = self.post(json_req)?;
+		helpers::read_result(value)
+	}
+}
+
+#[ac_async_macros::async_impl]
+impl Request for HttpRpcClient {
+	async fn request<R: DeserializeOwned + MaybeSend>(
+		&self,
+		method: &str,
+		params: RpcParams,
+	) -> Result<R> {
+		self.inner.request(method, RpcParamsWrapper(params)).await.map_err(Error::from)
+	}
+}
+
+#[maybe_async::sync_impl]
+impl BatchRequest for HttpRpcClient {
+	fn batch_request<R: DeserializeOwned + Debug + MaybeSend>(
+		&self,
+		requests: Vec<(&str, RpcParams)>,
+	) -> Result<Vec<Result<R>>> {
+		if requests.is_empty() {
+			return Ok(Vec::new())
+		}
+		let batch_len = requests.len();
+		let value = self.post(to_json_batch_req(requests)?)?;
+		let results = helpers::read_batch_results(value, batch_len)?
+			.into_iter()
+			.map(|result| Ok(serde_json::from_value(result?)?))
+			.collect();
+		Ok(results)
+	}
+}
+
+#[ac_async_macros::async_impl]
+impl BatchRequest for HttpRpcClient {
+	async fn batch_request<R: DeserializeOwned + Debug + MaybeSend>(
+		&self,
+		requests: Vec<(&str, RpcParams)>,
+	) -> Result<Vec<Result<R>>> {
+		batch_request(&*self.inner, requests).await
+	}
+}
+
+#[ac_async_macros::maybe_async]
+impl Subscribe for HttpRpcClient {
+	type Subscription<Notification>
+		= HttpSubscription<Notification>
+	where
+		Notification: DeserializeOwned + MaybeSend;
+
+	async fn subscribe<Notification: DeserializeOwned + MaybeSend>(
+		&self,
+		sub: &str,
+		_params: RpcParams,
+		_unsub: &str,
+	) -> Result<Self::Subscription<Notification>> {
+		Err(Error::SubscriptionNotSupported(sub.into()))
+	}
+}
+
+/// Placeholder subscription of the [`HttpRpcClient`].
+///
+/// It can never be created, because http does not support subscriptions. It only exists so that
+/// the `HttpRpcClient` can be used with the subscription based apis, which will then return
+/// a [`Error::SubscriptionNotSupported`] error.
+#[derive(Debug)]
+pub struct HttpSubscription<Notification> {
+	_phantom: PhantomData<Notification>,
+}
+
+#[ac_async_macros::maybe_async]
+impl<Notification: DeserializeOwned + MaybeSend> HandleSubscription<Notification>
+	for HttpSubscription<Notification>
+{
+	async fn next(&mut self) -> Option<Result<Notification>> {
+		None
+	}
+
+	async fn unsubscribe(self) -> Result<()> {
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn client_new() {
+		let port = 9944;
+		let address = "http://127.0.0.1";
+		let client = HttpRpcClient::new_with_port(address, port).unwrap();
+
+		let expected_url = Url::parse("http://127.0.0.1:9944").unwrap();
+		assert_eq!(client.url, expected_url);
+	}
+
+	#[test]
+	fn client_with_default_url() {
+		let expected_url = Url::parse("http://127.0.0.1:9944").unwrap();
+		let client = HttpRpcClient::with_default_url();
+
+		assert_eq!(client.url, expected_url);
+	}
+
+	#[test]
+	fn client_new_fails_for_invalid_url() {
+		assert!(HttpRpcClient::new("not a url").is_err());
+	}
+}
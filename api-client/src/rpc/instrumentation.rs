@@ -0,0 +1,433 @@
+/*
+   Copyright 2019 Supercomputing Systems AG
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+	   http://www.apache.org/licenses/LICENSE-2.0
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Rpc client wrapper emitting `tracing` spans and counting the calls of the inner client.
+
+use crate::rpc::{BatchRequest, Error, HandleSubscription, Request, Result, Subscribe};
+use ac_primitives::{MaybeSend, RpcParams};
+use core::{fmt::Debug, marker::PhantomData, time::Duration};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::{
+	collections::HashMap,
+	io,
+	sync::{
+		Arc, Mutex, MutexGuard,
+		atomic::{AtomicUsize, Ordering},
+	},
+	time::Instant,
+};
+use tracing::{Span, field::Empty};
+
+/// Rpc client wrapper, which instruments every call of the inner client.
+///
+/// Every request, batch request and subscription is executed within a `tracing` span named
+/// `rpc_request`, `rpc_batch_request` or `rpc_subscribe`. The spans carry the method, the
+/// latency in milliseconds, the size of the json response in bytes and, if the call failed,
+/// the kind of the error (see [`error_kind`]). Additionally, all calls are counted in the
+/// [`RpcMetrics`], which can be read out at any time via [`InstrumentedClient::metrics`].
+#[derive(Debug)]
+pub struct InstrumentedClient<Client> {
+	inner: Client,
+	metrics: Arc<RpcMetrics>,
+}
+
+impl<Client> InstrumentedClient<Client> {
+	/// Instrument the `inner` client.
+	pub fn new(inner: Client) -> Self {
+		Self { inner, metrics: Default::default() }
+	}
+
+	/// The counters of the client. The returned handle stays valid after the client has been
+	/// moved, e.g. into the `Api`.
+	pub fn metrics(&self) -> Arc<RpcMetrics> {
+		self.metrics.clone()
+	}
+
+	/// The wrapped client.
+	pub fn inner(&self) -> &Client {
+		&self.inner
+	}
+}
+
+/// Counters of an [`InstrumentedClient`].
+#[derive(Debug, Default)]
+pub struct RpcMetrics {
+	methods: Mutex<HashMap<String, MethodMetrics>>,
+	active_subscriptions: AtomicUsize,
+}
+
+/// Counters of a single rpc method.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MethodMetrics {
+	/// Number of requests, including failed ones. For subscription methods, this is the
+	/// number of subscription attempts.
+	pub requests: u64,
+	/// Number of requests that failed.
+	pub failures: u64,
+	/// Summed up latency of all requests.
+	pub total_latency: Duration,
+	/// Summed up size of all json responses in bytes.
+	pub response_bytes: u64,
+	/// Number of received subscription notifications.
+	pub notifications: u64,
+}
+
+impl RpcMetrics {
+	/// The counters of all methods that have been called so far.
+	pub fn methods(&self) -> HashMap<String, MethodMetrics> {
+		self.lock().clone()
+	}
+
+	/// The counters of the given `method`.
+	pub fn method(&self, method: &str) -> MethodMetrics {
+		self.lock().get(method).copied().unwrap_or_default()
+	}
+
+	/// Total number of requests of all methods.
+	pub fn requests(&self) -> u64 {
+		self.lock().values().map(|metrics| metrics.requests).sum()
+	}
+
+	/// Total number of failed requests of all methods.
+	pub fn failures(&self) -> u64 {
+		self.lock().values().map(|metrics| metrics.failures).sum()
+	}
+
+	/// Number of subscriptions that have been established and not yet been dropped.
+	pub fn active_subscriptions(&self) -> usize {
+		self.active_subscriptions.load(Ordering::Relaxed)
+	}
+
+	fn record_call(&self, method: &str, latency: Duration, response_bytes: usize, failed: bool) {
+		let mut methods = self.lock();
+		let metrics = methods.entry(method.into()).or_default();
+		metrics.requests += 1;
+		metrics.failures += u64::from(failed);
+		metrics.total_latency += latency;
+		metrics.response_bytes += response_bytes as u64;
+	}
+
+	fn record_notification(&self, method: &str) {
+		self.lock().entry(method.into()).or_default().notifications += 1;
+	}
+
+	fn lock(&self) -> MutexGuard<'_, HashMap<String, MethodMetrics>> {
+		// The counters stay valid even if a panic happened while they were locked.
+		self.methods.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+	}
+}
+
+/// Short, stable description of the kind of `error`, as recorded in the `error_kind` span field.
+pub fn error_kind(error: &Error) -> &'static str {
+	match error {
+		Error::JsonRpc(_) => "json_rpc",
+		Error::SerdeJson(_) => "serde",
+		Error::RequestTimeout => "timeout",
+		Error::SubscriptionNotSupported(_) => "subscription_not_supported",
+		Error::UnexpectedResponse(_) => "unexpected_response",
+		error if error.is_connection_error() => "connection",
+		_ => "other",
+	}
+}
+
+#[ac_async_macros::maybe_async]
+impl<Client: Request> Request for InstrumentedClient<Client> {
+	async fn request<R: DeserializeOwned + MaybeSend>(
+		&self,
+		method: &str,
+		params: RpcParams,
+	) -> Result<R> {
+		let span = tracing::debug_span!(
+			"rpc_request",
+			method,
+			latency_ms = Empty,
+			response_size = Empty,
+			error_kind = Empty
+		);
+		let start = Instant::now();
+		let result = in_span(&span, || self.inner.request::<Value>(method, params)).await;
+		let latency = start.elapsed();
+
+		let response_bytes = result.as_ref().map(serialized_size).unwrap_or_default();
+		let result = result.and_then(|value| Ok(serde_json::from_value(value)?));
+		self.metrics.record_call(method, latency, response_bytes, result.is_err());
+		record_outcome(&span, latency, response_bytes, result.as_ref().err());
+		result
+	}
+}
+
+#[ac_async_macros::maybe_async]
+impl<Client: BatchRequest> BatchRequest for InstrumentedClient<Client> {
+	async fn batch_request<R: DeserializeOwned + Debug + MaybeSend>(
+		&self,
+		requests: Vec<(&str, RpcParams)>,
+	) -> Result<Vec<Result<R>>> {
+		let methods: Vec<String> = requests.iter().map(|(method, _)| method.to_string()).collect();
+		let span = tracing::debug_span!(
+			"rpc_batch_request",
+			requests = methods.len(),
+			latency_ms = Empty,
+			response_size = Empty,
+			error_kind = Empty
+		);
+		let start = Instant::now();
+		let result = in_span(&span, || self.inner.batch_request::<Value>(requests)).await;
+		let latency = start.elapsed();
+
+		let results = match result {
+			Ok(results) => results,
+			Err(e) => {
+				for method in &methods {
+					self.metrics.record_call(method, latency, 0, true);
+				}
+				record_outcome(&span, latency, 0, Some(&e));
+				return Err(e)
+			},
+		};
+		let mut batch_response_bytes = 0;
+		let results = methods
+			.iter()
+			.zip(results)
+			.map(|(method, result)| {
+				let response_bytes = result.as_ref().map(serialized_size).unwrap_or_default();
+				batch_response_bytes += response_bytes;
+				let result = result.and_then(|value| Ok(serde_json::from_value(value)?));
+				self.metrics.record_call(method, latency, response_bytes, result.is_err());
+				result
+			})
+			.collect();
+		record_outcome(&span, latency, batch_response_bytes, None);
+		Ok(results)
+	}
+}
+
+#[ac_async_macros::maybe_async]
+impl<Client: Subscribe> Subscribe for InstrumentedClient<Client> {
+	type Subscription<Notification>
+		= InstrumentedSubscription<Client::Subscription<Notification>, Notification>
+	where
+		Notification: DeserializeOwned + MaybeSend;
+
+	async fn subscribe<Notification: DeserializeOwned + MaybeSend>(
+		&self,
+		sub: &str,
+		params: RpcParams,
+		unsub: &str,
+	) -> Result<Self::Subscription<Notification>> {
+		let span = tracing::debug_span!(
+			"rpc_subscribe",
+			method = sub,
+			latency_ms = Empty,
+			response_size = Empty,
+			error_kind = Empty
+		);
+		let start = Instant::now();
+		let result = in_span(&span, || self.inner.subscribe(sub, params, unsub)).await;
+		let latency = start.elapsed();
+
+		self.metrics.record_call(sub, latency, 0, result.is_err());
+		record_outcome(&span, latency, 0, result.as_ref().err());
+		Ok(InstrumentedSubscription {
+			inner: result?,
+			method: sub.into(),
+			active: ActiveSubscription::new(self.metrics.clone()),
+			_phantom: Default::default(),
+		})
+	}
+}
+
+/// Subscription of the [`InstrumentedClient`], counting the received notifications.
+/// It is counted as active subscription until it is dropped.
+#[derive(Debug)]
+pub struct InstrumentedSubscription<Subscription, Notification> {
+	inner: Subscription,
+	method: String,
+	active: ActiveSubscription,
+	_phantom: PhantomData<Notification>,
+}
+
+/// Counts a subscription as active as long as it exists.
+#[derive(Debug)]
+struct ActiveSubscription {
+	metrics: Arc<RpcMetrics>,
+}
+
+impl ActiveSubscription {
+	fn new(metrics: Arc<RpcMetrics>) -> Self {
+		metrics.active_subscriptions.fetch_add(1, Ordering::Relaxed);
+		Self { metrics }
+	}
+}
+
+impl Drop for ActiveSubscription {
+	fn drop(&mut self) {
+		self.metrics.active_subscriptions.fetch_sub(1, Ordering::Relaxed);
+	}
+}
+
+#[ac_async_macros::maybe_async]
+impl<Subscription, Notification> HandleSubscription<Notification>
+	for InstrumentedSubscription<Subscription, Notification>
+where
+	Subscription: HandleSubscription<Notification>,
+	Notification: DeserializeOwned + MaybeSend,
+{
+	async fn next(&mut self) -> Option<Result<Notification>> {
+		let notification = self.inner.next().await?;
+		self.active.metrics.record_notification(&self.method);
+		if let Err(e) = &notification {
+			tracing::debug!(method = %self.method, error_kind = error_kind(e), "Subscription error");
+		}
+		Some(notification)
+	}
+
+	async fn unsubscribe(self) -> Result<()> {
+		self.inner.unsubscribe().await
+	}
+
+	fn subscription_id(&self) -> Option<String> {
+		self.inner.subscription_id()
+	}
+}
+
+fn record_outcome(span: &Span, latency: Duration, response_bytes: usize, error: Option<&Error>) {
+	span.record("latency_ms", latency.as_millis() as u64);
+	span.record("response_size", response_bytes as u64);
+	if let Some(error) = error {
+		span.record("error_kind", error_kind(error));
+	}
+}
+
+/// Runs `call` within the `span`.
+#[maybe_async::sync_impl]
+fn in_span<T>(span: &Span, call: impl FnOnce() -> T) -> T {
+	span.in_scope(call)
+}
+
+/// Runs the future returned by `call` within the `span`.
+#[maybe_async::async_impl]
+async fn in_span<F: core::future::Future>(span: &Span, call: impl FnOnce() -> F) -> F::Output {
+	use tracing::Instrument;
+	call().instrument(span.clone()).await
+}
+
+/// Returns the size of the serialized `value` without allocating it.
+fn serialized_size(value: &Value) -> usize {
+	struct ByteCounter(usize);
+
+	impl io::Write for ByteCounter {
+		fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+			self.0 += buf.len();
+			Ok(buf.len())
+		}
+
+		fn flush(&mut self) -> io::Result<()> {
+			Ok(())
+		}
+	}
+
+	let mut counter = ByteCounter(0);
+	// Writing to the counter can not fail.
+	let _ = serde_json::to_writer(&mut counter, value);
+	counter.0
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::rpc::mocks::RpcClientMock;
+	use ac_compose_macros::rpc_params;
+
+	#[test]
+	fn requests_and_failures_are_counted_per_method() {
+		let client = InstrumentedClient::new(
+			RpcClientMock::default().with_response("system_name", "node").with_error(
+				"author_submitExtrinsic",
+				1010,
+				"Invalid Transaction",
+			),
+		);
+
+		let _: String = client.request("system_name", rpc_params![]).unwrap();
+		let _: String = client.request("system_name", rpc_params![]).unwrap();
+		assert!(client.request::<String>("author_submitExtrinsic", rpc_params!["0x00"]).is_err());
+
+		let metrics = client.metrics();
+		assert_eq!(metrics.requests(), 3);
+		assert_eq!(metrics.failures(), 1);
+		let system_name = metrics.method("system_name");
+		assert_eq!(system_name.requests, 2);
+		assert_eq!(system_name.failures, 0);
+		assert_eq!(system_name.response_bytes, 2 * "\"node\"".len() as u64);
+		assert_eq!(metrics.method("author_submitExtrinsic").failures, 1);
+	}
+
+	#[test]
+	fn undeserializable_response_is_counted_as_failure() {
+		let client =
+			InstrumentedClient::new(RpcClientMock::default().with_response("system_name", "node"));
+
+		let result = client.request::<u32>("system_name", rpc_params![]);
+
+		assert!(matches!(&result, Err(e) if error_kind(e) == "serde"));
+		assert_eq!(client.metrics().method("system_name").failures, 1);
+	}
+
+	#[test]
+	fn batch_requests_are_counted_per_method() {
+		let client =
+			InstrumentedClient::new(RpcClientMock::default().with_response("system_name", "node"));
+
+		let results = client
+			.batch_request::<String>(vec![
+				("system_name", rpc_params![]),
+				("system_chain", rpc_params![]),
+			])
+			.unwrap();
+
+		assert!(results[0].is_ok());
+		assert!(results[1].is_err());
+		assert_eq!(client.metrics().method("system_name").requests, 1);
+		assert_eq!(client.metrics().method("system_chain").failures, 1);
+	}
+
+	#[test]
+	fn active_subscriptions_are_counted_until_dropped() {
+		let client = InstrumentedClient::new(
+			RpcClientMock::default().with_subscription("chain_subscribeNewHeads", vec![1u32, 2]),
+		);
+		let metrics = client.metrics();
+		let subscribe = || {
+			client
+				.subscribe::<u32>(
+					"chain_subscribeNewHeads",
+					rpc_params![],
+					"chain_unsubscribeNewHeads",
+				)
+				.unwrap()
+		};
+
+		let mut first = subscribe();
+		let second = subscribe();
+		assert_eq!(metrics.active_subscriptions(), 2);
+
+		assert_eq!(first.next().unwrap().unwrap(), 1);
+		assert_eq!(metrics.method("chain_subscribeNewHeads").notifications, 1);
+
+		first.unsubscribe().unwrap();
+		assert_eq!(metrics.active_subscriptions(), 1);
+		drop(second);
+		assert_eq!(metrics.active_subscriptions(), 0);
+	}
+}
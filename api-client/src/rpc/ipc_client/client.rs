@@ -0,0 +1,269 @@
+/*
+   Copyright 2019 Supercomputing Systems AG
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+	   http://www.apache.org/licenses/LICENSE-2.0
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use crate::rpc::{
+	BatchRequest, Error as RpcClientError, Request, Result, Subscribe, helpers,
+	ipc_client::{
+		connection::{Connection, PendingRequest},
+		subscription::IpcSubscriptionWrapper,
+	},
+};
+use ac_primitives::{MaybeSend, RpcParams};
+use serde::de::DeserializeOwned;
+use std::{
+	fmt::Debug,
+	os::unix::net::UnixStream,
+	path::{Path, PathBuf},
+	sync::{Arc, mpsc::channel},
+};
+
+/// Json-rpc client connecting to the node via unix domain socket (IPC).
+///
+/// Intended for services running on the same host as the node, which do not need the overhead
+/// of a websocket. The messages are exchanged as plain json, each message followed by a newline.
+/// All requests and subscriptions share one connection. It is not re-established once lost,
+/// all following requests fail with [`RpcClientError::ConnectionClosed`].
+/// Clones of the client share the same connection.
+#[derive(Debug, Clone)]
+pub struct IpcRpcClient {
+	path: PathBuf,
+	connection: Arc<Connection>,
+}
+
+impl IpcRpcClient {
+	/// Connect to the unix socket at the given `path`.
+	/// Example path: "/tmp/node.ipc"
+	pub fn new(path: impl AsRef<Path>) -> Result<Self> {
+		let path = path.as_ref().to_path_buf();
+		let stream = UnixStream::connect(&path)?;
+		Ok(Self { path, connection: Connection::start(stream)? })
+	}
+
+	/// The path of the unix socket the client is connected to.
+	pub fn path(&self) -> &Path {
+		&self.path
+	}
+}
+
+#[ac_async_macros::maybe_async]
+impl Request for IpcRpcClient {
+	async fn request<R: DeserializeOwned + MaybeSend>(
+		&self,
+		method: &str,
+		params: RpcParams,
+	) -> Result<R> {
+		let (response, receiver) = channel();
+		self.connection
+			.send(helpers::to_json_value_req(method, params)?, PendingRequest::Request(response))?;
+		let value = receiver.recv().map_err(|_| RpcClientError::ConnectionClosed)?;
+		helpers::read_result(value)
+	}
+}
+
+#[ac_async_macros::maybe_async]
+impl BatchRequest for IpcRpcClient {
+	async fn batch_request<R: DeserializeOwned + Debug + MaybeSend>(
+		&self,
+		requests: Vec<(&str, RpcParams)>,
+	) -> Result<Vec<Result<R>>> {
+		if requests.is_empty() {
+			return Ok(Vec::new())
+		}
+		let requests = requests
+			.into_iter()
+			.map(|(method, params)| helpers::to_json_value_req(method, params))
+			.collect::<Result<Vec<_>>>()?;
+		let (response, receiver) = channel();
+		self.connection.send_batch(requests, response)?;
+		let results = receiver
+			.recv()
+			.map_err(|_| RpcClientError::ConnectionClosed)??
+			.into_iter()
+			.map(|result| Ok(serde_json::from_value(result?)?))
+			.collect();
+		Ok(results)
+	}
+}
+
+#[ac_async_macros::maybe_async]
+impl Subscribe for IpcRpcClient {
+	type Subscription<Notification>
+		= IpcSubscriptionWrapper<Notification>
+	where
+		Notification: DeserializeOwned + MaybeSend;
+
+	async fn subscribe<Notification: DeserializeOwned + MaybeSend>(
+		&self,
+		sub: &str,
+		params: RpcParams,
+		unsub: &str,
+	) -> Result<Self::Subscription<Notification>> {
+		let (confirmation, confirmation_receiver) = channel();
+		let (notifications, receiver) = channel();
+		let pending_request = PendingRequest::Subscribe {
+			unsubscribe_method: unsub.into(),
+			confirmation,
+			notifications,
+		};
+		self.connection
+			.send(helpers::to_json_value_req(sub, params)?, pending_request)?;
+		let subscription_id =
+			confirmation_receiver.recv().map_err(|_| RpcClientError::ConnectionClosed)??;
+		Ok(IpcSubscriptionWrapper::new(receiver, subscription_id, self.connection.clone()))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::rpc::HandleSubscription;
+	use ac_compose_macros::rpc_params;
+	use serde_json::{Value, json};
+	use std::{
+		io::{BufRead, BufReader, Write},
+		os::unix::net::UnixListener,
+		thread,
+	};
+
+	/// Starts a json-rpc server on a unix socket, which serves a single connection.
+	fn start_server(name: &str) -> PathBuf {
+		let path = std::env::temp_dir().join(format!("ac-ipc-{name}-{}.ipc", std::process::id()));
+		let _ = std::fs::remove_file(&path);
+		let listener = UnixListener::bind(&path).unwrap();
+		thread::spawn(move || {
+			let (stream, _) = listener.accept().unwrap();
+			let mut writer = stream.try_clone().unwrap();
+			for line in BufReader::new(stream).lines() {
+				let request: Value = serde_json::from_str(&line.unwrap()).unwrap();
+				for response in respond(&request) {
+					writeln!(writer, "{response}").unwrap();
+				}
+			}
+		});
+		path
+	}
+
+	fn respond(request: &Value) -> Vec<Value> {
+		if let Value::Array(requests) = request {
+			// Answer batches in reverse order, as nodes may do.
+			let responses = requests.iter().rev().flat_map(respond).collect();
+			return vec![Value::Array(responses)]
+		}
+		let id = &request["id"];
+		match request["method"].as_str().unwrap() {
+			"system_name" => vec![json!({ "jsonrpc": "2.0", "id": id, "result": "node" })],
+			"chain_subscribeNewHeads" => {
+				let notification = |result: u32| {
+					json!({
+						"jsonrpc": "2.0",
+						"method": "chain_newHead",
+						"params": { "subscription": "sub", "result": result },
+					})
+				};
+				vec![
+					json!({ "jsonrpc": "2.0", "id": id, "result": "sub" }),
+					notification(1),
+					notification(2),
+				]
+			},
+			"chain_unsubscribeNewHeads" => {
+				assert_eq!(request["params"], json!(["sub"]));
+				vec![json!({ "jsonrpc": "2.0", "id": id, "result": true })]
+			},
+			// Nodes answer without id if they can't tell which request an error belongs to.
+			"oversized_request" => vec![json!({
+				"jsonrpc": "2.0",
+				"id": null,
+				"error": { "code": -32600, "message": "Request is too big" },
+			})],
+			method => vec![json!({
+				"jsonrpc": "2.0",
+				"id": id,
+				"error": { "code": -32601, "message": format!("Method not found: {method}") },
+			})],
+		}
+	}
+
+	#[test]
+	fn request_returns_result() {
+		let client = IpcRpcClient::new(start_server("request")).unwrap();
+
+		let name: String = client.request("system_name", rpc_params![]).unwrap();
+
+		assert_eq!(name, "node");
+	}
+
+	#[test]
+	fn error_response_returns_json_rpc_error() {
+		let client = IpcRpcClient::new(start_server("error")).unwrap();
+
+		let result = client.request::<String>("system_chain", rpc_params![]);
+
+		assert!(matches!(result, Err(RpcClientError::JsonRpc(e)) if e.code == -32601));
+	}
+
+	#[test]
+	fn error_without_id_fails_pending_request() {
+		let client = IpcRpcClient::new(start_server("error-without-id")).unwrap();
+
+		let result = client.request::<String>("oversized_request", rpc_params![]);
+
+		assert!(matches!(result, Err(RpcClientError::JsonRpc(e)) if e.code == -32600));
+		// The connection is still usable afterwards.
+		assert_eq!(client.request::<String>("system_name", rpc_params![]).unwrap(), "node");
+	}
+
+	#[test]
+	fn batch_results_are_returned_in_order() {
+		let client = IpcRpcClient::new(start_server("batch")).unwrap();
+
+		let results = client
+			.batch_request::<String>(vec![
+				("system_name", rpc_params![]),
+				("system_chain", rpc_params![]),
+			])
+			.unwrap();
+
+		assert_eq!(results[0].as_ref().unwrap(), "node");
+		assert!(results[1].is_err());
+	}
+
+	#[test]
+	fn subscription_receives_notifications() {
+		let client = IpcRpcClient::new(start_server("subscription")).unwrap();
+
+		let mut subscription: IpcSubscriptionWrapper<u32> = client
+			.subscribe("chain_subscribeNewHeads", rpc_params![], "chain_unsubscribeNewHeads")
+			.unwrap();
+
+		assert_eq!(subscription.next().unwrap().unwrap(), 1);
+		assert_eq!(subscription.next().unwrap().unwrap(), 2);
+		subscription.unsubscribe().unwrap();
+		// The connection is still usable afterwards.
+		assert_eq!(client.request::<String>("system_name", rpc_params![]).unwrap(), "node");
+	}
+
+	#[test]
+	fn closed_connection_fails_requests() {
+		let path = std::env::temp_dir().join(format!("ac-ipc-closed-{}.ipc", std::process::id()));
+		let _ = std::fs::remove_file(&path);
+		let listener = UnixListener::bind(&path).unwrap();
+		let client = IpcRpcClient::new(&path).unwrap();
+		// Accept and close the connection right away.
+		drop(listener.accept().unwrap());
+
+		let result = client.request::<String>("system_name", rpc_params![]);
+
+		assert!(result.unwrap_err().is_connection_error());
+	}
+}
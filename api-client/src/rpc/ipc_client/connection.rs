@@ -0,0 +1,299 @@
+/*
+   Copyright 2019 Supercomputing Systems AG
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+	   http://www.apache.org/licenses/LICENSE-2.0
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! The unix socket connection shared by all requests and subscriptions of an `IpcRpcClient`.
+//!
+//! Requests are written directly to the socket by the calling thread, tagged with a unique id.
+//! A background thread reads all incoming messages and forwards the responses to the waiting
+//! callers and the notifications to their subscriptions.
+
+use crate::rpc::{
+	Error as RpcClientError, Result,
+	helpers::{self, IncomingMessage},
+};
+use log::*;
+use serde_json::{Value, json};
+use std::{
+	collections::HashMap,
+	io::{BufReader, Write},
+	net::Shutdown,
+	os::unix::net::UnixStream,
+	sync::{
+		Arc, Mutex, MutexGuard, Weak,
+		atomic::{AtomicU64, Ordering},
+		mpsc::Sender,
+	},
+	thread,
+};
+
+/// Request waiting for its response.
+#[derive(Debug)]
+pub(crate) enum PendingRequest {
+	Request(Sender<Value>),
+	Batch {
+		len: usize,
+		response: Sender<Result<Vec<Result<Value>>>>,
+	},
+	Subscribe {
+		unsubscribe_method: String,
+		confirmation: Sender<Result<String>>,
+		notifications: Sender<Value>,
+	},
+}
+
+#[derive(Debug)]
+struct ActiveSubscription {
+	unsubscribe_method: String,
+	notifications: Sender<Value>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+	// Set once the socket has been closed. No requests are accepted afterwards.
+	closed: bool,
+	pending_requests: HashMap<u64, PendingRequest>,
+	subscriptions: HashMap<String, ActiveSubscription>,
+}
+
+/// Connection to the node. The socket is shut down once the connection is dropped, i.e. once
+/// the last client and subscription are gone.
+#[derive(Debug)]
+pub(crate) struct Connection {
+	writer: Mutex<UnixStream>,
+	next_id: AtomicU64,
+	state: Mutex<State>,
+}
+
+impl Connection {
+	/// Takes over the connected `stream` and starts the thread reading from it.
+	pub(crate) fn start(stream: UnixStream) -> Result<Arc<Self>> {
+		let reader = stream.try_clone()?;
+		let connection = Arc::new(Self {
+			writer: Mutex::new(stream),
+			next_id: AtomicU64::new(0),
+			state: Default::default(),
+		});
+		let weak = Arc::downgrade(&connection);
+		thread::Builder::new()
+			.name("ipc-rpc-client".into())
+			.spawn(move || read(reader, weak))?;
+		Ok(connection)
+	}
+
+	/// Sends the `request`, its response is forwarded according to the `pending_request`.
+	pub(crate) fn send(&self, mut request: Value, pending_request: PendingRequest) -> Result<()> {
+		let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+		request["id"] = id.into();
+		self.register_and_write(id, &request, pending_request)
+	}
+
+	/// Sends the `requests` as one batch. The results are returned via `response` in the
+	/// order of the requests.
+	pub(crate) fn send_batch(
+		&self,
+		mut requests: Vec<Value>,
+		response: Sender<Result<Vec<Result<Value>>>>,
+	) -> Result<()> {
+		let len = requests.len();
+		// The batch is identified by the id of its first request, the others follow in order.
+		let first_id = self.next_id.fetch_add(len as u64, Ordering::Relaxed);
+		for (id, request) in (first_id..).zip(requests.iter_mut()) {
+			request["id"] = id.into();
+		}
+		let pending_request = PendingRequest::Batch { len, response };
+		self.register_and_write(first_id, &Value::Array(requests), pending_request)
+	}
+
+	fn register_and_write(
+		&self,
+		id: u64,
+		message: &Value,
+		pending_request: PendingRequest,
+	) -> Result<()> {
+		{
+			// Register before sending, so the response can not arrive before the request is known.
+			let mut state = self.state();
+			if state.closed {
+				return Err(RpcClientError::ConnectionClosed)
+			}
+			state.pending_requests.insert(id, pending_request);
+		}
+		if let Err(e) = self.write(message) {
+			self.state().pending_requests.remove(&id);
+			return Err(e)
+		}
+		Ok(())
+	}
+
+	/// Stops forwarding the notifications of the subscription and returns the method to end it
+	/// at the node. Returns `None` if the subscription is not active anymore.
+	pub(crate) fn remove_subscription(&self, subscription_id: &str) -> Option<String> {
+		self.state()
+			.subscriptions
+			.remove(subscription_id)
+			.map(|subscription| subscription.unsubscribe_method)
+	}
+
+	fn write(&self, message: &Value) -> Result<()> {
+		trace!("Sending message {message}");
+		let mut bytes = serde_json::to_vec(message)?;
+		bytes.push(b'\n');
+		let mut writer = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+		writer.write_all(&bytes)?;
+		Ok(())
+	}
+
+	fn handle_message(&self, value: Value) {
+		match helpers::route_message(value) {
+			IncomingMessage::Response { id, value } => self.handle_response(id, value),
+			IncomingMessage::BatchResponse { first_id, responses } =>
+				self.handle_batch_response(first_id, responses),
+			IncomingMessage::Notification { subscription_id, result } =>
+				self.handle_notification(&subscription_id, result),
+			IncomingMessage::ErrorWithoutId(value) => self.fail_pending_requests(&value),
+			IncomingMessage::Unexpected(value) => warn!("Received unexpected message: {value}"),
+		}
+	}
+
+	fn handle_response(&self, id: u64, value: Value) {
+		let mut state = self.state();
+		match state.pending_requests.remove(&id) {
+			Some(PendingRequest::Request(response)) => {
+				let _ = response.send(value);
+			},
+			Some(PendingRequest::Subscribe { unsubscribe_method, confirmation, notifications }) =>
+				match helpers::read_subscription_id(&value) {
+					Some(subscription_id) => {
+						// Registered before the next message is read, so no notification is lost.
+						state.subscriptions.insert(
+							subscription_id.clone(),
+							ActiveSubscription { unsubscribe_method, notifications },
+						);
+						let _ = confirmation.send(Ok(subscription_id));
+					},
+					None => {
+						let _ = confirmation.send(Err(helpers::response_error(&value)));
+					},
+				},
+			Some(PendingRequest::Batch { response, .. }) => {
+				let _ = response.send(Err(RpcClientError::UnexpectedResponse(value.to_string())));
+			},
+			// E.g. the response to an unsubscription nobody waits for.
+			None => debug!("Received response for unknown request id {id}"),
+		}
+	}
+
+	fn handle_batch_response(&self, first_id: u64, responses: Value) {
+		let Some(PendingRequest::Batch { len, response }) =
+			self.state().pending_requests.remove(&first_id)
+		else {
+			warn!("Received batch response for unknown request id {first_id}");
+			return
+		};
+		let _ = response.send(helpers::read_batch_results(responses, len));
+	}
+
+	/// Fails all pending requests with the error `value`, because it is not known which of
+	/// them it belongs to.
+	fn fail_pending_requests(&self, value: &Value) {
+		warn!("Received error without id, failing all pending requests: {value}");
+		for (_, pending_request) in self.state().pending_requests.drain() {
+			match pending_request {
+				PendingRequest::Request(response) => {
+					let _ = response.send(value.clone());
+				},
+				PendingRequest::Batch { response, .. } => {
+					let _ = response.send(Err(helpers::response_error(value)));
+				},
+				PendingRequest::Subscribe { confirmation, .. } => {
+					let _ = confirmation.send(Err(helpers::response_error(value)));
+				},
+			}
+		}
+	}
+
+	fn handle_notification(&self, subscription_id: &str, result: Value) {
+		let mut state = self.state();
+		let Some(subscription) = state.subscriptions.get(subscription_id) else {
+			debug!("Received notification for unknown subscription {subscription_id}");
+			return
+		};
+		if subscription.notifications.send(result).is_ok() {
+			return
+		}
+		// The receiver has been dropped, so nobody is interested in this subscription anymore.
+		let Some(subscription) = state.subscriptions.remove(subscription_id) else { return };
+		drop(state);
+		let request = json!({
+			"method": subscription.unsubscribe_method,
+			"params": [subscription_id],
+			"jsonrpc": "2.0",
+			"id": self.next_id.fetch_add(1, Ordering::Relaxed),
+		});
+		// Nobody waits for the response, it is dropped as unknown upon arrival.
+		if let Err(e) = self.write(&request) {
+			warn!("Could not unsubscribe: {e:?}");
+		}
+	}
+
+	/// Fails all pending requests and ends all subscriptions.
+	fn close(&self) {
+		let mut state = self.state();
+		state.closed = true;
+		// Dropping the senders lets the waiting receivers fail.
+		state.pending_requests.clear();
+		state.subscriptions.clear();
+		drop(state);
+		let writer = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+		let _ = writer.shutdown(Shutdown::Both);
+	}
+
+	fn state(&self) -> MutexGuard<'_, State> {
+		// The state stays valid even if a panic happened while it was locked.
+		self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+	}
+}
+
+impl Drop for Connection {
+	fn drop(&mut self) {
+		// Lets the reading thread stop.
+		let writer = self.writer.get_mut().unwrap_or_else(|e| e.into_inner());
+		let _ = writer.shutdown(Shutdown::Both);
+	}
+}
+
+/// Reads all messages of the `stream` until it is closed or the `connection` has been dropped.
+fn read(stream: UnixStream, connection: Weak<Connection>) {
+	let messages =
+		serde_json::Deserializer::from_reader(BufReader::new(stream)).into_iter::<Value>();
+	for message in messages {
+		let Some(connection) = connection.upgrade() else { return };
+		match message {
+			Ok(value) => {
+				trace!("Got message {value}");
+				connection.handle_message(value)
+			},
+			Err(e) if e.is_io() || e.is_eof() => {
+				debug!("Connection closed: {e:?}");
+				break
+			},
+			Err(e) => {
+				warn!("Closing connection due to invalid message: {e:?}");
+				break
+			},
+		}
+	}
+	if let Some(connection) = connection.upgrade() {
+		connection.close();
+	}
+}
@@ -0,0 +1,88 @@
+/*
+   Copyright 2019 Supercomputing Systems AG
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+	   http://www.apache.org/licenses/LICENSE-2.0
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use crate::rpc::{
+	Error, HandleSubscription, Result,
+	helpers::{read_result, to_json_value_req},
+	ipc_client::connection::{Connection, PendingRequest},
+};
+use ac_primitives::{MaybeSend, RpcParams};
+use core::marker::PhantomData;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::sync::{
+	Arc,
+	mpsc::{Receiver, channel},
+};
+
+/// Subscription of the [`IpcRpcClient`](super::IpcRpcClient).
+///
+/// The subscription keeps the shared connection alive, even if all clients have been dropped.
+/// If the subscription is dropped without unsubscribing, it is ended at the node upon the next
+/// notification.
+#[derive(Debug)]
+pub struct IpcSubscriptionWrapper<Notification> {
+	receiver: Receiver<Value>,
+	subscription_id: String,
+	connection: Arc<Connection>,
+	_phantom: PhantomData<Notification>,
+}
+
+impl<Notification> IpcSubscriptionWrapper<Notification> {
+	pub(crate) fn new(
+		receiver: Receiver<Value>,
+		subscription_id: String,
+		connection: Arc<Connection>,
+	) -> Self {
+		Self { receiver, subscription_id, connection, _phantom: Default::default() }
+	}
+}
+
+#[ac_async_macros::maybe_async]
+impl<Notification: DeserializeOwned + MaybeSend> HandleSubscription<Notification>
+	for IpcSubscriptionWrapper<Notification>
+{
+	async fn next(&mut self) -> Option<Result<Notification>> {
+		// Fails once the subscription has been ended, e.g. because the connection was closed.
+		let notification = self.receiver.recv().ok()?;
+		Some(serde_json::from_value(notification).map_err(|e| e.into()))
+	}
+
+	async fn unsubscribe(self) -> Result<()> {
+		let Some(unsubscribe_method) = self.connection.remove_subscription(&self.subscription_id)
+		else {
+			// The connection has been closed, which ends all subscriptions anyway.
+			return Ok(())
+		};
+		let mut params = RpcParams::new();
+		params.insert(&self.subscription_id)?;
+		let (response, receiver) = channel();
+		self.connection.send(
+			to_json_value_req(&unsubscribe_method, params)?,
+			PendingRequest::Request(response),
+		)?;
+		let unsubscribed: bool =
+			read_result(receiver.recv().map_err(|_| Error::ConnectionClosed)?)?;
+		if !unsubscribed {
+			return Err(Error::UnexpectedResponse(format!(
+				"Node did not end subscription {}",
+				self.subscription_id
+			)))
+		}
+		Ok(())
+	}
+
+	fn subscription_id(&self) -> Option<String> {
+		Some(self.subscription_id.clone())
+	}
+}
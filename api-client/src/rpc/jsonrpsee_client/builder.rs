@@ -0,0 +1,163 @@
+/*
+   Copyright 2019 Supercomputing Systems AG
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+	   http://www.apache.org/licenses/LICENSE-2.0
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use crate::rpc::{
+	BackoffPolicy, Error, Result,
+	jsonrpsee_client::{JsonrpseeClient, ReconnectingJsonrpseeClient},
+};
+use core::time::Duration;
+use http::HeaderName;
+use jsonrpsee::{
+	client_transport::ws::{
+		CustomCertStore, HeaderMap, HeaderValue, Url, WsTransportClientBuilder,
+	},
+	core::client::{Client, ClientBuilder, async_client::PingConfig},
+};
+
+/// Builder for a [`JsonrpseeClient`] with custom transport configuration.
+///
+/// Every option that is not set keeps the default of `jsonrpsee`.
+#[derive(Debug, Clone)]
+pub struct JsonrpseeClientBuilder {
+	url: String,
+	request_timeout: Option<Duration>,
+	max_request_size: Option<u32>,
+	max_response_size: Option<u32>,
+	headers: HeaderMap,
+	certificate_store: Option<CustomCertStore>,
+	ping_interval: Option<Duration>,
+	max_concurrent_requests: Option<usize>,
+	max_buffer_capacity_per_subscription: usize,
+}
+
+impl JsonrpseeClientBuilder {
+	/// Create a new builder for a client connecting to the given url string.
+	/// Example url input: "ws://127.0.0.1:9944"
+	pub fn new(url: &str) -> Self {
+		Self {
+			url: url.into(),
+			request_timeout: None,
+			max_request_size: None,
+			max_response_size: None,
+			headers: HeaderMap::new(),
+			certificate_store: None,
+			ping_interval: None,
+			max_concurrent_requests: None,
+			max_buffer_capacity_per_subscription: 4096,
+		}
+	}
+
+	/// Set the time to wait for the response of a request.
+	pub fn request_timeout(mut self, timeout: Duration) -> Self {
+		self.request_timeout = Some(timeout);
+		self
+	}
+
+	/// Set the maximum size of a request in bytes.
+	pub fn max_request_size(mut self, size: u32) -> Self {
+		self.max_request_size = Some(size);
+		self
+	}
+
+	/// Set the maximum size of a response in bytes.
+	pub fn max_response_size(mut self, size: u32) -> Self {
+		self.max_response_size = Some(size);
+		self
+	}
+
+	/// Add a http header to the websocket handshake, e.g. an `authorization` header.
+	/// An existing header with the same name is replaced.
+	/// Fails if the `name` is not a valid header name.
+	pub fn header<Name>(mut self, name: Name, value: HeaderValue) -> Result<Self>
+	where
+		Name: TryInto<HeaderName>,
+		Name::Error: std::error::Error + Send + Sync + 'static,
+	{
+		let name = name.try_into().map_err(|e| Error::Client(Box::new(e)))?;
+		self.headers.insert(name, value);
+		Ok(self)
+	}
+
+	/// Use the given tls configuration, including its root certificates, instead of the
+	/// native certificate store of the system.
+	pub fn custom_cert_store(mut self, certificate_store: CustomCertStore) -> Self {
+		self.certificate_store = Some(certificate_store);
+		self
+	}
+
+	/// Send websocket pings in the given interval to keep the connection alive and detect
+	/// unresponsive nodes. Pings are disabled by default.
+	pub fn ping_interval(mut self, interval: Duration) -> Self {
+		self.ping_interval = Some(interval);
+		self
+	}
+
+	/// Set the maximum number of requests waiting for their response at the same time.
+	pub fn max_concurrent_requests(mut self, max: usize) -> Self {
+		self.max_concurrent_requests = Some(max);
+		self
+	}
+
+	/// Set the number of notifications buffered per subscription. If a subscription is not
+	/// polled often enough to keep up with the node, it is dropped once its buffer is full.
+	/// Default: 4096.
+	pub fn max_buffer_capacity_per_subscription(mut self, max: usize) -> Self {
+		self.max_buffer_capacity_per_subscription = max;
+		self
+	}
+
+	/// Connect to the node and create the client.
+	pub async fn build(self) -> Result<JsonrpseeClient> {
+		let client = self.connect().await?;
+		Ok(JsonrpseeClient::new_with_client(client))
+	}
+
+	/// Connect to the node and create a client that reconnects according to the `backoff`
+	/// policy, using the same configuration, if the connection is lost.
+	pub async fn build_reconnecting(
+		self,
+		backoff: BackoffPolicy,
+	) -> Result<ReconnectingJsonrpseeClient> {
+		ReconnectingJsonrpseeClient::new_with_builder(self, backoff).await
+	}
+
+	/// Establishes a new websocket connection according to the configuration.
+	pub(crate) async fn connect(&self) -> Result<Client> {
+		let url: Url = self.url.parse().map_err(|e| Error::Client(Box::new(e)))?;
+
+		let mut transport = WsTransportClientBuilder::default().set_headers(self.headers.clone());
+		if let Some(size) = self.max_request_size {
+			transport = transport.max_request_size(size);
+		}
+		if let Some(size) = self.max_response_size {
+			transport = transport.max_response_size(size);
+		}
+		if let Some(certificate_store) = self.certificate_store.clone() {
+			transport = transport.with_custom_cert_store(certificate_store);
+		}
+		let (tx, rx) = transport.build(url).await.map_err(|e| Error::Client(Box::new(e)))?;
+
+		let mut client = ClientBuilder::default()
+			.max_buffer_capacity_per_subscription(self.max_buffer_capacity_per_subscription);
+		if let Some(timeout) = self.request_timeout {
+			client = client.request_timeout(timeout);
+		}
+		if let Some(interval) = self.ping_interval {
+			client = client.enable_ws_ping(PingConfig::new().ping_interval(interval));
+		}
+		if let Some(max) = self.max_concurrent_requests {
+			client = client.max_concurrent_requests(max);
+		}
+		Ok(client.build_with_tokio(tx, rx))
+	}
+}
@@ -11,20 +11,26 @@
    limitations under the License.
 */
 
-use crate::rpc::{Error, Request, Result, RpcParams, Subscribe};
-use jsonrpsee::{
-	client_transport::ws::{Url, WsTransportClientBuilder},
-	core::{
-		client::{Client, ClientBuilder, ClientT, Error as JsonrpseeError, SubscriptionClientT},
-		traits::ToRpcParams,
-	},
+use crate::rpc::{BatchRequest, Error, Request, Result, RpcParams, Subscribe};
+use ac_primitives::MaybeSend;
+use core::fmt::Debug;
+use jsonrpsee::core::{
+	client::{Client, ClientT, Error as JsonrpseeError, SubscriptionClientT},
+	params::BatchRequestBuilder,
+	traits::ToRpcParams,
 };
 use serde::de::DeserializeOwned;
 use serde_json::value::RawValue;
 use std::sync::Arc;
 
+pub use builder::JsonrpseeClientBuilder;
+pub use http::HeaderName;
+pub use jsonrpsee::client_transport::ws::{CustomCertStore, HeaderValue};
+pub use reconnecting::{ReconnectingJsonrpseeClient, ReconnectingSubscription};
 pub use subscription::SubscriptionWrapper;
 
+mod builder;
+mod reconnecting;
 mod subscription;
 
 #[derive(Clone)]
@@ -41,15 +47,7 @@ impl JsonrpseeClient {
 	/// Create a new client with the given url string.
 	/// Example url input: "ws://127.0.0.1:9944"
 	pub async fn new(url: &str) -> Result<Self> {
-		let parsed_url: Url = url.parse().map_err(|e| Error::Client(Box::new(e)))?;
-		let (tx, rx) = WsTransportClientBuilder::default()
-			.build(parsed_url)
-			.await
-			.map_err(|e| Error::Client(Box::new(e)))?;
-		let client = ClientBuilder::default()
-			.max_buffer_capacity_per_subscription(4096)
-			.build_with_tokio(tx, rx);
-		Ok(Self { inner: Arc::new(client) })
+		JsonrpseeClientBuilder::new(url).build().await
 	}
 
 	/// Create a new client with the given address, port and max number of reconnection attempts.
@@ -92,24 +90,35 @@ impl JsonrpseeClient {
 	}
 }
 
-#[maybe_async::async_impl(?Send)]
+#[ac_async_macros::async_impl]
 impl Request for JsonrpseeClient {
-	async fn request<R: DeserializeOwned>(&self, method: &str, params: RpcParams) -> Result<R> {
-		self.inner
-			.request(method, RpcParamsWrapper(params))
-			.await
-			.map_err(|e| Error::Client(Box::new(e)))
+	async fn request<R: DeserializeOwned + MaybeSend>(
+		&self,
+		method: &str,
+		params: RpcParams,
+	) -> Result<R> {
+		self.inner.request(method, RpcParamsWrapper(params)).await.map_err(Error::from)
 	}
 }
 
-#[maybe_async::async_impl(?Send)]
+#[ac_async_macros::async_impl]
+impl BatchRequest for JsonrpseeClient {
+	async fn batch_request<R: DeserializeOwned + Debug + MaybeSend>(
+		&self,
+		requests: Vec<(&str, RpcParams)>,
+	) -> Result<Vec<Result<R>>> {
+		batch_request(&*self.inner, requests).await
+	}
+}
+
+#[ac_async_macros::async_impl]
 impl Subscribe for JsonrpseeClient {
 	type Subscription<Notification>
 		= SubscriptionWrapper<Notification>
 	where
-		Notification: DeserializeOwned;
+		Notification: DeserializeOwned + MaybeSend;
 
-	async fn subscribe<Notification: DeserializeOwned>(
+	async fn subscribe<Notification: DeserializeOwned + MaybeSend>(
 		&self,
 		sub: &str,
 		params: RpcParams,
@@ -119,11 +128,32 @@ impl Subscribe for JsonrpseeClient {
 			.subscribe(sub, RpcParamsWrapper(params), unsub)
 			.await
 			.map(|sub| sub.into())
-			.map_err(|e| Error::Client(Box::new(e)))
+			.map_err(Error::from)
+	}
+}
+
+/// Sends the `requests` as one batch with any jsonrpsee client.
+pub(crate) async fn batch_request<C: ClientT, R: DeserializeOwned + Debug>(
+	client: &C,
+	requests: Vec<(&str, RpcParams)>,
+) -> Result<Vec<Result<R>>> {
+	// Jsonrpsee rejects empty batches, so there is no need to bother the node.
+	if requests.is_empty() {
+		return Ok(Vec::new())
+	}
+	let mut batch = BatchRequestBuilder::new();
+	for (method, params) in requests {
+		batch.insert(method, RpcParamsWrapper(params))?;
 	}
+	let response = client.batch_request(batch).await?;
+	let results = response
+		.into_iter()
+		.map(|result| result.map_err(|e| Error::JsonRpc(e.into())))
+		.collect();
+	Ok(results)
 }
 
-struct RpcParamsWrapper(RpcParams);
+pub(crate) struct RpcParamsWrapper(pub(crate) RpcParams);
 
 impl ToRpcParams for RpcParamsWrapper {
 	fn to_rpc_params(self) -> core::result::Result<Option<Box<RawValue>>, serde_json::Error> {
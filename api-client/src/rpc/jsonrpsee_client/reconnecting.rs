@@ -0,0 +1,330 @@
+/*
+   Copyright 2019 Supercomputing Systems AG
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+	   http://www.apache.org/licenses/LICENSE-2.0
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Jsonrpsee client that re-establishes its websocket connection once it has been lost.
+
+use crate::rpc::{
+	BackoffPolicy, BatchRequest, Error, HandleSubscription, MaybeSend, Request, Result, RpcParams,
+	Subscribe,
+	jsonrpsee_client::{
+		JsonrpseeClientBuilder, RpcParamsWrapper, batch_request, subscription::subscription_id,
+	},
+};
+use core::{
+	fmt::Debug,
+	future::Future,
+	sync::atomic::{AtomicUsize, Ordering},
+};
+use jsonrpsee::core::client::{
+	Client, ClientT, Subscription, SubscriptionClientT, SubscriptionCloseReason,
+};
+use log::*;
+use serde::de::DeserializeOwned;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+/// Methods the node must not receive twice, because it might already have acted on them.
+const NON_IDEMPOTENT_METHODS: &[&str] = &[
+	"author_rotateKeys",
+	"author_submitAndWatchExtrinsic",
+	"author_submitExtrinsic",
+	"transaction_v1_broadcast",
+	"transactionWatch_v1_submitAndWatch",
+];
+
+/// Websocket client based on `jsonrpsee`, which reconnects to the node if the connection is lost.
+///
+/// A lost connection is re-established according to the [`BackoffPolicy`] as soon as a request
+/// or subscription notices it. Requests that failed due to the lost connection are sent once
+/// more after reconnecting. Active subscriptions are renewed with their original parameters and
+/// return a single [`Error::SubscriptionRenewed`] before continuing with the notifications of the
+/// new connection. This signals that notifications might have been missed, so the caller can
+/// backfill e.g. the blocks finalized in the meantime.
+///
+/// Methods that submit extrinsics or rotate keys are never sent twice, because the node might
+/// already have acted on them. If their connection is lost, they fail with
+/// [`Error::ConnectionClosed`] and their subscriptions end with it, instead of being renewed.
+///
+/// Clones of the client share the same connection.
+#[derive(Debug, Clone)]
+pub struct ReconnectingJsonrpseeClient {
+	connection: Arc<Connection>,
+}
+
+#[derive(Debug)]
+struct Connection {
+	builder: JsonrpseeClientBuilder,
+	backoff: BackoffPolicy,
+	client: Mutex<Arc<Client>>,
+	reconnections: AtomicUsize,
+}
+
+impl ReconnectingJsonrpseeClient {
+	/// Create a new client to a local Substrate node with default port.
+	pub async fn with_default_url() -> Result<Self> {
+		Self::new("ws://127.0.0.1:9944").await
+	}
+
+	/// Create a new client with the given url string and the default [`BackoffPolicy`].
+	/// Example url input: "ws://127.0.0.1:9944"
+	pub async fn new(url: &str) -> Result<Self> {
+		Self::new_with_backoff(url, BackoffPolicy::default()).await
+	}
+
+	/// Create a new client with the given url string and the policy that defines
+	/// how to reconnect in case the connection is lost.
+	/// Example url input: "ws://127.0.0.1:9944"
+	pub async fn new_with_backoff(url: &str, backoff: BackoffPolicy) -> Result<Self> {
+		Self::new_with_builder(JsonrpseeClientBuilder::new(url), backoff).await
+	}
+
+	/// Create a new client with the transport configuration of the `builder`, which is
+	/// used for every reconnection as well.
+	pub async fn new_with_builder(
+		builder: JsonrpseeClientBuilder,
+		backoff: BackoffPolicy,
+	) -> Result<Self> {
+		let client = connect_with_backoff(&builder, &backoff).await?;
+		let connection = Connection {
+			builder,
+			backoff,
+			client: Mutex::new(Arc::new(client)),
+			reconnections: AtomicUsize::new(0),
+		};
+		Ok(Self { connection: Arc::new(connection) })
+	}
+
+	/// Checks if the client is currently connected to the target.
+	pub async fn is_connected(&self) -> bool {
+		self.connection.client.lock().await.is_connected()
+	}
+
+	/// Number of times the connection has been re-established since the client was created.
+	pub fn reconnections(&self) -> usize {
+		self.connection.reconnections.load(Ordering::Relaxed)
+	}
+}
+
+impl Connection {
+	/// Returns the current client. Reconnects first, if its connection has been closed.
+	async fn client(&self) -> Result<Arc<Client>> {
+		let client = self.client.lock().await.clone();
+		if client.is_connected() { Ok(client) } else { self.reconnect(&client).await }
+	}
+
+	/// Replaces the `closed` client with a newly connected one. If another caller has
+	/// already done so in the meantime, the current client is returned instead.
+	async fn reconnect(&self, closed: &Arc<Client>) -> Result<Arc<Client>> {
+		let mut client = self.client.lock().await;
+		if !Arc::ptr_eq(&client, closed) {
+			return Ok(client.clone())
+		}
+		warn!("Connection lost, reconnecting");
+		*client = Arc::new(connect_with_backoff(&self.builder, &self.backoff).await?);
+		self.reconnections.fetch_add(1, Ordering::Relaxed);
+		Ok(client.clone())
+	}
+
+	/// Runs `call` with the current client. If it fails due to a closed connection, it is run
+	/// once more after reconnecting, if it may be `resent`. Otherwise it fails with
+	/// [`Error::ConnectionClosed`]. Returns the client that has been used along with the result.
+	async fn call<T, F, Fut>(&self, resend: bool, call: F) -> Result<(Arc<Client>, T)>
+	where
+		F: Fn(Arc<Client>) -> Fut,
+		Fut: Future<Output = Result<T>>,
+	{
+		let client = self.client().await?;
+		// The result is not kept across the reconnection, so the future stays `Send` with the
+		// `send-futures` feature, independent of the result type.
+		match call(client.clone()).await {
+			Err(e) if !client.is_connected() =>
+				debug!("Call failed due to a closed connection: {e:?}"),
+			result => return result.map(|result| (client, result)),
+		}
+		if !resend {
+			// The connection is re-established upon the next call.
+			return Err(Error::ConnectionClosed)
+		}
+		let client = self.reconnect(&client).await?;
+		call(client.clone()).await.map(|result| (client, result))
+	}
+
+	async fn subscribe<Notification: DeserializeOwned + MaybeSend>(
+		&self,
+		sub: &str,
+		params: &RpcParams,
+		unsub: &str,
+	) -> Result<(Arc<Client>, Subscription<Notification>)> {
+		self.call(is_idempotent(sub), |client| {
+			let params = params.clone();
+			async move {
+				client
+					.subscribe(sub, RpcParamsWrapper(params), unsub)
+					.await
+					.map_err(Error::from)
+			}
+		})
+		.await
+	}
+}
+
+#[ac_async_macros::async_impl]
+impl Request for ReconnectingJsonrpseeClient {
+	async fn request<R: DeserializeOwned + MaybeSend>(
+		&self,
+		method: &str,
+		params: RpcParams,
+	) -> Result<R> {
+		let (_, result) = self
+			.connection
+			.call(is_idempotent(method), |client| {
+				let params = params.clone();
+				async move {
+					client.request(method, RpcParamsWrapper(params)).await.map_err(Error::from)
+				}
+			})
+			.await?;
+		Ok(result)
+	}
+}
+
+#[ac_async_macros::async_impl]
+impl BatchRequest for ReconnectingJsonrpseeClient {
+	async fn batch_request<R: DeserializeOwned + Debug + MaybeSend>(
+		&self,
+		requests: Vec<(&str, RpcParams)>,
+	) -> Result<Vec<Result<R>>> {
+		let resend = requests.iter().all(|(method, _)| is_idempotent(method));
+		let (_, results) = self
+			.connection
+			.call(resend, |client| {
+				let requests = requests.clone();
+				async move { batch_request(&*client, requests).await }
+			})
+			.await?;
+		Ok(results)
+	}
+}
+
+#[ac_async_macros::async_impl]
+impl Subscribe for ReconnectingJsonrpseeClient {
+	type Subscription<Notification>
+		= ReconnectingSubscription<Notification>
+	where
+		Notification: DeserializeOwned + MaybeSend;
+
+	async fn subscribe<Notification: DeserializeOwned + MaybeSend>(
+		&self,
+		sub: &str,
+		params: RpcParams,
+		unsub: &str,
+	) -> Result<Self::Subscription<Notification>> {
+		let (client, inner) = self.connection.subscribe(sub, &params, unsub).await?;
+		Ok(ReconnectingSubscription {
+			connection: self.connection.clone(),
+			sub: sub.into(),
+			params,
+			unsub: unsub.into(),
+			client,
+			inner: Some(inner),
+		})
+	}
+}
+
+/// Subscription of the [`ReconnectingJsonrpseeClient`], which is renewed if its connection
+/// is lost or if it could not keep up with the node. Subscriptions of methods that must not
+/// be sent twice end with [`Error::ConnectionClosed`] instead.
+#[derive(Debug)]
+pub struct ReconnectingSubscription<Notification> {
+	connection: Arc<Connection>,
+	sub: String,
+	params: RpcParams,
+	unsub: String,
+	// Client the current subscription has been established with.
+	client: Arc<Client>,
+	// `None` once the subscription has ended.
+	inner: Option<Subscription<Notification>>,
+}
+
+#[ac_async_macros::async_impl]
+impl<Notification: DeserializeOwned + MaybeSend> HandleSubscription<Notification>
+	for ReconnectingSubscription<Notification>
+{
+	async fn next(&mut self) -> Option<Result<Notification>> {
+		let inner = self.inner.as_mut()?;
+		if let Some(result) = inner.next().await {
+			return Some(result.map_err(|e| Error::Client(Box::new(e))))
+		}
+
+		let lagged = matches!(inner.close_reason(), Some(SubscriptionCloseReason::Lagged));
+		if !lagged && self.client.is_connected() {
+			// The subscription has regularly been ended by the node.
+			self.inner = None;
+			return None
+		}
+
+		if !is_idempotent(&self.sub) {
+			warn!("Subscription {} closed unexpectedly", self.sub);
+			self.inner = None;
+			return Some(Err(Error::ConnectionClosed))
+		}
+
+		warn!("Subscription {} closed unexpectedly, renewing it", self.sub);
+		match self.connection.subscribe(&self.sub, &self.params, &self.unsub).await {
+			Ok((client, inner)) => {
+				self.client = client;
+				self.inner = Some(inner);
+				Some(Err(Error::SubscriptionRenewed))
+			},
+			Err(e) => {
+				self.inner = None;
+				Some(Err(e))
+			},
+		}
+	}
+
+	async fn unsubscribe(self) -> Result<()> {
+		match self.inner {
+			Some(inner) => inner.unsubscribe().await.map_err(Error::from),
+			None => Ok(()),
+		}
+	}
+
+	/// Returns the id of the current subscription, which changes whenever it is renewed.
+	fn subscription_id(&self) -> Option<String> {
+		self.inner.as_ref().and_then(subscription_id)
+	}
+}
+
+/// Returns false if the node must not receive the `method` twice.
+fn is_idempotent(method: &str) -> bool {
+	!NON_IDEMPOTENT_METHODS.contains(&method)
+}
+
+/// Connects to the node. Retries according to the `backoff` policy in case of failure.
+async fn connect_with_backoff(
+	builder: &JsonrpseeClientBuilder,
+	backoff: &BackoffPolicy,
+) -> Result<Client> {
+	let mut failed_attempts: u8 = 0;
+	loop {
+		match builder.connect().await {
+			Ok(client) => return Ok(client),
+			Err(e) => warn!("Connection attempt failed due to {e:?}"),
+		}
+		failed_attempts = failed_attempts.saturating_add(1);
+		let delay = backoff.delay(failed_attempts).ok_or(Error::MaxConnectionAttemptsExceeded)?;
+		trace!("Trying to reconnect in {delay:?}. Failed attempts: {failed_attempts}");
+		tokio::time::sleep(delay).await;
+	}
+}
@@ -12,7 +12,11 @@
 */
 
 use crate::rpc::{Error, HandleSubscription, Result};
-use jsonrpsee::core::client::Subscription;
+use ac_primitives::MaybeSend;
+use jsonrpsee::{
+	core::client::{Subscription, SubscriptionKind},
+	types::SubscriptionId,
+};
 use serde::de::DeserializeOwned;
 
 #[derive(Debug)]
@@ -20,8 +24,8 @@ pub struct SubscriptionWrapper<Notification> {
 	inner: Subscription<Notification>,
 }
 
-#[maybe_async::async_impl(?Send)]
-impl<Notification: DeserializeOwned> HandleSubscription<Notification>
+#[ac_async_macros::async_impl]
+impl<Notification: DeserializeOwned + MaybeSend> HandleSubscription<Notification>
 	for SubscriptionWrapper<Notification>
 {
 	async fn next(&mut self) -> Option<Result<Notification>> {
@@ -32,7 +36,11 @@ impl<Notification: DeserializeOwned> HandleSubscription<Notification>
 	}
 
 	async fn unsubscribe(self) -> Result<()> {
-		self.inner.unsubscribe().await.map_err(|e| Error::Client(Box::new(e)))
+		self.inner.unsubscribe().await.map_err(Error::from)
+	}
+
+	fn subscription_id(&self) -> Option<String> {
+		subscription_id(&self.inner)
 	}
 }
 
@@ -41,3 +49,14 @@ impl<Notification> From<Subscription<Notification>> for SubscriptionWrapper<Noti
 		Self { inner }
 	}
 }
+
+/// Returns the id the node has assigned to the `subscription`.
+pub(crate) fn subscription_id<Notification>(
+	subscription: &Subscription<Notification>,
+) -> Option<String> {
+	match subscription.kind() {
+		SubscriptionKind::Subscription(SubscriptionId::Num(id)) => Some(id.to_string()),
+		SubscriptionKind::Subscription(SubscriptionId::Str(id)) => Some(id.to_string()),
+		SubscriptionKind::Method(_) => None,
+	}
+}
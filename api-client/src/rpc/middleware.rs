@@ -0,0 +1,485 @@
+/*
+   Copyright 2019 Supercomputing Systems AG
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+	   http://www.apache.org/licenses/LICENSE-2.0
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Rpc client wrapper adding timeouts, retries and rate limiting to any rpc client.
+
+use crate::rpc::{BackoffPolicy, BatchRequest, Error, Request, Result, Subscribe};
+use ac_primitives::{MaybeSend, RpcParams};
+use core::{fmt::Debug, time::Duration};
+use log::*;
+use serde::de::DeserializeOwned;
+use std::{
+	sync::{Mutex, MutexGuard},
+	time::Instant,
+};
+
+#[cfg(feature = "sync-api")]
+use std::sync::Condvar;
+#[cfg(not(feature = "sync-api"))]
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+/// Methods starting with one of these prefixes only read from the node, so they are retried
+/// by default.
+pub const IDEMPOTENT_METHOD_PREFIXES: [&str; 2] = ["state_", "chain_"];
+
+/// Rpc client wrapper, which applies the configured limits to every request of the inner client.
+///
+/// - Requests are delayed to not exceed the rate limit, see [`MiddlewareClient::with_rate_limit`].
+/// - The number of requests waiting for their response at the same time can be capped, see
+///   [`MiddlewareClient::with_max_in_flight`].
+/// - Idempotent requests failing due to a connection error (see [`Error::is_connection_error`])
+///   are retried according to the [`BackoffPolicy`], see [`MiddlewareClient::with_retries`].
+/// - In async mode, requests not answered in time fail with [`Error::RequestTimeout`].
+///
+/// The limits apply to requests and to the establishment of subscriptions, but not to
+/// subscription notifications. By default, no limit is applied. The wrapper can be combined
+/// with any other client wrapper, e.g. the [`crate::rpc::FailoverClient`].
+#[derive(Debug)]
+pub struct MiddlewareClient<Client> {
+	inner: Client,
+	#[cfg(not(feature = "sync-api"))]
+	timeout: Option<Duration>,
+	retries: Option<BackoffPolicy>,
+	idempotent_method_prefixes: Vec<String>,
+	rate_limit: Option<TokenBucket>,
+	in_flight_limit: Option<InFlightLimit>,
+}
+
+impl<Client> MiddlewareClient<Client> {
+	/// Wrap the `inner` client. No limit is applied until configured.
+	pub fn new(inner: Client) -> Self {
+		Self {
+			inner,
+			#[cfg(not(feature = "sync-api"))]
+			timeout: None,
+			retries: None,
+			idempotent_method_prefixes: IDEMPOTENT_METHOD_PREFIXES
+				.iter()
+				.map(|prefix| prefix.to_string())
+				.collect(),
+			rate_limit: None,
+			in_flight_limit: None,
+		}
+	}
+
+	/// Fail requests with [`Error::RequestTimeout`] if they are not answered within `timeout`.
+	/// Timed out requests are retried like requests that failed due to a connection error.
+	///
+	/// Only available in async mode, because a blocking call can not be interrupted. In sync
+	/// mode, use the timeouts of the inner client instead, if it offers any.
+	#[cfg(not(feature = "sync-api"))]
+	pub fn with_timeout(mut self, timeout: Duration) -> Self {
+		self.timeout = Some(timeout);
+		self
+	}
+
+	/// Retry idempotent requests failing due to a connection error according to the `backoff`
+	/// policy, e.g. [`BackoffPolicy::exponential`].
+	pub fn with_retries(mut self, backoff: BackoffPolicy) -> Self {
+		self.retries = Some(backoff);
+		self
+	}
+
+	/// Set the prefixes of the methods that may be retried. Default: [`IDEMPOTENT_METHOD_PREFIXES`].
+	pub fn with_idempotent_method_prefixes(mut self, prefixes: &[&str]) -> Self {
+		self.idempotent_method_prefixes =
+			prefixes.iter().map(|prefix| prefix.to_string()).collect();
+		self
+	}
+
+	/// Send no more than `requests_per_second` requests on average, allowing bursts of up to
+	/// `burst` requests at once. Each request of a batch counts as a single request.
+	pub fn with_rate_limit(mut self, requests_per_second: u32, burst: u32) -> Self {
+		self.rate_limit = Some(TokenBucket::new(requests_per_second, burst));
+		self
+	}
+
+	/// Limit the number of requests waiting for their response at the same time to `max`.
+	/// Additional requests wait until a previous one has been answered.
+	pub fn with_max_in_flight(mut self, max: usize) -> Self {
+		self.in_flight_limit = Some(InFlightLimit::new(max));
+		self
+	}
+
+	/// The wrapped client.
+	pub fn inner(&self) -> &Client {
+		&self.inner
+	}
+
+	/// Returns true if `method` is safe to be sent more than once.
+	pub fn is_idempotent(&self, method: &str) -> bool {
+		self.idempotent_method_prefixes
+			.iter()
+			.any(|prefix| method.starts_with(prefix.as_str()))
+	}
+
+	/// Returns the time to wait before retrying, or `None` if the call must not be retried.
+	fn retry_delay(
+		&self,
+		idempotent: bool,
+		error: &Error,
+		failed_attempts: u8,
+	) -> Option<Duration> {
+		if !idempotent || !error.is_connection_error() {
+			return None
+		}
+		let delay = self.retries?.delay(failed_attempts)?;
+		warn!("Rpc call failed due to {error:?}, retrying in {delay:?}");
+		Some(delay)
+	}
+}
+
+#[ac_async_macros::maybe_async]
+impl<Client> MiddlewareClient<Client> {
+	/// Waits until the rate limit allows to send `requests` more requests.
+	async fn wait_for_rate_limit(&self, requests: usize) {
+		let Some(rate_limit) = &self.rate_limit else { return };
+		for _ in 0..requests {
+			while let Some(wait) = rate_limit.try_take() {
+				sleep(wait).await;
+			}
+		}
+	}
+}
+
+#[ac_async_macros::async_impl]
+impl<Client> MiddlewareClient<Client> {
+	async fn acquire_in_flight_permit(&self) -> Option<SemaphorePermit<'_>> {
+		match &self.in_flight_limit {
+			Some(limit) => Some(limit.acquire().await),
+			None => None,
+		}
+	}
+
+	async fn timed<T>(&self, call: impl core::future::Future<Output = Result<T>>) -> Result<T> {
+		match self.timeout {
+			Some(timeout) =>
+				tokio::time::timeout(timeout, call).await.map_err(|_| Error::RequestTimeout)?,
+			None => call.await,
+		}
+	}
+}
+
+#[maybe_async::sync_impl]
+impl<Client> MiddlewareClient<Client> {
+	fn acquire_in_flight_permit(&self) -> Option<InFlightPermit<'_>> {
+		self.in_flight_limit.as_ref().map(|limit| limit.acquire())
+	}
+
+	// A blocking call has already returned at this point, so there is nothing to time out.
+	fn timed<T>(&self, result: Result<T>) -> Result<T> {
+		result
+	}
+}
+
+#[ac_async_macros::maybe_async]
+impl<Client: Request> Request for MiddlewareClient<Client> {
+	async fn request<R: DeserializeOwned + MaybeSend>(
+		&self,
+		method: &str,
+		params: RpcParams,
+	) -> Result<R> {
+		let idempotent = self.is_idempotent(method);
+		let mut failed_attempts: u8 = 0;
+		loop {
+			self.wait_for_rate_limit(1).await;
+			let result = {
+				let _permit = self.acquire_in_flight_permit().await;
+				self.timed(self.inner.request(method, params.clone())).await
+			};
+			match result {
+				Err(e) => {
+					failed_attempts = failed_attempts.saturating_add(1);
+					match self.retry_delay(idempotent, &e, failed_attempts) {
+						Some(delay) => sleep(delay).await,
+						None => return Err(e),
+					}
+				},
+				result => return result,
+			}
+		}
+	}
+}
+
+#[ac_async_macros::maybe_async]
+impl<Client: BatchRequest> BatchRequest for MiddlewareClient<Client> {
+	async fn batch_request<R: DeserializeOwned + Debug + MaybeSend>(
+		&self,
+		requests: Vec<(&str, RpcParams)>,
+	) -> Result<Vec<Result<R>>> {
+		let idempotent = requests.iter().all(|(method, _)| self.is_idempotent(method));
+		let mut failed_attempts: u8 = 0;
+		loop {
+			self.wait_for_rate_limit(requests.len()).await;
+			let result = {
+				let _permit = self.acquire_in_flight_permit().await;
+				self.timed(self.inner.batch_request(requests.clone())).await
+			};
+			match result {
+				Err(e) => {
+					failed_attempts = failed_attempts.saturating_add(1);
+					match self.retry_delay(idempotent, &e, failed_attempts) {
+						Some(delay) => sleep(delay).await,
+						None => return Err(e),
+					}
+				},
+				result => return result,
+			}
+		}
+	}
+}
+
+#[ac_async_macros::maybe_async]
+impl<Client: Subscribe> Subscribe for MiddlewareClient<Client> {
+	type Subscription<Notification>
+		= Client::Subscription<Notification>
+	where
+		Notification: DeserializeOwned + MaybeSend;
+
+	async fn subscribe<Notification: DeserializeOwned + MaybeSend>(
+		&self,
+		sub: &str,
+		params: RpcParams,
+		unsub: &str,
+	) -> Result<Self::Subscription<Notification>> {
+		let idempotent = self.is_idempotent(sub);
+		let mut failed_attempts: u8 = 0;
+		loop {
+			self.wait_for_rate_limit(1).await;
+			let result = {
+				let _permit = self.acquire_in_flight_permit().await;
+				self.timed(self.inner.subscribe(sub, params.clone(), unsub)).await
+			};
+			match result {
+				Err(e) => {
+					failed_attempts = failed_attempts.saturating_add(1);
+					match self.retry_delay(idempotent, &e, failed_attempts) {
+						Some(delay) => sleep(delay).await,
+						None => return Err(e),
+					}
+				},
+				result => return result,
+			}
+		}
+	}
+}
+
+/// Token bucket, refilled with `rate` tokens per second up to its `capacity`.
+#[derive(Debug)]
+struct TokenBucket {
+	rate: f64,
+	capacity: f64,
+	state: Mutex<TokenBucketState>,
+}
+
+#[derive(Debug)]
+struct TokenBucketState {
+	tokens: f64,
+	last_refill: Instant,
+}
+
+impl TokenBucket {
+	fn new(rate: u32, capacity: u32) -> Self {
+		// A bucket that can not hold a single token would block forever.
+		let capacity = f64::from(capacity.max(1));
+		let state = TokenBucketState { tokens: capacity, last_refill: Instant::now() };
+		Self { rate: f64::from(rate.max(1)), capacity, state: Mutex::new(state) }
+	}
+
+	/// Takes a token if one is available. Otherwise returns the time until the next token
+	/// will be available.
+	fn try_take(&self) -> Option<Duration> {
+		let mut state = lock(&self.state);
+		let now = Instant::now();
+		let refill = now.duration_since(state.last_refill).as_secs_f64() * self.rate;
+		state.tokens = (state.tokens + refill).min(self.capacity);
+		state.last_refill = now;
+		if state.tokens >= 1.0 {
+			state.tokens -= 1.0;
+			None
+		} else {
+			Some(Duration::from_secs_f64((1.0 - state.tokens) / self.rate))
+		}
+	}
+}
+
+#[cfg(not(feature = "sync-api"))]
+#[derive(Debug)]
+struct InFlightLimit {
+	semaphore: Semaphore,
+}
+
+#[cfg(not(feature = "sync-api"))]
+impl InFlightLimit {
+	fn new(max: usize) -> Self {
+		Self { semaphore: Semaphore::new(max.max(1)) }
+	}
+
+	async fn acquire(&self) -> SemaphorePermit<'_> {
+		// The semaphore is never closed.
+		self.semaphore
+			.acquire()
+			.await
+			.expect("In-flight semaphore is never closed; qed")
+	}
+}
+
+#[cfg(feature = "sync-api")]
+#[derive(Debug)]
+struct InFlightLimit {
+	max: usize,
+	in_flight: Mutex<usize>,
+	released: Condvar,
+}
+
+#[cfg(feature = "sync-api")]
+impl InFlightLimit {
+	fn new(max: usize) -> Self {
+		Self { max: max.max(1), in_flight: Mutex::new(0), released: Condvar::new() }
+	}
+
+	fn acquire(&self) -> InFlightPermit<'_> {
+		let mut in_flight = lock(&self.in_flight);
+		while *in_flight >= self.max {
+			in_flight = self.released.wait(in_flight).unwrap_or_else(|e| e.into_inner());
+		}
+		*in_flight += 1;
+		InFlightPermit { limit: self }
+	}
+}
+
+/// Permit to send a request, which is returned once dropped.
+#[cfg(feature = "sync-api")]
+struct InFlightPermit<'a> {
+	limit: &'a InFlightLimit,
+}
+
+#[cfg(feature = "sync-api")]
+impl Drop for InFlightPermit<'_> {
+	fn drop(&mut self) {
+		*lock(&self.limit.in_flight) -= 1;
+		self.limit.released.notify_one();
+	}
+}
+
+#[maybe_async::sync_impl]
+fn sleep(duration: Duration) {
+	std::thread::sleep(duration)
+}
+
+#[maybe_async::async_impl]
+async fn sleep(duration: Duration) {
+	tokio::time::sleep(duration).await
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+	// The guarded counters stay valid even if a panic happened while they were locked.
+	mutex.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use core::sync::atomic::{AtomicUsize, Ordering};
+
+	/// Client failing with a connection error until `failures` requests have been sent.
+	struct TestClient {
+		failures: usize,
+		requests: AtomicUsize,
+	}
+
+	impl TestClient {
+		fn new(failures: usize) -> Self {
+			Self { failures, requests: AtomicUsize::new(0) }
+		}
+	}
+
+	impl Request for TestClient {
+		fn request<R: DeserializeOwned + MaybeSend>(
+			&self,
+			method: &str,
+			_params: RpcParams,
+		) -> Result<R> {
+			if self.requests.fetch_add(1, Ordering::Relaxed) < self.failures {
+				return Err(Error::ConnectionClosed)
+			}
+			Ok(serde_json::from_value(method.into())?)
+		}
+	}
+
+	fn retrying_client(failures: usize, max_attempts: u8) -> MiddlewareClient<TestClient> {
+		MiddlewareClient::new(TestClient::new(failures))
+			.with_retries(BackoffPolicy::constant(Duration::ZERO, max_attempts))
+	}
+
+	#[test]
+	fn idempotent_request_is_retried() {
+		let client = retrying_client(2, 3);
+
+		let result: String = client.request("state_getStorage", RpcParams::new()).unwrap();
+
+		assert_eq!(result, "state_getStorage");
+		assert_eq!(client.inner().requests.load(Ordering::Relaxed), 3);
+	}
+
+	#[test]
+	fn retries_stop_after_max_attempts() {
+		let client = retrying_client(5, 2);
+
+		let result = client.request::<String>("chain_getHeader", RpcParams::new());
+
+		assert!(matches!(result, Err(Error::ConnectionClosed)));
+		assert_eq!(client.inner().requests.load(Ordering::Relaxed), 3);
+	}
+
+	#[test]
+	fn non_idempotent_request_is_not_retried() {
+		let client = retrying_client(1, 3);
+
+		let result = client.request::<String>("author_submitExtrinsic", RpcParams::new());
+
+		assert!(matches!(result, Err(Error::ConnectionClosed)));
+		assert_eq!(client.inner().requests.load(Ordering::Relaxed), 1);
+	}
+
+	#[test]
+	fn custom_idempotent_methods_are_retried() {
+		let client = retrying_client(1, 3).with_idempotent_method_prefixes(&["system_"]);
+
+		assert!(client.request::<String>("chain_getHeader", RpcParams::new()).is_err());
+		assert_eq!(
+			client.request::<String>("system_name", RpcParams::new()).unwrap(),
+			"system_name"
+		);
+	}
+
+	#[test]
+	fn token_bucket_allows_burst_and_then_waits() {
+		let bucket = TokenBucket::new(1, 2);
+
+		assert!(bucket.try_take().is_none());
+		assert!(bucket.try_take().is_none());
+		let wait = bucket.try_take().unwrap();
+		assert!(wait > Duration::ZERO && wait <= Duration::from_secs(1));
+	}
+
+	#[test]
+	fn in_flight_permit_is_released_on_drop() {
+		let limit = InFlightLimit::new(1);
+
+		let permit = limit.acquire();
+		assert_eq!(*lock(&limit.in_flight), 1);
+		drop(permit);
+		assert_eq!(*lock(&limit.in_flight), 0);
+	}
+}
@@ -11,36 +11,353 @@
    limitations under the License.
 */
 
-use crate::rpc::{Request, Result};
-use ac_primitives::RpcParams;
-use serde::de::DeserializeOwned;
-use std::{collections::HashMap, sync::RwLock};
+//! Rpc client mock to test code built on top of the `Api` without a running node.
+
+use crate::rpc::{
+	BatchRequest, Error, HandleSubscription, JsonRpcError, Request, Result, Subscribe,
+};
+use ac_primitives::{MaybeSend, RpcParams};
+use core::{fmt::Debug, marker::PhantomData};
+use serde::{Serialize, de::DeserializeOwned};
+use serde_json::Value;
+use std::{
+	collections::{HashMap, VecDeque},
+	sync::{
+		RwLock,
+		atomic::{AtomicU64, Ordering},
+	},
+};
 
 type MethodKey = String;
 type SerializedValue = String;
 
-#[derive(Debug)]
+/// Json-rpc error code of a method that is not available.
+pub const METHOD_NOT_FOUND_CODE: i32 = -32601;
+/// Json-rpc error code of a method that has been called with unexpected params.
+pub const INVALID_PARAMS_CODE: i32 = -32602;
+
+/// Rpc client returning predefined responses.
+///
+/// Responses can either be defined for a method regardless of the params, or for a specific set
+/// of params. The latter takes precedence. Requests that have no matching response return a
+/// json-rpc error, just like a node would.
+/// Subscriptions are assigned the ids "0", "1", .. in the order they have been created.
+#[derive(Debug, Default)]
 pub struct RpcClientMock {
-	pub state: RwLock<HashMap<MethodKey, SerializedValue>>,
+	responses: RwLock<HashMap<MethodKey, Vec<MockResponse>>>,
+	next_subscription_id: AtomicU64,
+}
+
+#[derive(Debug, Clone)]
+struct MockResponse {
+	// `None` matches any params.
+	params: Option<Value>,
+	result: MockResult,
+}
+
+#[derive(Debug, Clone)]
+enum MockResult {
+	Response(SerializedValue),
+	Error(JsonRpcError),
+	Subscription(Vec<Value>),
 }
 
 impl RpcClientMock {
+	/// Create a new mock returning the serialized values of `state` for the
+	/// respective methods, regardless of the params.
 	pub fn new(state: HashMap<MethodKey, SerializedValue>) -> Self {
-		Self { state: RwLock::new(state) }
+		let mock = Self::default();
+		for (method, value) in state {
+			mock.update_entry(method, value);
+		}
+		mock
 	}
 
+	/// Set the serialized `value` returned for `key` method, regardless of the params.
 	pub fn update_entry(&self, key: MethodKey, value: SerializedValue) {
-		let mut lock = self.state.write().unwrap();
-		lock.insert(key, value);
+		self.insert(key, None, MockResult::Response(value));
+	}
+
+	/// Return `value` for all requests of `method`, regardless of the params.
+	pub fn with_response<V: Serialize>(self, method: &str, value: V) -> Self {
+		self.insert(method.into(), None, MockResult::Response(serialize(value)));
+		self
+	}
+
+	/// Return `value` for requests of `method` with exactly the given `params`.
+	pub fn with_response_for<V: Serialize>(
+		self,
+		method: &str,
+		params: RpcParams,
+		value: V,
+	) -> Self {
+		self.insert(
+			method.into(),
+			Some(params_to_value(params)),
+			MockResult::Response(serialize(value)),
+		);
+		self
+	}
+
+	/// Return a json-rpc error for all requests and subscriptions of `method`.
+	pub fn with_error(self, method: &str, code: i32, message: &str) -> Self {
+		self.with_json_rpc_error(method, JsonRpcError::new(code, message, None))
+	}
+
+	/// Return the given json-rpc `error`, including its data, for all requests and subscriptions
+	/// of `method`.
+	pub fn with_json_rpc_error(self, method: &str, error: JsonRpcError) -> Self {
+		self.insert(method.into(), None, MockResult::Error(error));
+		self
+	}
+
+	/// Let all subscriptions to `method` emit the given `notifications`. The subscription ends
+	/// once all of them have been returned.
+	pub fn with_subscription<N: Serialize>(self, method: &str, notifications: Vec<N>) -> Self {
+		let notifications = notifications.into_iter().map(to_value).collect();
+		self.insert(method.into(), None, MockResult::Subscription(notifications));
+		self
+	}
+
+	/// Let subscriptions to `method` with exactly the given `params` emit the given
+	/// `notifications`.
+	pub fn with_subscription_for<N: Serialize>(
+		self,
+		method: &str,
+		params: RpcParams,
+		notifications: Vec<N>,
+	) -> Self {
+		let notifications = notifications.into_iter().map(to_value).collect();
+		self.insert(
+			method.into(),
+			Some(params_to_value(params)),
+			MockResult::Subscription(notifications),
+		);
+		self
+	}
+
+	fn insert(&self, method: MethodKey, params: Option<Value>, result: MockResult) {
+		let mut responses = self.responses.write().unwrap_or_else(|e| e.into_inner());
+		let method_responses = responses.entry(method).or_default();
+		method_responses.retain(|response| response.params != params);
+		method_responses.push(MockResponse { params, result });
+	}
+
+	/// Returns the result defined for the exact `params`, or else the one for any params.
+	fn find(&self, method: &str, params: RpcParams) -> Result<MockResult> {
+		let params = params.to_json_value()?;
+		let responses = self.responses.read().unwrap_or_else(|e| e.into_inner());
+		let Some(method_responses) = responses.get(method) else {
+			return Err(json_rpc_error(
+				METHOD_NOT_FOUND_CODE,
+				&format!("Method not found: {method}"),
+			))
+		};
+		method_responses
+			.iter()
+			.find(|response| response.params.as_ref() == Some(&params))
+			.or_else(|| method_responses.iter().find(|response| response.params.is_none()))
+			.map(|response| response.result.clone())
+			.ok_or_else(|| {
+				json_rpc_error(
+					INVALID_PARAMS_CODE,
+					&format!("No response for {method} with {params}"),
+				)
+			})
 	}
 }
 
-#[maybe_async::maybe_async(?Send)]
+#[ac_async_macros::maybe_async]
 impl Request for RpcClientMock {
-	async fn request<R: DeserializeOwned>(&self, method: &str, _params: RpcParams) -> Result<R> {
-		let lock = self.state.read().unwrap();
-		let response = lock.get(method).unwrap();
-		let deserialized_value: R = serde_json::from_str(response).unwrap();
-		Ok(deserialized_value)
+	async fn request<R: DeserializeOwned + MaybeSend>(
+		&self,
+		method: &str,
+		params: RpcParams,
+	) -> Result<R> {
+		match self.find(method, params)? {
+			MockResult::Response(value) => Ok(serde_json::from_str(&value)?),
+			MockResult::Error(error) => Err(Error::JsonRpc(error)),
+			MockResult::Subscription(_) =>
+				Err(json_rpc_error(METHOD_NOT_FOUND_CODE, &format!("{method} is a subscription"))),
+		}
+	}
+}
+
+#[ac_async_macros::maybe_async]
+impl BatchRequest for RpcClientMock {
+	async fn batch_request<R: DeserializeOwned + Debug + MaybeSend>(
+		&self,
+		requests: Vec<(&str, RpcParams)>,
+	) -> Result<Vec<Result<R>>> {
+		let mut results = Vec::with_capacity(requests.len());
+		for (method, params) in requests {
+			results.push(self.request(method, params).await);
+		}
+		Ok(results)
+	}
+}
+
+#[ac_async_macros::maybe_async]
+impl Subscribe for RpcClientMock {
+	type Subscription<Notification>
+		= SubscriptionMock<Notification>
+	where
+		Notification: DeserializeOwned + MaybeSend;
+
+	async fn subscribe<Notification: DeserializeOwned + MaybeSend>(
+		&self,
+		sub: &str,
+		params: RpcParams,
+		_unsub: &str,
+	) -> Result<Self::Subscription<Notification>> {
+		match self.find(sub, params)? {
+			MockResult::Subscription(notifications) => Ok(SubscriptionMock {
+				notifications: notifications.into(),
+				subscription_id: self
+					.next_subscription_id
+					.fetch_add(1, Ordering::Relaxed)
+					.to_string(),
+				_phantom: Default::default(),
+			}),
+			MockResult::Error(error) => Err(Error::JsonRpc(error)),
+			MockResult::Response(_) =>
+				Err(json_rpc_error(METHOD_NOT_FOUND_CODE, &format!("{sub} is no subscription"))),
+		}
+	}
+}
+
+/// Subscription of the [`RpcClientMock`], emitting the predefined notifications.
+#[derive(Debug)]
+pub struct SubscriptionMock<Notification> {
+	notifications: VecDeque<Value>,
+	subscription_id: String,
+	_phantom: PhantomData<Notification>,
+}
+
+#[ac_async_macros::maybe_async]
+impl<Notification: DeserializeOwned + MaybeSend> HandleSubscription<Notification>
+	for SubscriptionMock<Notification>
+{
+	async fn next(&mut self) -> Option<Result<Notification>> {
+		let notification = self.notifications.pop_front()?;
+		Some(serde_json::from_value(notification).map_err(|e| e.into()))
+	}
+
+	async fn unsubscribe(self) -> Result<()> {
+		Ok(())
+	}
+
+	fn subscription_id(&self) -> Option<String> {
+		Some(self.subscription_id.clone())
+	}
+}
+
+/// Creates the error a node client returns for a json-rpc error response.
+fn json_rpc_error(code: i32, message: &str) -> Error {
+	Error::JsonRpc(JsonRpcError::new(code, message, None))
+}
+
+fn params_to_value(params: RpcParams) -> Value {
+	params.to_json_value().expect("Mock params must be valid json")
+}
+
+fn to_value<V: Serialize>(value: V) -> Value {
+	serde_json::to_value(value).expect("Mock value must be serializable")
+}
+
+fn serialize<V: Serialize>(value: V) -> SerializedValue {
+	to_value(value).to_string()
+}
+
+/// Creates an offline api on top of the `client` mock, with the Kusama metadata of the repo.
+#[cfg(test)]
+pub(crate) fn create_mock_api<Client>(
+	client: Client,
+) -> crate::Api<ac_primitives::DefaultRuntimeConfig, Client> {
+	let encoded_metadata = std::fs::read(crate::api::api_client::KSM_V14_METADATA_PATH).unwrap();
+	let metadata = crate::api::api_client::decode_metadata(&encoded_metadata).unwrap();
+	create_mock_api_with_metadata(metadata, client)
+}
+
+/// Creates an offline api on top of the `client` mock, with the given `metadata`.
+#[cfg(test)]
+pub(crate) fn create_mock_api_with_metadata<Client>(
+	metadata: ac_node_api::Metadata,
+	client: Client,
+) -> crate::Api<ac_primitives::DefaultRuntimeConfig, Client> {
+	crate::Api::new_offline(
+		sp_core::H256::random(),
+		metadata,
+		sp_version::RuntimeVersion::default(),
+		client,
+	)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::rpc::TransactionPoolError;
+	use ac_compose_macros::rpc_params;
+
+	#[test]
+	fn response_for_params_takes_precedence() {
+		let client = RpcClientMock::default()
+			.with_response("chain_getBlockHash", "0x00")
+			.with_response_for("chain_getBlockHash", rpc_params![1], "0x01");
+
+		let hash: String = client.request("chain_getBlockHash", rpc_params![1]).unwrap();
+		assert_eq!(hash, "0x01");
+		let hash: String = client.request("chain_getBlockHash", rpc_params![2]).unwrap();
+		assert_eq!(hash, "0x00");
+	}
+
+	#[test]
+	fn missing_method_returns_error() {
+		let client = RpcClientMock::default();
+
+		let result = client.request::<String>("system_name", rpc_params![]);
+
+		assert!(matches!(result, Err(Error::JsonRpc(e)) if e.code == METHOD_NOT_FOUND_CODE));
+	}
+
+	#[test]
+	fn unmatched_params_return_error() {
+		let client = RpcClientMock::default().with_response_for(
+			"chain_getBlockHash",
+			rpc_params![1],
+			"0x01",
+		);
+
+		let result = client.request::<String>("chain_getBlockHash", rpc_params![2]);
+
+		assert!(matches!(result, Err(Error::JsonRpc(e)) if e.code == INVALID_PARAMS_CODE));
+	}
+
+	#[test]
+	fn scripted_error_is_returned() {
+		let client = RpcClientMock::default().with_error(
+			"author_submitExtrinsic",
+			1012,
+			"Transaction is temporarily banned",
+		);
+
+		let result = client.request::<String>("author_submitExtrinsic", rpc_params!["0x00"]);
+
+		assert_eq!(result.unwrap_err().pool_error(), Some(TransactionPoolError::TemporarilyBanned));
+	}
+
+	#[test]
+	fn subscription_emits_notifications() {
+		let client =
+			RpcClientMock::default().with_subscription("chain_subscribeNewHeads", vec![1u32, 2]);
+
+		let mut subscription: SubscriptionMock<u32> = client
+			.subscribe("chain_subscribeNewHeads", rpc_params![], "chain_unsubscribeNewHeads")
+			.unwrap();
+
+		assert_eq!(subscription.subscription_id().as_deref(), Some("0"));
+		assert_eq!(subscription.next().unwrap().unwrap(), 1);
+		assert_eq!(subscription.next().unwrap().unwrap(), 2);
+		assert!(subscription.next().is_none());
 	}
 }
@@ -15,10 +15,14 @@
 
 */
 
-use ac_primitives::RpcParams;
+use ac_primitives::{MaybeSend, MaybeSync, RpcParams};
 #[cfg(all(not(feature = "sync-api"), not(feature = "std")))]
 use alloc::boxed::Box;
-use alloc::string::{String, ToString};
+use alloc::{
+	string::{String, ToString},
+	vec::Vec,
+};
+use core::fmt::Debug;
 use serde::de::DeserializeOwned;
 
 #[cfg(feature = "tungstenite-client")]
@@ -26,37 +30,89 @@ pub use tungstenite_client::TungsteniteRpcClient;
 #[cfg(feature = "tungstenite-client")]
 pub mod tungstenite_client;
 
+#[cfg(feature = "http-client")]
+pub use http_client::HttpRpcClient;
+#[cfg(feature = "http-client")]
+pub mod http_client;
+
+#[cfg(all(feature = "ipc-client", unix))]
+pub use ipc_client::IpcRpcClient;
+#[cfg(all(feature = "ipc-client", unix))]
+pub mod ipc_client;
+
 #[cfg(all(feature = "jsonrpsee-client", not(feature = "sync-api")))]
-pub use jsonrpsee_client::JsonrpseeClient;
+pub use jsonrpsee_client::{JsonrpseeClient, JsonrpseeClientBuilder, ReconnectingJsonrpseeClient};
 #[cfg(all(feature = "jsonrpsee-client", not(feature = "sync-api")))]
 #[allow(dead_code)]
 #[allow(unused_imports)]
 pub mod jsonrpsee_client;
 
+pub mod backoff;
 pub mod error;
-#[cfg(feature = "tungstenite-client")]
+#[cfg(feature = "std")]
+pub mod failover;
+#[cfg(any(
+	feature = "tungstenite-client",
+	feature = "ipc-client",
+	all(feature = "http-client", feature = "sync-api")
+))]
 mod helpers;
-
-pub use error::{Error, Result};
-
-#[cfg(test)]
+#[cfg(feature = "instrumentation")]
+pub mod instrumentation;
+#[cfg(all(feature = "std", any(feature = "sync-api", feature = "jsonrpsee-client")))]
+pub mod middleware;
+#[cfg(feature = "std")]
+pub mod recording;
+
+pub use backoff::BackoffPolicy;
+pub use error::{Error, JsonRpcError, Result, TransactionPoolError};
+#[cfg(feature = "std")]
+pub use failover::{FailoverClient, SelectionStrategy};
+#[cfg(feature = "instrumentation")]
+pub use instrumentation::{InstrumentedClient, RpcMetrics};
+#[cfg(all(feature = "std", any(feature = "sync-api", feature = "jsonrpsee-client")))]
+pub use middleware::MiddlewareClient;
+#[cfg(feature = "std")]
+pub use recording::{RecordingClient, ReplayClient};
+
+#[cfg(any(feature = "mocks", test))]
 pub mod mocks;
 
 /// Trait to be implemented by the rpc-client for sending rpc requests and extrinsic.
-#[maybe_async::maybe_async(?Send)]
-pub trait Request {
+///
+/// With the `send-futures` feature, the client needs to be `Send` and `Sync` and the returned
+/// futures are `Send`. This applies to all client traits.
+#[ac_async_macros::maybe_async]
+pub trait Request: MaybeSend + MaybeSync {
 	/// Sends a RPC request to the substrate node and returns the answer as string.
-	async fn request<R: DeserializeOwned>(&self, method: &str, params: RpcParams) -> Result<R>;
+	async fn request<R: DeserializeOwned + MaybeSend>(
+		&self,
+		method: &str,
+		params: RpcParams,
+	) -> Result<R>;
+}
+
+/// Trait to be implemented by the rpc-client for sending several rpc requests within
+/// one json-rpc batch.
+#[ac_async_macros::maybe_async]
+pub trait BatchRequest: MaybeSend + MaybeSync {
+	/// Sends all `requests` to the substrate node in one batch. The results are returned
+	/// in the same order as the requests. An error of a single request does not fail the
+	/// whole batch, it is returned at the position of the failed request instead.
+	async fn batch_request<R: DeserializeOwned + Debug + MaybeSend>(
+		&self,
+		requests: Vec<(&str, RpcParams)>,
+	) -> Result<Vec<Result<R>>>;
 }
 
 /// Trait to be implemented by the rpc-client for subscribing to the substrate node.
-#[maybe_async::maybe_async(?Send)]
-pub trait Subscribe {
+#[ac_async_macros::maybe_async]
+pub trait Subscribe: MaybeSend + MaybeSync {
 	type Subscription<Notification>: HandleSubscription<Notification>
 	where
-		Notification: DeserializeOwned;
+		Notification: DeserializeOwned + MaybeSend;
 
-	async fn subscribe<Notification: DeserializeOwned>(
+	async fn subscribe<Notification: DeserializeOwned + MaybeSend>(
 		&self,
 		sub: &str,
 		params: RpcParams,
@@ -66,8 +122,8 @@ pub trait Subscribe {
 
 /// Trait to use the full functionality of jsonrpseee Subscription type
 /// without actually enforcing it.
-#[maybe_async::maybe_async(?Send)]
-pub trait HandleSubscription<Notification: DeserializeOwned> {
+#[ac_async_macros::maybe_async]
+pub trait HandleSubscription<Notification: DeserializeOwned>: MaybeSend {
 	/// Returns the next notification from the stream.
 	/// This may return `None` if the subscription has been terminated,
 	/// which may happen if the channel becomes full or is dropped.
@@ -79,6 +135,12 @@ pub trait HandleSubscription<Notification: DeserializeOwned> {
 
 	/// Unsubscribe and consume the subscription.
 	async fn unsubscribe(self) -> Result<()>;
+
+	/// Returns the id the node has assigned to the subscription, if known to the client.
+	/// Needed by functions referring to a subscription, e.g. the `chainHead_v1` operations.
+	fn subscription_id(&self) -> Option<String> {
+		None
+	}
 }
 
 pub fn to_json_req(method: &str, params: RpcParams) -> Result<String> {
@@ -90,3 +152,20 @@ pub fn to_json_req(method: &str, params: RpcParams) -> Result<String> {
 	})
 	.to_string())
 }
+
+/// Creates a json-rpc batch request. The id of each request equals its index within the batch.
+pub fn to_json_batch_req(requests: Vec<(&str, RpcParams)>) -> Result<String> {
+	let batch = requests
+		.into_iter()
+		.enumerate()
+		.map(|(id, (method, params))| {
+			Ok(serde_json::json!({
+				"method": method,
+				"params": params.to_json_value()?,
+				"jsonrpc": "2.0",
+				"id": id,
+			}))
+		})
+		.collect::<Result<Vec<_>>>()?;
+	Ok(serde_json::Value::Array(batch).to_string())
+}
@@ -0,0 +1,554 @@
+/*
+   Copyright 2019 Supercomputing Systems AG
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+	   http://www.apache.org/licenses/LICENSE-2.0
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Record the communication with a node and replay it later on without any network access.
+//!
+//! The [`RecordingClient`] wraps any rpc client and captures every request and subscription,
+//! including its parameters and responses. The recorded exchanges can be saved to a json file,
+//! which is served by the [`ReplayClient`] afterwards. This allows deterministic tests against
+//! real chain data.
+
+use crate::rpc::{
+	BatchRequest, Error, HandleSubscription, JsonRpcError, Request, Result, Subscribe,
+};
+use ac_primitives::{MaybeSend, RpcParams};
+use core::{fmt::Debug, marker::PhantomData};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use serde_json::Value;
+use std::{
+	fs::File,
+	io::{BufReader, BufWriter},
+	path::{Path, PathBuf},
+	sync::{Arc, Mutex, MutexGuard},
+};
+
+/// A single recorded request or subscription.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum Exchange {
+	Request {
+		method: String,
+		params: Value,
+		result: RecordedResult,
+	},
+	Subscription {
+		method: String,
+		params: Value,
+		/// Set if the subscription could not be established.
+		error: Option<RecordedError>,
+		/// Id assigned by the node, if known to the recording client.
+		#[serde(default, skip_serializing_if = "Option::is_none")]
+		subscription_id: Option<String>,
+		notifications: Vec<Value>,
+	},
+}
+
+/// Response of the node to a recorded request.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RecordedResult {
+	Ok(Value),
+	Err(RecordedError),
+}
+
+/// Recorded error of a request or subscription.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RecordedError {
+	/// Json-rpc error response of the node, including its code and data. It is replayed as
+	/// [`Error::JsonRpc`].
+	JsonRpc(JsonRpcError),
+	/// Any other error, e.g. of the connection. Only its description is recorded.
+	Other(String),
+}
+
+impl From<&Error> for RecordedError {
+	fn from(error: &Error) -> Self {
+		match error {
+			Error::JsonRpc(error) => Self::JsonRpc(error.clone()),
+			error => Self::Other(format!("{error:?}")),
+		}
+	}
+}
+
+impl From<RecordedError> for Error {
+	fn from(error: RecordedError) -> Self {
+		match error {
+			RecordedError::JsonRpc(error) => Self::JsonRpc(error),
+			RecordedError::Other(description) => Self::Client(description.into()),
+		}
+	}
+}
+
+impl Exchange {
+	fn is_request_for(&self, method: &str, params: &Value) -> bool {
+		matches!(self, Self::Request { method: m, params: p, .. } if m == method && p == params)
+	}
+
+	fn is_subscription_for(&self, method: &str, params: &Value) -> bool {
+		matches!(self, Self::Subscription { method: m, params: p, .. } if m == method && p == params)
+	}
+}
+
+/// Rpc client wrapper recording all exchanges of the inner client.
+///
+/// The recording is written to the file given upon creation by calling [`RecordingClient::save`].
+/// Subscription notifications are only recorded once they have been received via
+/// [`HandleSubscription::next`].
+#[derive(Debug)]
+pub struct RecordingClient<Client> {
+	inner: Client,
+	path: PathBuf,
+	exchanges: Arc<Mutex<Vec<Exchange>>>,
+}
+
+impl<Client> RecordingClient<Client> {
+	/// Create a new client recording all exchanges of `inner`, which are saved to `path`.
+	pub fn new(inner: Client, path: impl Into<PathBuf>) -> Self {
+		Self { inner, path: path.into(), exchanges: Default::default() }
+	}
+
+	/// All exchanges recorded so far.
+	pub fn exchanges(&self) -> Vec<Exchange> {
+		lock(&self.exchanges).clone()
+	}
+
+	/// Write all exchanges recorded so far to the json file.
+	pub fn save(&self) -> Result<()> {
+		let writer = BufWriter::new(File::create(&self.path)?);
+		serde_json::to_writer_pretty(writer, &*lock(&self.exchanges))?;
+		Ok(())
+	}
+
+	fn record(&self, exchange: Exchange) -> usize {
+		let mut exchanges = lock(&self.exchanges);
+		exchanges.push(exchange);
+		exchanges.len() - 1
+	}
+}
+
+#[ac_async_macros::maybe_async]
+impl<Client: Request> Request for RecordingClient<Client> {
+	async fn request<R: DeserializeOwned + MaybeSend>(
+		&self,
+		method: &str,
+		params: RpcParams,
+	) -> Result<R> {
+		let recorded_params = params.clone().to_json_value()?;
+		let result = self.inner.request::<Value>(method, params).await;
+		self.record(Exchange::Request {
+			method: method.into(),
+			params: recorded_params,
+			result: to_recorded_result(&result),
+		});
+		Ok(serde_json::from_value(result?)?)
+	}
+}
+
+#[ac_async_macros::maybe_async]
+impl<Client: BatchRequest> BatchRequest for RecordingClient<Client> {
+	async fn batch_request<R: DeserializeOwned + Debug + MaybeSend>(
+		&self,
+		requests: Vec<(&str, RpcParams)>,
+	) -> Result<Vec<Result<R>>> {
+		// The requests of a batch are recorded one by one, so they can be replayed independently.
+		let recorded_requests = requests
+			.iter()
+			.map(|(method, params)| Ok((method.to_string(), params.clone().to_json_value()?)))
+			.collect::<Result<Vec<_>>>()?;
+		let results = self.inner.batch_request::<Value>(requests).await?;
+		let results = recorded_requests
+			.into_iter()
+			.zip(results)
+			.map(|((method, params), result)| {
+				self.record(Exchange::Request {
+					method,
+					params,
+					result: to_recorded_result(&result),
+				});
+				Ok(serde_json::from_value(result?)?)
+			})
+			.collect();
+		Ok(results)
+	}
+}
+
+#[ac_async_macros::maybe_async]
+impl<Client: Subscribe> Subscribe for RecordingClient<Client> {
+	type Subscription<Notification>
+		= RecordingSubscription<Client::Subscription<Value>, Notification>
+	where
+		Notification: DeserializeOwned + MaybeSend;
+
+	async fn subscribe<Notification: DeserializeOwned + MaybeSend>(
+		&self,
+		sub: &str,
+		params: RpcParams,
+		unsub: &str,
+	) -> Result<Self::Subscription<Notification>> {
+		let recorded_params = params.clone().to_json_value()?;
+		let result = self.inner.subscribe::<Value>(sub, params, unsub).await;
+		let index = self.record(Exchange::Subscription {
+			method: sub.into(),
+			params: recorded_params,
+			error: result.as_ref().err().map(RecordedError::from),
+			subscription_id: result.as_ref().ok().and_then(|s| s.subscription_id()),
+			notifications: Vec::new(),
+		});
+		Ok(RecordingSubscription {
+			inner: result?,
+			exchanges: self.exchanges.clone(),
+			index,
+			_phantom: Default::default(),
+		})
+	}
+}
+
+/// Subscription of the [`RecordingClient`], recording every received notification.
+#[derive(Debug)]
+pub struct RecordingSubscription<Subscription, Notification> {
+	inner: Subscription,
+	exchanges: Arc<Mutex<Vec<Exchange>>>,
+	// Position of the subscription within the recorded exchanges.
+	index: usize,
+	_phantom: PhantomData<Notification>,
+}
+
+#[ac_async_macros::maybe_async]
+impl<Subscription, Notification> HandleSubscription<Notification>
+	for RecordingSubscription<Subscription, Notification>
+where
+	Subscription: HandleSubscription<Value>,
+	Notification: DeserializeOwned + MaybeSend,
+{
+	async fn next(&mut self) -> Option<Result<Notification>> {
+		let notification = match self.inner.next().await? {
+			Ok(notification) => notification,
+			Err(e) => return Some(Err(e)),
+		};
+		if let Some(Exchange::Subscription { notifications, .. }) =
+			lock(&self.exchanges).get_mut(self.index)
+		{
+			notifications.push(notification.clone());
+		}
+		Some(serde_json::from_value(notification).map_err(|e| e.into()))
+	}
+
+	async fn unsubscribe(self) -> Result<()> {
+		self.inner.unsubscribe().await
+	}
+
+	fn subscription_id(&self) -> Option<String> {
+		self.inner.subscription_id()
+	}
+}
+
+/// Rpc client serving previously recorded exchanges without any network access.
+///
+/// Requests and subscriptions are matched by method and params. If the same request has been
+/// recorded several times, the responses are returned in the recorded order. Once all of them
+/// have been served, the last one is repeated.
+#[derive(Debug)]
+pub struct ReplayClient {
+	exchanges: Vec<Exchange>,
+	served: Mutex<Vec<bool>>,
+}
+
+impl ReplayClient {
+	/// Create a new client serving the given `exchanges`.
+	pub fn new(exchanges: Vec<Exchange>) -> Self {
+		let served = Mutex::new(vec![false; exchanges.len()]);
+		Self { exchanges, served }
+	}
+
+	/// Create a new client serving the exchanges recorded to the json file at `path`.
+	pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+		let reader = BufReader::new(File::open(path)?);
+		let exchanges = serde_json::from_reader(reader)?;
+		Ok(Self::new(exchanges))
+	}
+
+	/// Returns the first matching exchange that has not been served yet,
+	/// or the last matching one if all of them have been served already.
+	fn find(&self, is_match: impl Fn(&Exchange) -> bool) -> Option<&Exchange> {
+		let mut served = lock(&self.served);
+		let mut last_match = None;
+		for (index, exchange) in self.exchanges.iter().enumerate().filter(|(_, e)| is_match(e)) {
+			if !served[index] {
+				served[index] = true;
+				return Some(exchange)
+			}
+			last_match = Some(exchange);
+		}
+		last_match
+	}
+}
+
+#[ac_async_macros::maybe_async]
+impl Request for ReplayClient {
+	async fn request<R: DeserializeOwned + MaybeSend>(
+		&self,
+		method: &str,
+		params: RpcParams,
+	) -> Result<R> {
+		let params = params.to_json_value()?;
+		match self.find(|exchange| exchange.is_request_for(method, &params)) {
+			Some(Exchange::Request { result: RecordedResult::Ok(value), .. }) =>
+				Ok(serde_json::from_value(value.clone())?),
+			Some(Exchange::Request { result: RecordedResult::Err(error), .. }) =>
+				Err(error.clone().into()),
+			_ => Err(not_recorded(method, &params)),
+		}
+	}
+}
+
+#[ac_async_macros::maybe_async]
+impl BatchRequest for ReplayClient {
+	async fn batch_request<R: DeserializeOwned + Debug + MaybeSend>(
+		&self,
+		requests: Vec<(&str, RpcParams)>,
+	) -> Result<Vec<Result<R>>> {
+		let mut results = Vec::with_capacity(requests.len());
+		for (method, params) in requests {
+			results.push(self.request(method, params).await);
+		}
+		Ok(results)
+	}
+}
+
+#[ac_async_macros::maybe_async]
+impl Subscribe for ReplayClient {
+	type Subscription<Notification>
+		= ReplaySubscription<Notification>
+	where
+		Notification: DeserializeOwned + MaybeSend;
+
+	async fn subscribe<Notification: DeserializeOwned + MaybeSend>(
+		&self,
+		sub: &str,
+		params: RpcParams,
+		_unsub: &str,
+	) -> Result<Self::Subscription<Notification>> {
+		let params = params.to_json_value()?;
+		match self.find(|exchange| exchange.is_subscription_for(sub, &params)) {
+			Some(Exchange::Subscription { error: Some(error), .. }) => Err(error.clone().into()),
+			Some(Exchange::Subscription { subscription_id, notifications, .. }) =>
+				Ok(ReplaySubscription {
+					notifications: notifications.clone().into_iter(),
+					subscription_id: subscription_id.clone(),
+					_phantom: Default::default(),
+				}),
+			_ => Err(not_recorded(sub, &params)),
+		}
+	}
+}
+
+/// Subscription of the [`ReplayClient`], returning the recorded notifications.
+#[derive(Debug)]
+pub struct ReplaySubscription<Notification> {
+	notifications: std::vec::IntoIter<Value>,
+	subscription_id: Option<String>,
+	_phantom: PhantomData<Notification>,
+}
+
+#[ac_async_macros::maybe_async]
+impl<Notification: DeserializeOwned + MaybeSend> HandleSubscription<Notification>
+	for ReplaySubscription<Notification>
+{
+	async fn next(&mut self) -> Option<Result<Notification>> {
+		let notification = self.notifications.next()?;
+		Some(serde_json::from_value(notification).map_err(|e| e.into()))
+	}
+
+	async fn unsubscribe(self) -> Result<()> {
+		Ok(())
+	}
+
+	fn subscription_id(&self) -> Option<String> {
+		self.subscription_id.clone()
+	}
+}
+
+fn to_recorded_result(result: &Result<Value>) -> RecordedResult {
+	match result {
+		Ok(value) => RecordedResult::Ok(value.clone()),
+		Err(e) => RecordedResult::Err(e.into()),
+	}
+}
+
+fn not_recorded(method: &str, params: &Value) -> Error {
+	Error::UnexpectedResponse(format!("No recorded exchange for {method} with params {params}"))
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+	// The recorded data stays consistent, even if a panic happened while it was locked.
+	mutex.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::rpc::{TransactionPoolError, mocks::RpcClientMock};
+	use ac_compose_macros::rpc_params;
+	use sp_runtime::transaction_validity::InvalidTransaction;
+
+	fn recording_client(data: &[(&str, Value)], file_name: &str) -> RecordingClient<RpcClientMock> {
+		let data = data
+			.iter()
+			.map(|(method, value)| (method.to_string(), value.to_string()))
+			.collect();
+		RecordingClient::new(RpcClientMock::new(data), std::env::temp_dir().join(file_name))
+	}
+
+	#[test]
+	fn recorded_request_is_replayed() {
+		let client =
+			recording_client(&[("chain_getBlockHash", "0x01".into())], "unsaved_recording.json");
+		let hash: String = client.request("chain_getBlockHash", rpc_params![0]).unwrap();
+		assert_eq!(hash, "0x01");
+
+		let replay = ReplayClient::new(client.exchanges());
+		let replayed_hash: String = replay.request("chain_getBlockHash", rpc_params![0]).unwrap();
+
+		assert_eq!(replayed_hash, hash);
+	}
+
+	#[test]
+	fn request_with_other_params_is_not_replayed() {
+		let client =
+			recording_client(&[("chain_getBlockHash", "0x01".into())], "unsaved_recording.json");
+		let _: String = client.request("chain_getBlockHash", rpc_params![0]).unwrap();
+
+		let replay = ReplayClient::new(client.exchanges());
+		let result = replay.request::<String>("chain_getBlockHash", rpc_params![1]);
+
+		assert!(matches!(result, Err(Error::UnexpectedResponse(_))));
+	}
+
+	#[test]
+	fn repeated_requests_are_replayed_in_order() {
+		let exchange = |value: &str| Exchange::Request {
+			method: "chain_getFinalizedHead".into(),
+			params: rpc_params![].to_json_value().unwrap(),
+			result: RecordedResult::Ok(value.into()),
+		};
+		let replay = ReplayClient::new(vec![exchange("0x01"), exchange("0x02")]);
+		let request = || replay.request::<String>("chain_getFinalizedHead", rpc_params![]);
+
+		assert_eq!(request().unwrap(), "0x01");
+		assert_eq!(request().unwrap(), "0x02");
+		// The last response is repeated.
+		assert_eq!(request().unwrap(), "0x02");
+	}
+
+	#[test]
+	fn recorded_error_is_replayed() {
+		let replay = ReplayClient::new(vec![Exchange::Request {
+			method: "author_submitExtrinsic".into(),
+			params: rpc_params!["0x00"].to_json_value().unwrap(),
+			result: RecordedResult::Err(RecordedError::JsonRpc(JsonRpcError::new(
+				1010,
+				"Invalid Transaction",
+				Some("Transaction is outdated".into()),
+			))),
+		}]);
+
+		let result = replay.request::<String>("author_submitExtrinsic", rpc_params!["0x00"]);
+
+		assert!(matches!(
+			result.unwrap_err().pool_error(),
+			Some(TransactionPoolError::InvalidTransaction(Some(InvalidTransaction::Stale)))
+		));
+	}
+
+	#[test]
+	fn recorded_subscription_error_is_replayed() {
+		let error = JsonRpcError::new(-32602, "Invalid params", Some("Invalid block hash".into()));
+		let client = RecordingClient::new(
+			RpcClientMock::default().with_json_rpc_error("chain_subscribeNewHeads", error.clone()),
+			std::env::temp_dir().join("unsaved_recording.json"),
+		);
+		let result = client.subscribe::<u32>(
+			"chain_subscribeNewHeads",
+			rpc_params![],
+			"chain_unsubscribeNewHeads",
+		);
+		assert!(matches!(result, Err(Error::JsonRpc(e)) if e == error));
+
+		let replay = ReplayClient::new(client.exchanges());
+		let result = replay.subscribe::<u32>(
+			"chain_subscribeNewHeads",
+			rpc_params![],
+			"chain_unsubscribeNewHeads",
+		);
+
+		assert!(matches!(result, Err(Error::JsonRpc(e)) if e == error));
+	}
+
+	#[test]
+	fn recorded_subscription_is_replayed() {
+		let replay = ReplayClient::new(vec![Exchange::Subscription {
+			method: "chain_subscribeFinalizedHeads".into(),
+			params: rpc_params![].to_json_value().unwrap(),
+			error: None,
+			subscription_id: Some("sub".into()),
+			notifications: vec![1.into(), 2.into()],
+		}]);
+
+		let mut subscription: ReplaySubscription<u32> = replay
+			.subscribe(
+				"chain_subscribeFinalizedHeads",
+				rpc_params![],
+				"chain_unsubscribeFinalizedHeads",
+			)
+			.unwrap();
+
+		assert_eq!(subscription.subscription_id().as_deref(), Some("sub"));
+		assert_eq!(subscription.next().unwrap().unwrap(), 1);
+		assert_eq!(subscription.next().unwrap().unwrap(), 2);
+		assert!(subscription.next().is_none());
+	}
+
+	#[test]
+	fn saved_recording_can_be_loaded() {
+		let client = recording_client(&[("system_name", "node".into())], "recording_test.json");
+		let _: String = client.request("system_name", rpc_params![]).unwrap();
+		client.save().unwrap();
+
+		let replay = ReplayClient::from_file(&client.path).unwrap();
+
+		assert_eq!(replay.exchanges, client.exchanges());
+		let _ = std::fs::remove_file(&client.path);
+	}
+
+	#[test]
+	fn exchanges_have_readable_json_format() {
+		let exchange = Exchange::Request {
+			method: "system_name".into(),
+			params: Value::Array(vec![]),
+			result: RecordedResult::Ok("node".into()),
+		};
+
+		let json = serde_json::to_value(&exchange).unwrap();
+
+		assert_eq!(
+			json,
+			serde_json::json!({
+				"kind": "request",
+				"method": "system_name",
+				"params": [],
+				"result": { "ok": "node" },
+			})
+		);
+	}
+}
@@ -15,41 +15,77 @@
 
 */
 use crate::rpc::{
-	Error as RpcClientError, Request, Result, Subscribe, helpers, to_json_req,
-	tungstenite_client::subscription::TungsteniteSubscriptionWrapper,
+	BackoffPolicy, BatchRequest, Request, Result, Subscribe, helpers,
+	tungstenite_client::{
+		connection::{
+			Command, CommandSender, ConnectionWorker, DEFAULT_REQUEST_TIMEOUT, SubscriptionIds,
+			receive_response,
+		},
+		subscription::TungsteniteSubscriptionWrapper,
+	},
 };
-use ac_primitives::RpcParams;
-use log::*;
+use ac_primitives::{MaybeSend, RpcParams};
 use serde::de::DeserializeOwned;
-use serde_json::Value;
 use std::{
 	fmt::Debug,
-	net::TcpStream,
-	sync::mpsc::{Sender as ThreadOut, channel},
+	sync::{Arc, mpsc::channel},
 	thread,
-	thread::sleep,
 	time::Duration,
 };
-use tungstenite::{
-	Message, WebSocket, client::connect_with_config, handshake::client::Response,
-	stream::MaybeTlsStream,
-};
 use url::Url;
 
-pub(crate) type MySocket = WebSocket<MaybeTlsStream<TcpStream>>;
-
+/// Websocket rpc client based on `tungstenite`.
+///
+/// All requests and subscriptions share one websocket connection, which is
+/// established upon the first request and re-established if it is lost.
+/// Clones of the client share the same connection.
+/// Requests fail with [`Error::RequestTimeout`](crate::rpc::Error::RequestTimeout) if the node does not answer within the
+/// request timeout, which is 60 seconds by default.
 #[derive(Debug, Clone)]
 pub struct TungsteniteRpcClient {
 	url: Url,
-	max_attempts: u8,
+	commands: Arc<CommandSender>,
+	subscription_ids: SubscriptionIds,
+	request_timeout: Duration,
 }
 
 impl TungsteniteRpcClient {
 	/// Create a new client with the given url string.
 	/// Example url input: "ws://127.0.0.1:9944"
 	pub fn new(url: &str, max_attempts: u8) -> Result<Self> {
+		Self::new_with_backoff(url, BackoffPolicy::default().with_max_attempts(max_attempts))
+	}
+
+	/// Create a new client with the given url string and the policy that defines
+	/// how to reconnect in case the connection is lost.
+	/// Example url input: "ws://127.0.0.1:9944"
+	pub fn new_with_backoff(url: &str, backoff: BackoffPolicy) -> Result<Self> {
 		let url: Url = Url::parse(url)?;
-		Ok(Self { url, max_attempts })
+		let (commands, receiver) = channel();
+		let subscription_ids = SubscriptionIds::default();
+		let worker = ConnectionWorker::new(
+			url.clone(),
+			backoff,
+			receiver,
+			commands.clone(),
+			subscription_ids.clone(),
+		);
+		thread::Builder::new()
+			.name("tungstenite-rpc-client".into())
+			.spawn(move || worker.run())?;
+		Ok(Self {
+			url,
+			commands: Arc::new(CommandSender::new(commands)),
+			subscription_ids,
+			request_timeout: DEFAULT_REQUEST_TIMEOUT,
+		})
+	}
+
+	/// Set the time to wait for the response of the node, including the time needed to
+	/// (re-)connect to it.
+	pub fn with_request_timeout(mut self, timeout: Duration) -> Self {
+		self.request_timeout = timeout;
+		self
 	}
 
 	/// Create a new client with the given address, port and max number of reconnection attempts.
@@ -68,168 +104,245 @@ impl TungsteniteRpcClient {
 	}
 }
 
-#[maybe_async::maybe_async(?Send)]
+#[ac_async_macros::maybe_async]
 impl Request for TungsteniteRpcClient {
-	async fn request<R: DeserializeOwned>(&self, method: &str, params: RpcParams) -> Result<R> {
-		let json_req = to_json_req(method, params)?;
-		let response = self.direct_rpc_request(json_req)?;
-		let deserialized_value: R = serde_json::from_str(&response)?;
-		Ok(deserialized_value)
+	async fn request<R: DeserializeOwned + MaybeSend>(
+		&self,
+		method: &str,
+		params: RpcParams,
+	) -> Result<R> {
+		let request = helpers::to_json_value_req(method, params)?;
+		let (response, receiver) = channel();
+		self.send_command(Command::Request { request, response })?;
+		let value = receive_response(&receiver, self.request_timeout)?;
+		helpers::read_result(value)
+	}
+}
+
+#[ac_async_macros::maybe_async]
+impl BatchRequest for TungsteniteRpcClient {
+	async fn batch_request<R: DeserializeOwned + Debug + MaybeSend>(
+		&self,
+		requests: Vec<(&str, RpcParams)>,
+	) -> Result<Vec<Result<R>>> {
+		if requests.is_empty() {
+			return Ok(Vec::new())
+		}
+		let requests = requests
+			.into_iter()
+			.map(|(method, params)| helpers::to_json_value_req(method, params))
+			.collect::<Result<Vec<_>>>()?;
+		let (response, receiver) = channel();
+		self.send_command(Command::Batch { requests, response })?;
+		let results = receive_response(&receiver, self.request_timeout)?
+			.into_iter()
+			.map(|result| Ok(serde_json::from_value(result?)?))
+			.collect();
+		Ok(results)
 	}
 }
 
-#[maybe_async::maybe_async(?Send)]
+#[ac_async_macros::maybe_async]
 impl Subscribe for TungsteniteRpcClient {
 	type Subscription<Notification>
 		= TungsteniteSubscriptionWrapper<Notification>
 	where
-		Notification: DeserializeOwned;
+		Notification: DeserializeOwned + MaybeSend;
 
-	async fn subscribe<Notification: DeserializeOwned>(
+	async fn subscribe<Notification: DeserializeOwned + MaybeSend>(
 		&self,
 		sub: &str,
 		params: RpcParams,
-		_unsub: &str,
+		unsub: &str,
 	) -> Result<Self::Subscription<Notification>> {
-		let json_req = to_json_req(sub, params)?;
-		let (result_in, receiver) = channel();
-		self.start_rpc_client_thread(json_req, result_in)?;
-		let subscription = TungsteniteSubscriptionWrapper::new(receiver);
-		Ok(subscription)
+		let request = helpers::to_json_value_req(sub, params)?;
+		let (confirmation, confirmation_receiver) = channel();
+		let (notifications, receiver) = channel();
+		self.send_command(Command::Subscribe {
+			request,
+			unsubscribe_method: unsub.into(),
+			confirmation,
+			notifications,
+		})?;
+		let key = receive_response(&confirmation_receiver, self.request_timeout)?;
+		Ok(TungsteniteSubscriptionWrapper::new(
+			receiver,
+			key,
+			self.subscription_ids.clone(),
+			self.commands.clone(),
+			self.request_timeout,
+		))
 	}
 }
 
 impl TungsteniteRpcClient {
-	fn direct_rpc_request(&self, json_req: String) -> Result<String> {
-		let (mut socket, response) = attempt_connection_until(&self.url, self.max_attempts)?;
-		debug!("Connected to the server. Response HTTP code: {}", response.status());
-
-		// Send request to server.
-		socket.send(Message::Text(json_req.into()))?;
-
-		let msg = read_until_text_message(&mut socket)?;
-
-		trace!("Got get_request_msg {msg}");
-		let result_str =
-			serde_json::from_str(msg.as_str()).map(|v: Value| v["result"].to_string())?;
-		Ok(result_str)
+	fn send_command(&self, command: Command) -> Result<()> {
+		self.commands.send(command)
 	}
+}
 
-	fn start_rpc_client_thread(
-		&self,
-		json_req: String,
-		result_in: ThreadOut<String>,
-	) -> Result<()> {
-		let url = self.url.clone();
-		let max_attempts = self.max_attempts;
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::rpc::{Error as RpcClientError, HandleSubscription};
+	use ac_compose_macros::rpc_params;
+	use serde_json::{Value, json};
+	use std::net::{TcpListener, TcpStream};
+	use tungstenite::Message;
+
+	/// Starts a websocket json-rpc server, which serves every connection in a separate thread.
+	/// Returns the url to connect to.
+	fn start_server() -> String {
+		let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+		let url = format!("ws://{}", listener.local_addr().unwrap());
 		thread::spawn(move || {
-			let mut current_attempt = 0;
-			while current_attempt <= max_attempts {
-				if let Err(error) =
-					subscribe_to_server(&url, max_attempts, json_req.clone(), result_in.clone())
-					&& !do_reconnect(&error)
-				{
-					break
-				}
-				current_attempt += 1;
+			for (connection, stream) in listener.incoming().enumerate() {
+				thread::spawn(move || serve(connection, stream.unwrap()));
 			}
 		});
-		Ok(())
+		url
 	}
-}
 
-fn subscribe_to_server(
-	url: &Url,
-	max_attempts: u8,
-	json_req: String,
-	result_in: ThreadOut<String>,
-) -> Result<()> {
-	let (mut socket, response) = attempt_connection_until(url, max_attempts)?;
-	debug!("Connected to the server. Response HTTP code: {}", response.status());
-
-	// Subscribe to server
-	socket.send(Message::Text(json_req.into()))?;
-
-	// Read the first message response - must be the subscription id.
-	let msg = read_until_text_message(&mut socket)?;
-	let value: Value = serde_json::from_str(&msg)?;
-
-	let subcription_id = match helpers::read_subscription_id(&value) {
-		Some(id) => id,
-		None => {
-			let message = helpers::read_error_message(&value, &msg);
-			result_in.send(message)?;
-			return Ok(())
-		},
-	};
+	/// Answers the requests of one connection, until the client sends `close_connection`.
+	fn serve(connection: usize, stream: TcpStream) {
+		let mut socket = tungstenite::accept(stream).unwrap();
+		let mut held_responses = Vec::new();
+		while let Ok(message) = socket.read() {
+			let Message::Text(text) = message else { continue };
+			let request: Value = serde_json::from_str(&text).unwrap();
+			if request["method"] == "close_connection" {
+				return
+			}
+			for response in respond(connection, &request, &mut held_responses) {
+				if socket.send(Message::Text(response.to_string().into())).is_err() {
+					return
+				}
+			}
+		}
+	}
 
-	loop {
-		let msg = read_until_text_message(&mut socket)?;
-		send_message_to_client(result_in.clone(), &msg, &subcription_id)?;
+	fn respond(connection: usize, request: &Value, held_responses: &mut Vec<Value>) -> Vec<Value> {
+		if let Value::Array(requests) = request {
+			// Answer batches in reverse order, as nodes may do.
+			let responses = requests
+				.iter()
+				.rev()
+				.flat_map(|request| respond(connection, request, held_responses))
+				.collect();
+			return vec![Value::Array(responses)]
+		}
+		let id = &request["id"];
+		let response = |result: Value| json!({ "jsonrpc": "2.0", "id": id, "result": result });
+		match request["method"].as_str().unwrap() {
+			"system_name" => vec![response(json!("node"))],
+			// Echoes the param, but only once a second request to hold has arrived. Both are
+			// answered in reverse order.
+			"hold" => {
+				held_responses.push(response(request["params"][0].clone()));
+				if held_responses.len() < 2 {
+					return Vec::new()
+				}
+				held_responses.drain(..).rev().collect()
+			},
+			"no_response" => Vec::new(),
+			// Nodes answer without id if they can't tell which request an error belongs to.
+			"oversized_request" => vec![json!({
+				"jsonrpc": "2.0",
+				"id": null,
+				"error": { "code": -32600, "message": "Request is too big" },
+			})],
+			// The subscription id and the notification tell which connection they belong to.
+			"chain_subscribeNewHeads" => {
+				let subscription = format!("sub-{connection}");
+				vec![
+					response(json!(subscription)),
+					json!({
+						"jsonrpc": "2.0",
+						"method": "chain_newHead",
+						"params": { "subscription": subscription, "result": connection },
+					}),
+				]
+			},
+			"chain_unsubscribeNewHeads" => vec![response(json!(true))],
+			method => vec![json!({
+				"jsonrpc": "2.0",
+				"id": id,
+				"error": { "code": -32601, "message": format!("Method not found: {method}") },
+			})],
+		}
 	}
-}
 
-pub fn do_reconnect(error: &RpcClientError) -> bool {
-	matches!(
-		error,
-		RpcClientError::SerdeJson(_) | RpcClientError::ConnectionClosed | RpcClientError::Client(_)
-	)
-}
+	#[test]
+	fn concurrent_requests_share_the_connection() {
+		let client = TungsteniteRpcClient::new(&start_server(), 1).unwrap();
+		let other_client = client.clone();
 
-fn send_message_to_client(
-	result_in: ThreadOut<String>,
-	message: &str,
-	subscription_id: &str,
-) -> Result<()> {
-	trace!("got on_subscription_msg {message}");
-	let value: Value = serde_json::from_str(message)?;
+		let other_request =
+			thread::spawn(move || other_client.request::<u32>("hold", rpc_params![1]).unwrap());
+		let result: u32 = client.request("hold", rpc_params![2]).unwrap();
 
-	if helpers::subscription_id_matches(&value, subscription_id) {
-		result_in.send(serde_json::to_string(&value["params"]["result"])?)?;
+		// The responses arrive in reverse order, but each one is returned to its caller.
+		assert_eq!(result, 2);
+		assert_eq!(other_request.join().unwrap(), 1);
 	}
 
-	Ok(())
-}
+	#[test]
+	fn batch_results_are_returned_in_order() {
+		let client = TungsteniteRpcClient::new(&start_server(), 1).unwrap();
+		// Let the ids of the batch start after the ones already used.
+		client.request::<String>("system_name", rpc_params![]).unwrap();
+
+		let results = client
+			.batch_request::<String>(vec![
+				("system_name", rpc_params![]),
+				("system_chain", rpc_params![]),
+				("system_name", rpc_params![]),
+			])
+			.unwrap();
+
+		assert_eq!(results[0].as_ref().unwrap(), "node");
+		assert!(matches!(results[1], Err(RpcClientError::JsonRpc(ref e)) if e.code == -32601));
+		assert_eq!(results[2].as_ref().unwrap(), "node");
+	}
 
-fn attempt_connection_until(url: &Url, max_attempts: u8) -> Result<(MySocket, Response)> {
-	let mut current_attempt: u8 = 0;
-	while current_attempt <= max_attempts {
-		match connect_with_config(url.clone(), None, u8::MAX - 1) {
-			Ok((socket, responses)) => return Ok((socket, responses)),
-			Err(e) => warn!("Connection attempt failed due to {e:?}"),
-		};
-		trace!("Trying to reconnect. Current attempt {current_attempt}");
-		sleep(Duration::from_secs(5));
-		current_attempt += 1;
+	#[test]
+	fn subscription_is_renewed_upon_reconnection() {
+		let client = TungsteniteRpcClient::new(&start_server(), 3).unwrap();
+		let mut subscription: TungsteniteSubscriptionWrapper<usize> = client
+			.subscribe("chain_subscribeNewHeads", rpc_params![], "chain_unsubscribeNewHeads")
+			.unwrap();
+		assert_eq!(subscription.next().unwrap().unwrap(), 0);
+		assert_eq!(subscription.subscription_id().unwrap(), "sub-0");
+
+		let result = client.request::<bool>("close_connection", rpc_params![]);
+
+		assert!(result.unwrap_err().is_connection_error());
+		assert_eq!(subscription.next().unwrap().unwrap(), 1);
+		assert_eq!(subscription.subscription_id().unwrap(), "sub-1");
+		subscription.unsubscribe().unwrap();
 	}
 
-	Err(RpcClientError::MaxConnectionAttemptsExceeded)
-}
+	#[test]
+	fn error_without_id_fails_pending_request() {
+		let client = TungsteniteRpcClient::new(&start_server(), 1).unwrap();
 
-fn read_until_text_message(socket: &mut MySocket) -> Result<String> {
-	loop {
-		match socket.read()? {
-			Message::Text(s) => break Ok(s.as_str().into()),
-			Message::Binary(_) => {
-				debug!("skip binary msg");
-			},
-			Message::Ping(_) => {
-				debug!("skip ping msg");
-			},
-			Message::Pong(_) => {
-				debug!("skip ping msg");
-			},
-			Message::Close(_) => break Err(RpcClientError::ConnectionClosed),
-			Message::Frame(_) => {
-				debug!("skip frame msg");
-			},
-		}
+		let result = client.request::<String>("oversized_request", rpc_params![]);
+
+		assert!(matches!(result, Err(RpcClientError::JsonRpc(e)) if e.code == -32600));
+		// The connection is still usable afterwards.
+		assert_eq!(client.request::<String>("system_name", rpc_params![]).unwrap(), "node");
 	}
-}
 
-#[cfg(test)]
-mod tests {
-	use super::*;
+	#[test]
+	fn request_without_response_times_out() {
+		let client = TungsteniteRpcClient::new(&start_server(), 1)
+			.unwrap()
+			.with_request_timeout(Duration::from_millis(100));
+
+		let result = client.request::<String>("no_response", rpc_params![]);
+
+		assert!(matches!(result, Err(RpcClientError::RequestTimeout)));
+	}
 
 	#[test]
 	fn client_new() {
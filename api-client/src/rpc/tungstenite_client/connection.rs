@@ -0,0 +1,753 @@
+/*
+   Copyright 2019 Supercomputing Systems AG
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+	   http://www.apache.org/licenses/LICENSE-2.0
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! The websocket connection shared by all requests and subscriptions of a `TungsteniteRpcClient`.
+//!
+//! The connection is managed by a single background thread. Requests are handed over to that
+//! thread via channel and tagged with a unique id, so that the responses can be matched to the
+//! waiting caller. Subscription notifications are forwarded according to their subscription id.
+//!
+//! Incoming messages are read by a second thread, which blocks on the socket until data arrives
+//! and forwards the messages via the same channel. So both threads sleep until there is
+//! something to do. Closing the connection shuts the socket down, which wakes the reader.
+//!
+//! Every subscription is identified by a local key, which stays the same for the whole lifetime
+//! of the subscription. The subscription id assigned by the node changes whenever the
+//! subscription is renewed after a reconnection. The current ids are kept in the shared
+//! [`SubscriptionIds`] table.
+
+use crate::rpc::{
+	BackoffPolicy, Error as RpcClientError, Result,
+	helpers::{self, IncomingMessage},
+};
+use log::*;
+use serde_json::{Value, json};
+use std::{
+	collections::{HashMap, VecDeque},
+	io::{self, ErrorKind},
+	net::{Shutdown, TcpStream},
+	sync::{
+		Arc, Mutex,
+		mpsc::{Receiver, RecvTimeoutError, Sender, TryRecvError, channel},
+	},
+	thread::{self, sleep},
+	time::Duration,
+};
+use tungstenite::{Message, WebSocket, client::connect_with_config, stream::MaybeTlsStream};
+use url::Url;
+
+pub(crate) type MySocket = WebSocket<MaybeTlsStream<TcpStream>>;
+
+/// Time a client waits for the response of the node, unless configured otherwise.
+pub(crate) const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Ids assigned by the node to the active subscriptions, by local key. A subscription that is
+/// being renewed has no entry until the node has confirmed it.
+pub(crate) type SubscriptionIds = Arc<Mutex<HashMap<u64, String>>>;
+
+/// Sends commands to the connection thread. Once dropped, i.e. once all clients and
+/// subscriptions sharing the connection are gone, the connection thread is shut down.
+#[derive(Debug)]
+pub(crate) struct CommandSender(Sender<Command>);
+
+impl CommandSender {
+	pub(crate) fn new(commands: Sender<Command>) -> Self {
+		Self(commands)
+	}
+
+	pub(crate) fn send(&self, command: Command) -> Result<()> {
+		// The connection thread only stops once all senders are dropped, so this can only
+		// fail if the thread panicked.
+		self.0.send(command).map_err(|_| RpcClientError::ConnectionClosed)
+	}
+}
+
+impl Drop for CommandSender {
+	fn drop(&mut self) {
+		let _ = self.0.send(Command::Shutdown);
+	}
+}
+
+/// Waits up to `timeout` for the response the connection thread returns via `receiver`.
+pub(crate) fn receive_response<T>(receiver: &Receiver<Result<T>>, timeout: Duration) -> Result<T> {
+	match receiver.recv_timeout(timeout) {
+		Ok(result) => result,
+		Err(RecvTimeoutError::Timeout) => Err(RpcClientError::RequestTimeout),
+		// The connection thread only stops once all clients have been dropped.
+		Err(RecvTimeoutError::Disconnected) => Err(RpcClientError::ConnectionClosed),
+	}
+}
+
+/// Commands sent to the connection thread, either by the clients or by the reader thread.
+#[derive(Debug)]
+pub(crate) enum Command {
+	/// Send a request and return its response.
+	Request { request: Value, response: Sender<Result<Value>> },
+	/// Send several requests within one batch and return their results in order.
+	Batch { requests: Vec<Value>, response: Sender<Result<Vec<Result<Value>>>> },
+	/// Send a subscription request. The local key of the subscription is returned via
+	/// `confirmation` once the node has confirmed it, the notifications are forwarded to
+	/// `notifications`.
+	Subscribe {
+		request: Value,
+		unsubscribe_method: String,
+		confirmation: Sender<Result<u64>>,
+		notifications: Sender<String>,
+	},
+	/// End the subscription with the given local key and return the response of the node.
+	Unsubscribe { key: u64, response: Sender<Result<Value>> },
+	/// A text message has been received on the connection with the given id.
+	Received { connection: u64, message: String },
+	/// The connection with the given id has been closed or has failed.
+	Disconnected { connection: u64 },
+	/// All clients and subscriptions have been dropped.
+	Shutdown,
+}
+
+/// The current websocket connection. The socket is shared with the reader thread.
+struct Connection {
+	id: u64,
+	socket: Arc<Mutex<MySocket>>,
+	// Handle of the underlying tcp stream, to wait for incoming data and to shut it down.
+	stream: TcpStream,
+}
+
+impl Connection {
+	fn send(&self, message: Value) -> Result<()> {
+		let mut socket = self.socket.lock().map_err(|_| RpcClientError::ConnectionClosed)?;
+		socket.send(Message::Text(message.to_string().into())).map_err(|e| e.into())
+	}
+}
+
+impl Drop for Connection {
+	/// Closes the websocket and shuts the stream down, which ends the reader thread.
+	fn drop(&mut self) {
+		if let Ok(mut socket) = self.socket.lock() {
+			let _ = socket.close(None);
+			let _ = socket.flush();
+		}
+		let _ = self.stream.shutdown(Shutdown::Both);
+	}
+}
+
+/// Request that has been sent to the node, but has not been answered yet.
+enum PendingRequest {
+	Request(Sender<Result<Value>>),
+	Batch { len: usize, response: Sender<Result<Vec<Result<Value>>>> },
+	Subscribe(SubscriptionRequest),
+}
+
+/// Everything needed to (re-)establish a subscription.
+struct SubscriptionRequest {
+	key: u64,
+	request: Value,
+	unsubscribe_method: String,
+	// `None` in case of a resubscription, because nobody is waiting for it.
+	confirmation: Option<Sender<Result<u64>>>,
+	notifications: Sender<String>,
+}
+
+struct ActiveSubscription {
+	key: u64,
+	request: Value,
+	unsubscribe_method: String,
+	notifications: Sender<String>,
+}
+
+impl From<SubscriptionRequest> for ActiveSubscription {
+	fn from(subscription: SubscriptionRequest) -> Self {
+		let SubscriptionRequest { key, request, unsubscribe_method, notifications, .. } =
+			subscription;
+		Self { key, request, unsubscribe_method, notifications }
+	}
+}
+
+pub(crate) struct ConnectionWorker {
+	url: Url,
+	backoff: BackoffPolicy,
+	commands: Receiver<Command>,
+	// Handed to the reader thread of each connection.
+	command_sender: Sender<Command>,
+	queued_commands: VecDeque<Command>,
+	next_connection_id: u64,
+	next_id: u64,
+	pending_requests: HashMap<u64, PendingRequest>,
+	subscriptions: HashMap<String, ActiveSubscription>,
+	// Shared with the subscriptions. Kept in sync with `subscriptions`.
+	subscription_ids: SubscriptionIds,
+	// Subscriptions of a closed connection, which need to be renewed upon reconnection.
+	lost_subscriptions: Vec<ActiveSubscription>,
+}
+
+impl ConnectionWorker {
+	pub(crate) fn new(
+		url: Url,
+		backoff: BackoffPolicy,
+		commands: Receiver<Command>,
+		command_sender: Sender<Command>,
+		subscription_ids: SubscriptionIds,
+	) -> Self {
+		Self {
+			url,
+			backoff,
+			commands,
+			command_sender,
+			queued_commands: Default::default(),
+			next_connection_id: 0,
+			next_id: 0,
+			pending_requests: Default::default(),
+			subscriptions: Default::default(),
+			subscription_ids,
+			lost_subscriptions: Default::default(),
+		}
+	}
+
+	/// Runs until all clients sharing this connection have been dropped.
+	pub(crate) fn run(mut self) {
+		let mut maybe_connection: Option<Connection> = None;
+		loop {
+			if !self.receive_commands() {
+				// All clients have been dropped. Dropping the connection closes it.
+				return
+			}
+
+			if maybe_connection.is_none() {
+				self.end_lost_subscriptions();
+				// Connect lazily: Without any subscriptions to renew, there is no
+				// need to connect before the next command arrives.
+				if self.queued_commands.is_empty() && self.lost_subscriptions.is_empty() {
+					match self.next_command() {
+						Some(command) => self.queue_command(command),
+						None => return,
+					}
+					continue
+				}
+				match self.connect() {
+					Ok(connection) => maybe_connection = Some(connection),
+					Err(e) => {
+						self.abort_all(e);
+						continue
+					},
+				}
+			}
+			let Some(connection) = maybe_connection.as_ref() else { continue };
+
+			if let Err(e) =
+				self.resubscribe(connection).and_then(|_| self.send_commands(connection))
+			{
+				warn!("Could not send request to the node: {e:?}");
+				self.handle_disconnect();
+				maybe_connection = None;
+				continue
+			}
+
+			// Sleep until a client sends a command or the reader thread receives a message.
+			match self.next_command() {
+				Some(Command::Received { connection: id, message }) if id == connection.id =>
+					self.handle_message(connection, &message),
+				Some(Command::Disconnected { connection: id }) if id == connection.id => {
+					self.handle_disconnect();
+					maybe_connection = None;
+				},
+				Some(command) => self.queue_command(command),
+				None => return,
+			}
+		}
+	}
+
+	/// Blocks until the next command arrives. Returns `None` if all clients have been dropped.
+	fn next_command(&self) -> Option<Command> {
+		match self.commands.recv() {
+			Ok(Command::Shutdown) | Err(_) => None,
+			Ok(command) => Some(command),
+		}
+	}
+
+	/// Queues commands of the clients. Messages and disconnections of previous connections
+	/// are outdated and therefore dropped.
+	fn queue_command(&mut self, command: Command) {
+		match command {
+			Command::Received { connection, .. } | Command::Disconnected { connection } =>
+				trace!("Skipping event of previous connection {connection}"),
+			command => self.queued_commands.push_back(command),
+		}
+	}
+
+	/// Moves all newly arrived commands to the queue. Returns false if all clients have been dropped.
+	fn receive_commands(&mut self) -> bool {
+		loop {
+			match self.commands.try_recv() {
+				Ok(Command::Shutdown) | Err(TryRecvError::Disconnected) => return false,
+				Ok(command) => self.queue_command(command),
+				Err(TryRecvError::Empty) => return true,
+			}
+		}
+	}
+
+	/// Connects to the node and starts the reader thread of the new connection.
+	fn connect(&mut self) -> Result<Connection> {
+		let socket = attempt_connection_until(&self.url, &self.backoff)?;
+		let stream = match socket.get_ref() {
+			MaybeTlsStream::Plain(stream) => stream.try_clone()?,
+			MaybeTlsStream::NativeTls(stream) => stream.get_ref().try_clone()?,
+			_ => return Err(RpcClientError::Io("Unknown stream type".into())),
+		};
+		let id = self.next_connection_id;
+		self.next_connection_id += 1;
+		let connection = Connection { id, socket: Arc::new(Mutex::new(socket)), stream };
+
+		let reader = Reader {
+			connection: id,
+			socket: connection.socket.clone(),
+			stream: connection.stream.try_clone()?,
+			worker: self.command_sender.clone(),
+		};
+		thread::Builder::new()
+			.name("tungstenite-rpc-reader".into())
+			.spawn(move || reader.run())?;
+		Ok(connection)
+	}
+
+	fn send_commands(&mut self, connection: &Connection) -> Result<()> {
+		// Unsubscriptions of subscriptions that are currently being renewed.
+		let mut deferred_commands = Vec::new();
+		while let Some(command) = self.queued_commands.pop_front() {
+			let (message, pending_request) = match command {
+				Command::Request { mut request, response } => {
+					request["id"] = self.next_id.into();
+					(request, PendingRequest::Request(response))
+				},
+				Command::Batch { requests, response } => {
+					let len = requests.len();
+					let batch = self.assign_batch_ids(requests);
+					(batch, PendingRequest::Batch { len, response })
+				},
+				Command::Subscribe { request, unsubscribe_method, confirmation, notifications } => {
+					let mut message = request.clone();
+					message["id"] = self.next_id.into();
+					// The id of the initial request is unique, so it serves as local key.
+					let subscription = SubscriptionRequest {
+						key: self.next_id,
+						request,
+						unsubscribe_method,
+						confirmation: Some(confirmation),
+						notifications,
+					};
+					(message, PendingRequest::Subscribe(subscription))
+				},
+				Command::Unsubscribe { key, response } => {
+					if let Some((subscription_id, subscription)) = self.remove_subscription(key) {
+						self.send_unsubscribe(connection, subscription_id, subscription, response)?;
+					} else if self.is_renewing(key) {
+						deferred_commands.push(Command::Unsubscribe { key, response });
+					} else {
+						// The subscription has already ended.
+						let _ = response.send(Ok(unsubscribed()));
+					}
+					continue
+				},
+				// Only client commands are queued.
+				Command::Received { .. } | Command::Disconnected { .. } | Command::Shutdown =>
+					continue,
+			};
+			self.send(connection, message, pending_request)?;
+		}
+		self.queued_commands.extend(deferred_commands);
+		Ok(())
+	}
+
+	fn send_unsubscribe(
+		&mut self,
+		connection: &Connection,
+		subscription_id: String,
+		subscription: ActiveSubscription,
+		response: Sender<Result<Value>>,
+	) -> Result<()> {
+		debug!("Unsubscribing from {subscription_id}");
+		let message = json!({
+			"method": subscription.unsubscribe_method,
+			"params": [subscription_id],
+			"jsonrpc": "2.0",
+			"id": self.next_id,
+		});
+		self.send(connection, message, PendingRequest::Request(response))
+	}
+
+	fn remove_subscription(&mut self, key: u64) -> Option<(String, ActiveSubscription)> {
+		let subscription_id = self
+			.subscriptions
+			.iter()
+			.find(|(_, subscription)| subscription.key == key)
+			.map(|(subscription_id, _)| subscription_id.clone())?;
+		self.remove_subscription_by_id(&subscription_id)
+	}
+
+	fn remove_subscription_by_id(
+		&mut self,
+		subscription_id: &str,
+	) -> Option<(String, ActiveSubscription)> {
+		let (subscription_id, subscription) = self.subscriptions.remove_entry(subscription_id)?;
+		if let Ok(mut subscription_ids) = self.subscription_ids.lock() {
+			subscription_ids.remove(&subscription.key);
+		}
+		Some((subscription_id, subscription))
+	}
+
+	fn is_renewing(&self, key: u64) -> bool {
+		self.pending_requests.values().any(|pending_request| {
+			matches!(pending_request, PendingRequest::Subscribe(subscription) if subscription.key == key)
+		})
+	}
+
+	/// Drops all lost subscriptions that have been unsubscribed in the meantime,
+	/// so they are not renewed upon the next connection.
+	fn end_lost_subscriptions(&mut self) {
+		let (unsubscriptions, commands): (Vec<_>, Vec<_>) = self
+			.queued_commands
+			.drain(..)
+			.partition(|command| matches!(command, Command::Unsubscribe { .. }));
+		self.queued_commands = commands.into();
+		for command in unsubscriptions {
+			let Command::Unsubscribe { key, response } = command else { continue };
+			match self.lost_subscriptions.iter().position(|subscription| subscription.key == key) {
+				Some(index) => {
+					self.lost_subscriptions.swap_remove(index);
+					let _ = response.send(Ok(unsubscribed()));
+				},
+				None => self.queued_commands.push_back(Command::Unsubscribe { key, response }),
+			}
+		}
+	}
+
+	/// Renews all subscriptions of a previous connection.
+	fn resubscribe(&mut self, connection: &Connection) -> Result<()> {
+		while let Some(subscription) = self.lost_subscriptions.pop() {
+			debug!("Renewing subscription {:?}", subscription.request["method"]);
+			let mut message = subscription.request.clone();
+			message["id"] = self.next_id.into();
+			let ActiveSubscription { key, request, unsubscribe_method, notifications } =
+				subscription;
+			let subscription = SubscriptionRequest {
+				key,
+				request,
+				unsubscribe_method,
+				confirmation: None,
+				notifications,
+			};
+			self.send(connection, message, PendingRequest::Subscribe(subscription))?;
+		}
+		Ok(())
+	}
+
+	/// Assigns consecutive ids to the batch requests, starting with `next_id`.
+	fn assign_batch_ids(&self, requests: Vec<Value>) -> Value {
+		let batch = requests
+			.into_iter()
+			.zip(self.next_id..)
+			.map(|(mut request, id)| {
+				request["id"] = id.into();
+				request
+			})
+			.collect();
+		Value::Array(batch)
+	}
+
+	fn send(
+		&mut self,
+		connection: &Connection,
+		message: Value,
+		pending_request: PendingRequest,
+	) -> Result<()> {
+		let id = self.next_id;
+		self.next_id += match &pending_request {
+			PendingRequest::Batch { len, .. } => *len as u64,
+			_ => 1,
+		};
+		// Register before sending, so a failing send is handled like any other lost request.
+		self.pending_requests.insert(id, pending_request);
+		connection.send(message)
+	}
+
+	fn handle_message(&mut self, connection: &Connection, msg: &str) {
+		trace!("Got message {msg}");
+		let value: Value = match serde_json::from_str(msg) {
+			Ok(value) => value,
+			Err(e) => {
+				warn!("Could not parse message {msg}: {e:?}");
+				return
+			},
+		};
+		match helpers::route_message(value) {
+			IncomingMessage::Response { id, value } => self.handle_response(id, value),
+			IncomingMessage::BatchResponse { first_id, responses } =>
+				self.handle_batch_response(first_id, responses),
+			IncomingMessage::Notification { subscription_id, result } =>
+				self.handle_notification(connection, &subscription_id, &result),
+			IncomingMessage::ErrorWithoutId(value) => self.fail_pending_requests(&value),
+			IncomingMessage::Unexpected(_) => warn!("Received unexpected message: {msg}"),
+		}
+	}
+
+	fn handle_response(&mut self, id: u64, value: Value) {
+		match self.pending_requests.remove(&id) {
+			Some(PendingRequest::Request(response)) => {
+				let _ = response.send(Ok(value));
+			},
+			Some(PendingRequest::Subscribe(subscription)) =>
+				self.confirm_subscription(subscription, value),
+			Some(PendingRequest::Batch { response, .. }) => {
+				let _ = response.send(Err(RpcClientError::UnexpectedResponse(value.to_string())));
+			},
+			None => warn!("Received response for unknown request id {id}"),
+		}
+	}
+
+	fn handle_batch_response(&mut self, first_id: u64, responses: Value) {
+		let Some(PendingRequest::Batch { len, response }) = self.pending_requests.remove(&first_id)
+		else {
+			warn!("Received batch response for unknown request id {first_id}");
+			return
+		};
+		let _ = response.send(helpers::read_batch_results(responses, len));
+	}
+
+	/// Fails all pending requests with the error `value`, because it is not known which of
+	/// them it belongs to. Subscriptions that are being renewed are lost.
+	fn fail_pending_requests(&mut self, value: &Value) {
+		warn!("Received error without id, failing all pending requests: {value}");
+		for (_, pending_request) in self.pending_requests.drain() {
+			match pending_request {
+				PendingRequest::Request(response) => {
+					let _ = response.send(Ok(value.clone()));
+				},
+				PendingRequest::Batch { response, .. } => {
+					let _ = response.send(Err(helpers::response_error(value)));
+				},
+				PendingRequest::Subscribe(SubscriptionRequest {
+					confirmation: Some(confirmation),
+					..
+				}) => {
+					let _ = confirmation.send(Err(helpers::response_error(value)));
+				},
+				PendingRequest::Subscribe(SubscriptionRequest { request, .. }) =>
+					warn!("Could not renew subscription {:?}", request["method"]),
+			}
+		}
+	}
+
+	fn handle_notification(
+		&mut self,
+		connection: &Connection,
+		subscription_id: &str,
+		result: &Value,
+	) {
+		let Some(subscription) = self.subscriptions.get(subscription_id) else {
+			debug!("Received notification for unknown subscription {subscription_id}");
+			return
+		};
+		let notification = result.to_string();
+		if subscription.notifications.send(notification).is_err() {
+			// The receiver has been dropped, so nobody is interested in this subscription anymore.
+			let Some((subscription_id, subscription)) =
+				self.remove_subscription_by_id(subscription_id)
+			else {
+				return
+			};
+			// Nobody waits for the response.
+			let (response, _) = channel();
+			if let Err(e) =
+				self.send_unsubscribe(connection, subscription_id, subscription, response)
+			{
+				// The failing connection is detected by the reader thread.
+				warn!("Could not unsubscribe: {e:?}");
+			}
+		}
+	}
+
+	fn confirm_subscription(&mut self, subscription: SubscriptionRequest, value: Value) {
+		match helpers::read_subscription_id(&value) {
+			Some(subscription_id) => {
+				// Registered before confirming, so the id is known to the subscription right away.
+				if let Ok(mut subscription_ids) = self.subscription_ids.lock() {
+					subscription_ids.insert(subscription.key, subscription_id.clone());
+				}
+				if let Some(confirmation) = &subscription.confirmation {
+					let _ = confirmation.send(Ok(subscription.key));
+				}
+				self.subscriptions.insert(subscription_id, subscription.into());
+			},
+			None => {
+				let error = helpers::response_error(&value);
+				match subscription.confirmation {
+					Some(confirmation) => {
+						let _ = confirmation.send(Err(error));
+					},
+					None => warn!("Could not renew subscription: {error:?}"),
+				}
+			},
+		}
+	}
+
+	/// Fails all pending requests. Active subscriptions are kept to be renewed
+	/// upon the next connection.
+	fn handle_disconnect(&mut self) {
+		for (_, pending_request) in self.pending_requests.drain() {
+			match pending_request {
+				PendingRequest::Request(response) => {
+					let _ = response.send(Err(RpcClientError::ConnectionClosed));
+				},
+				PendingRequest::Batch { response, .. } => {
+					let _ = response.send(Err(RpcClientError::ConnectionClosed));
+				},
+				PendingRequest::Subscribe(SubscriptionRequest {
+					request,
+					confirmation: Some(confirmation),
+					..
+				}) => {
+					debug!("Subscription {:?} failed due to closed connection", request["method"]);
+					let _ = confirmation.send(Err(RpcClientError::ConnectionClosed));
+				},
+				PendingRequest::Subscribe(subscription) =>
+					self.lost_subscriptions.push(subscription.into()),
+			}
+		}
+		self.lost_subscriptions.extend(self.subscriptions.drain().map(|(_, s)| s));
+		// The ids of the lost subscriptions are invalid now.
+		if let Ok(mut subscription_ids) = self.subscription_ids.lock() {
+			subscription_ids.clear();
+		}
+	}
+
+	/// Fails all queued commands and ends all subscriptions, because no connection
+	/// could be established.
+	fn abort_all(&mut self, error: RpcClientError) {
+		warn!("Could not connect to {}: {error:?}", self.url);
+		// Dropping the notification senders ends the subscriptions on the receiving side.
+		self.lost_subscriptions.clear();
+		while let Some(command) = self.queued_commands.pop_front() {
+			let error = RpcClientError::MaxConnectionAttemptsExceeded;
+			match command {
+				Command::Request { response, .. } => {
+					let _ = response.send(Err(error));
+				},
+				Command::Batch { response, .. } => {
+					let _ = response.send(Err(error));
+				},
+				Command::Subscribe { confirmation, .. } => {
+					let _ = confirmation.send(Err(error));
+				},
+				// All subscriptions have just been ended.
+				Command::Unsubscribe { response, .. } => {
+					let _ = response.send(Ok(unsubscribed()));
+				},
+				// Only client commands are queued.
+				Command::Received { .. } | Command::Disconnected { .. } | Command::Shutdown => {},
+			}
+		}
+	}
+}
+
+/// Response of a successful unsubscription, used if the subscription has already
+/// ended without any involvement of the node.
+fn unsubscribed() -> Value {
+	json!({ "result": true })
+}
+
+/// Connects to the node. Retries according to the `backoff` policy in case of failure.
+fn attempt_connection_until(url: &Url, backoff: &BackoffPolicy) -> Result<MySocket> {
+	let mut failed_attempts: u8 = 0;
+	loop {
+		match connect_with_config(url.clone(), None, u8::MAX - 1) {
+			Ok((socket, response)) => {
+				debug!("Connected to the server. Response HTTP code: {}", response.status());
+				return Ok(socket)
+			},
+			Err(e) => warn!("Connection attempt failed due to {e:?}"),
+		};
+		failed_attempts = failed_attempts.saturating_add(1);
+		let delay = backoff
+			.delay(failed_attempts)
+			.ok_or(RpcClientError::MaxConnectionAttemptsExceeded)?;
+		trace!("Trying to reconnect in {delay:?}. Failed attempts: {failed_attempts}");
+		sleep(delay);
+	}
+}
+
+/// Reads the incoming messages of a connection and forwards them to the connection thread.
+struct Reader {
+	connection: u64,
+	socket: Arc<Mutex<MySocket>>,
+	stream: TcpStream,
+	worker: Sender<Command>,
+}
+
+impl Reader {
+	/// Runs until the connection is closed.
+	fn run(self) {
+		loop {
+			// Wait for data without locking the socket, so requests can be sent meanwhile.
+			match self.stream.peek(&mut [0u8]) {
+				Ok(0) => break,
+				Ok(_) => {},
+				Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+				Err(e) => {
+					debug!("Waiting for data failed: {e:?}");
+					break
+				},
+			}
+			let Ok(mut socket) = self.socket.lock() else { break };
+			match self.read_available_messages(&mut socket) {
+				Ok(true) => {},
+				Ok(false) => break,
+				Err(e) => {
+					warn!("Connection to the node failed: {e:?}");
+					break
+				},
+			}
+		}
+		// Fails if the connection thread has already stopped, which is fine.
+		let _ = self.worker.send(Command::Disconnected { connection: self.connection });
+	}
+
+	/// Forwards all messages that can be read without blocking. This includes messages that
+	/// have already been buffered by the tls or the websocket layer.
+	/// Returns false if the connection has been closed.
+	fn read_available_messages(&self, socket: &mut MySocket) -> io::Result<bool> {
+		self.stream.set_nonblocking(true)?;
+		let is_open = loop {
+			match socket.read() {
+				Ok(Message::Text(message)) => {
+					let command = Command::Received {
+						connection: self.connection,
+						message: message.as_str().into(),
+					};
+					if self.worker.send(command).is_err() {
+						break false
+					}
+				},
+				Ok(Message::Close(_)) => {
+					debug!("Connection closed by the node");
+					break false
+				},
+				Ok(_) => trace!("skip non text msg"),
+				Err(tungstenite::Error::Io(e)) if e.kind() == ErrorKind::WouldBlock => break true,
+				Err(e) => {
+					warn!("Connection to the node failed: {e:?}");
+					break false
+				},
+			}
+		};
+		self.stream.set_nonblocking(false)?;
+		Ok(is_open)
+	}
+}
@@ -19,4 +19,5 @@ pub use client::TungsteniteRpcClient;
 pub use subscription::TungsteniteSubscriptionWrapper;
 
 pub mod client;
+mod connection;
 pub mod subscription;
@@ -15,25 +15,60 @@
 
 */
 
-use crate::rpc::{Error, HandleSubscription, Result};
+use crate::rpc::{
+	Error, HandleSubscription, Result,
+	helpers::read_result,
+	tungstenite_client::connection::{Command, CommandSender, SubscriptionIds, receive_response},
+};
+use ac_primitives::MaybeSend;
 use core::marker::PhantomData;
 use serde::de::DeserializeOwned;
-use std::sync::mpsc::Receiver;
+use std::{
+	sync::{
+		Arc,
+		mpsc::{Receiver, channel},
+	},
+	time::Duration,
+};
 
+/// Subscription of the [`TungsteniteRpcClient`](super::TungsteniteRpcClient).
+///
+/// The subscription keeps the shared connection alive, even if all clients have been dropped.
+/// The connection is closed once the last client and subscription are gone.
 #[derive(Debug)]
 pub struct TungsteniteSubscriptionWrapper<Notification> {
 	receiver: Receiver<String>,
+	// Local key of the subscription, which stays the same across reconnections.
+	key: u64,
+	// Ids assigned by the node, which are updated by the connection whenever it renews a
+	// subscription.
+	subscription_ids: SubscriptionIds,
+	commands: Arc<CommandSender>,
+	request_timeout: Duration,
 	_phantom: PhantomData<Notification>,
 }
 
 impl<Notification> TungsteniteSubscriptionWrapper<Notification> {
-	pub fn new(receiver: Receiver<String>) -> Self {
-		Self { receiver, _phantom: Default::default() }
+	pub(crate) fn new(
+		receiver: Receiver<String>,
+		key: u64,
+		subscription_ids: SubscriptionIds,
+		commands: Arc<CommandSender>,
+		request_timeout: Duration,
+	) -> Self {
+		Self {
+			receiver,
+			key,
+			subscription_ids,
+			commands,
+			request_timeout,
+			_phantom: Default::default(),
+		}
 	}
 }
 
-#[maybe_async::maybe_async(?Send)]
-impl<Notification: DeserializeOwned> HandleSubscription<Notification>
+#[ac_async_macros::maybe_async]
+impl<Notification: DeserializeOwned + MaybeSend> HandleSubscription<Notification>
 	for TungsteniteSubscriptionWrapper<Notification>
 {
 	async fn next(&mut self) -> Option<Result<Notification>> {
@@ -42,14 +77,27 @@ impl<Notification: DeserializeOwned> HandleSubscription<Notification>
 			// Sender was disconnected, therefore no further messages are to be expected.
 			Err(_e) => return None,
 		};
-		Some(serde_json::from_str(&notification).map_err(|_| Error::ExtrinsicFailed(notification)))
+		Some(serde_json::from_str(&notification).map_err(|e| e.into()))
 	}
 
 	async fn unsubscribe(self) -> Result<()> {
-		// TODO: Nicer unsubscription.
-		// We close ungracefully: Simply drop the receiver. This will turn
-		// into an error on the sender side, terminating the websocket polling
-		// loop.
+		// The connection stops forwarding notifications as soon as it has received the command,
+		// so only the response of the node is left to wait for.
+		let (response, receiver) = channel();
+		self.commands.send(Command::Unsubscribe { key: self.key, response })?;
+		let unsubscribed: bool = read_result(receive_response(&receiver, self.request_timeout)?)?;
+		if !unsubscribed {
+			return Err(Error::UnexpectedResponse(format!(
+				"Node did not end subscription with key {}",
+				self.key
+			)))
+		}
 		Ok(())
 	}
+
+	/// Returns the id currently assigned by the node. It changes if the subscription is renewed
+	/// after a reconnection and is `None` while the subscription is being renewed or has ended.
+	fn subscription_id(&self) -> Option<String> {
+		self.subscription_ids.lock().ok()?.get(&self.key).cloned()
+	}
 }
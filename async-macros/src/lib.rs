@@ -0,0 +1,54 @@
+/*
+   Copyright 2019 Supercomputing Systems AG
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+	   http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+
+*/
+
+//! Attribute macros wrapping the ones of `maybe-async` for async traits and their impls.
+//!
+//! The futures are `Send` if the `send-futures` feature of the crate using the macros is
+//! enabled, and `?Send` otherwise. The crate needs to depend on `maybe-async` itself.
+
+use proc_macro::TokenStream;
+
+/// `#[maybe_async::maybe_async]` for traits and trait impls, with `Send` futures only if the
+/// `send-futures` feature is enabled.
+#[proc_macro_attribute]
+pub fn maybe_async(args: TokenStream, item: TokenStream) -> TokenStream {
+	with_send_futures_attributes("maybe_async", args, item)
+}
+
+/// `#[maybe_async::async_impl]` for trait impls that are only available in async mode, with
+/// `Send` futures only if the `send-futures` feature is enabled.
+#[proc_macro_attribute]
+pub fn async_impl(args: TokenStream, item: TokenStream) -> TokenStream {
+	with_send_futures_attributes("async_impl", args, item)
+}
+
+fn with_send_futures_attributes(
+	macro_name: &str,
+	args: TokenStream,
+	item: TokenStream,
+) -> TokenStream {
+	let attributes = if args.is_empty() {
+		format!(
+			"#[cfg_attr(not(feature = \"send-futures\"), maybe_async::{macro_name}(?Send))] \
+			 #[cfg_attr(feature = \"send-futures\", maybe_async::{macro_name})]"
+		)
+	} else {
+		format!("compile_error!(\"`{macro_name}` does not take any arguments\");")
+	};
+	let attributes: TokenStream = attributes.parse().expect("Attributes are valid tokens");
+	attributes.into_iter().chain(item).collect()
+}
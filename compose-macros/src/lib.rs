@@ -119,7 +119,7 @@ macro_rules! compose_extrinsic_with_nonce {
 
             debug!("Composing generic extrinsic for module {:?} and call {:?}", $pallet_name, $call_name);
 
-			let metadata = $api.metadata();
+			let metadata = $api.metadata_snapshot();
             let maybe_call = $crate::compose_call!(metadata, $pallet_name, $call_name $(, ($args)) *);
 
 			let maybe_extrinsic = match maybe_call {
@@ -49,13 +49,12 @@ async fn main() {
 	api.set_signer(signer.into());
 
 	let recipient: ExtrinsicAddressOf<ExtrinsicSigner> = Sr25519Keyring::Bob.to_account_id().into();
-	// We use a manual nonce input here, because otherwise the api retrieves the nonce via getter and needs
-	// to wait for the response of the node (and the actual execution of the previous extrinsic).
-	// But because we want to spam the node with extrinsic, we simple monotonically increase the nonce, without
-	// waiting for the response of the node.
-	let mut nonce = api.get_nonce().await.unwrap();
-	let first_nonce = nonce;
-	while nonce < first_nonce + 500 {
+	// We use the nonces managed by the api here, because otherwise the api retrieves the nonce via getter and
+	// needs to wait for the response of the node (and the actual execution of the previous extrinsic).
+	// But because we want to spam the node with extrinsic, the nonce manager only asks the node once and then
+	// simply monotonically increases the nonce, without waiting for the response of the node.
+	for _ in 0..500 {
+		let nonce = api.get_managed_nonce().await.unwrap();
 		// Compose a balance extrinsic.
 		let call = RuntimeCall::Balances(BalancesCall::transfer_allow_death {
 			dest: recipient.clone(),
@@ -64,8 +63,12 @@ async fn main() {
 		let xt = api.compose_extrinsic_offline(call, nonce);
 
 		println!("Sending extrinsic with nonce {}", nonce);
-		let _tx_hash = api.submit_extrinsic(xt).await.unwrap();
-
-		nonce += 1;
+		if let Err(e) = api.submit_extrinsic(xt).await {
+			// Sync the nonce with the node again, if it has been rejected due to its nonce.
+			let signer_account = api.signer_account().unwrap();
+			if !api.resync_nonce_on_error(signer_account, &e).await.unwrap() {
+				panic!("Could not submit extrinsic: {e:?}");
+			}
+		}
 	}
 }
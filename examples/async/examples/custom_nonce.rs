@@ -18,9 +18,9 @@
 
 use rococo_runtime::{BalancesCall, RuntimeCall};
 use sp_keyring::Sr25519Keyring;
-use sp_runtime::{MultiAddress, generic::Era};
+use sp_runtime::MultiAddress;
 use substrate_api_client::{
-	Api, Error, GetChainInfo, SubmitAndWatch, UnexpectedTxStatus, XtStatus,
+	Api, Error, SubmitAndWatch, UnexpectedTxStatus, XtStatus,
 	ac_primitives::{GenericAdditionalParams, RococoRuntimeConfig},
 	rpc::JsonrpseeClient,
 };
@@ -38,13 +38,10 @@ async fn main() {
 	let mut api = Api::<RococoRuntimeConfig, _>::new(client).await.unwrap();
 	api.set_signer(signer.into());
 
-	// Information for Era for mortal transactions.
-	let last_finalized_header_hash = api.get_finalized_head().await.unwrap().unwrap();
-	let header = api.get_header(Some(last_finalized_header_hash)).await.unwrap().unwrap();
+	// Mortal era anchored to the last finalized block.
 	let period = 5;
-	let tx_params = GenericAdditionalParams::new()
-		.era(Era::mortal(period, header.number.into()), last_finalized_header_hash)
-		.tip(0);
+	let mortality = api.mortality(period).await.unwrap();
+	let tx_params = GenericAdditionalParams::new().mortality(&mortality).tip(0);
 
 	// Set the custom additional params.
 	api.set_additional_params(tx_params);
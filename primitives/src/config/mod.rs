@@ -20,7 +20,7 @@ use sp_runtime::traits::{
 	MaybeSerializeDeserialize,
 };
 
-use crate::{ExtrinsicSigner, SignExtrinsic, extrinsic_params};
+use crate::{ExtrinsicSigner, MaybeSend, MaybeSync, SignExtrinsic, extrinsic_params};
 
 pub use asset_runtime_config::*;
 pub use default_runtime_config::*;
@@ -31,12 +31,21 @@ pub mod default_runtime_config;
 pub mod rococo_runtime_config;
 
 /// Runtime types.
-pub trait Config {
+///
+/// With the `send-futures` feature, all types need to be `Send` and `Sync`.
+pub trait Config: MaybeSend + MaybeSync {
 	/// Account index (aka nonce) type. This stores the number of previous
 	/// transactions associated with a sender account.
 	/// This type enforces the (de)serialization implementation
 	/// also in no-std mode (unlike substrates MaybeSerializeDeserialize).
-	type Index: Default + Debug + Copy + DeserializeOwned + AtLeast32Bit + Decode;
+	type Index: Default
+		+ Debug
+		+ Copy
+		+ DeserializeOwned
+		+ AtLeast32Bit
+		+ Decode
+		+ MaybeSend
+		+ MaybeSync;
 
 	/// The block number type used by the runtime.
 	type BlockNumber: Debug
@@ -48,7 +57,9 @@ pub trait Config {
 		+ core::hash::Hash
 		+ core::str::FromStr
 		+ Into<u64>
-		+ AtLeast32BitUnsigned;
+		+ AtLeast32BitUnsigned
+		+ MaybeSend
+		+ MaybeSync;
 
 	/// The output of the `Hashing` function.
 	type Hash: Debug
@@ -68,13 +79,19 @@ pub trait Config {
 		+ Clone
 		+ Encode
 		+ MaybeSerializeDeserialize
-		+ From<<Self::CryptoKey as Pair>::Public>;
+		+ From<<Self::CryptoKey as Pair>::Public>
+		+ MaybeSend
+		+ MaybeSync;
 
 	/// The address type.
-	type Address: Debug + Clone + Encode + From<Self::AccountId>; //type Lookup: StaticLookup<Target = Self::AccountId>;
+	type Address: Debug + Clone + Encode + From<Self::AccountId> + MaybeSend + MaybeSync; //type Lookup: StaticLookup<Target = Self::AccountId>;
 
 	/// The signature type.
-	type Signature: Debug + Encode + From<<Self::CryptoKey as Pair>::Signature>;
+	type Signature: Debug
+		+ Encode
+		+ From<<Self::CryptoKey as Pair>::Signature>
+		+ MaybeSend
+		+ MaybeSync;
 
 	/// The hashing system (algorithm) being used in the runtime (e.g. Blake2).
 	type Hasher: Debug + HashTrait<Output = Self::Hash>;
@@ -83,19 +100,22 @@ pub trait Config {
 	type Header: Debug
 		+ HeaderTrait<Number = Self::BlockNumber, Hashing = Self::Hasher>
 		+ Send
+		+ MaybeSync
 		+ DeserializeOwned;
 
 	/// The account data.
-	type AccountData: Debug + Clone + FullCodec;
+	type AccountData: Debug + Clone + FullCodec + MaybeSend + MaybeSync;
 
 	/// This type defines the extrinsic extra and additional parameters.
-	type ExtrinsicParams: extrinsic_params::ExtrinsicParams<Self::Index, Self::Hash>;
+	type ExtrinsicParams: extrinsic_params::ExtrinsicParams<Self::Index, Self::Hash>
+		+ MaybeSend
+		+ MaybeSync;
 
 	/// The cryptographic PKI key pair type used to sign the extrinsic
 	type CryptoKey: Pair;
 
 	/// This extrinsic signer.
-	type ExtrinsicSigner: SignExtrinsic<Self::AccountId>;
+	type ExtrinsicSigner: SignExtrinsic<Self::AccountId> + MaybeSend + MaybeSync;
 
 	/// The block type.
 	type Block: Block + DeserializeOwned;
@@ -108,7 +128,9 @@ pub trait Config {
 		+ Default
 		+ Copy
 		+ Serialize
-		+ DeserializeOwned;
+		+ DeserializeOwned
+		+ MaybeSend
+		+ MaybeSync;
 
 	/// The currency type of the contract pallet.
 	type ContractCurrency: Debug
@@ -118,7 +140,9 @@ pub trait Config {
 		+ Default
 		+ Copy
 		+ Serialize
-		+ DeserializeOwned;
+		+ DeserializeOwned
+		+ MaybeSend
+		+ MaybeSync;
 
 	/// The balance type of the staking pallet.
 	type StakingBalance: Debug
@@ -128,7 +152,9 @@ pub trait Config {
 		+ Default
 		+ Copy
 		+ Serialize
-		+ DeserializeOwned;
+		+ DeserializeOwned
+		+ MaybeSend
+		+ MaybeSync;
 }
 
 /// Helper struct for fast Config creation with different Extrinsic Params than the original Config.
@@ -150,8 +176,10 @@ pub struct WithExtrinsicParams<T: Config, E: extrinsic_params::ExtrinsicParams<T
 	_marker: PhantomData<(T, E)>,
 }
 
-impl<T: Config, E: extrinsic_params::ExtrinsicParams<T::Index, T::Hash>> Config
-	for WithExtrinsicParams<T, E>
+impl<T, E> Config for WithExtrinsicParams<T, E>
+where
+	T: Config,
+	E: extrinsic_params::ExtrinsicParams<T::Index, T::Hash> + MaybeSend + MaybeSync,
 {
 	type Index = T::Index;
 	type BlockNumber = T::BlockNumber;
@@ -193,7 +221,7 @@ where
 impl<T, A> Config for WithAddress<T, A>
 where
 	T: Config,
-	A: Debug + Clone + Encode + From<T::AccountId>,
+	A: Debug + Clone + Encode + From<T::AccountId> + MaybeSend + MaybeSync,
 {
 	type Index = T::Index;
 	type BlockNumber = T::BlockNumber;
@@ -15,7 +15,7 @@
 
 */
 
-use crate::config::Config;
+use crate::{MaybeSend, MaybeSync, Mortality, config::Config};
 use codec::{Codec, Decode, DecodeWithMemTracking, Encode};
 use primitive_types::H256;
 use scale_info::{StaticTypeInfo, TypeInfo};
@@ -107,7 +107,7 @@ pub trait ExtrinsicParams<Index, Hash> {
 	/// These params represent optional / additional params which are most likely
 	/// subject to change. This way, the trait does not need to be adapted if one of
 	/// these params is updated.
-	type AdditionalParams: Default + Clone;
+	type AdditionalParams: Default + Clone + MaybeSend + MaybeSync;
 
 	/// Extra mirroring the `TxExtension` defined on the node side.
 	/// These parameters are sent along with the extrinsic and are taken into account
@@ -188,6 +188,15 @@ impl<Tip: Default, Hash> GenericAdditionalParams<Tip, Hash> {
 		self
 	}
 
+	/// Set the mortal era and checkpoint of the `mortality`, e.g. as created by
+	/// `Api::mortality` of the api client.
+	pub fn mortality(self, mortality: &Mortality<Hash>) -> Self
+	where
+		Hash: Copy,
+	{
+		self.era(mortality.era(), mortality.checkpoint())
+	}
+
 	/// Set the tip you'd like to give to the block author
 	/// for this extrinsic.
 	pub fn tip(mut self, tip: impl Into<Tip>) -> Self {
@@ -206,7 +215,7 @@ impl<T, Tip> ExtrinsicParams<T::Index, T::Hash> for GenericExtrinsicParams<T, Ti
 where
 	T: Config,
 	u128: From<Tip>,
-	Tip: Copy + Default + Encode,
+	Tip: Copy + Default + Encode + MaybeSend + MaybeSync,
 {
 	type AdditionalParams = GenericAdditionalParams<Tip, T::Hash>;
 	type TxExtension = GenericTxExtension<Tip, T::Index>;
@@ -30,6 +30,7 @@
 // );
 
 use crate::{
+	MaybeSend, MaybeSync,
 	config::Config,
 	extrinsic_params::{ExtrinsicParams, GenericAdditionalParams, GenericTxExtension},
 };
@@ -114,7 +115,7 @@ impl<T, Tip> ExtrinsicParams<T::Index, T::Hash> for ExtrinsicParamsWithoutHashCh
 where
 	T: Config,
 	u128: From<Tip>,
-	Tip: Copy + Default + Encode,
+	Tip: Copy + Default + Encode + MaybeSend + MaybeSync,
 {
 	type AdditionalParams = GenericAdditionalParams<Tip, T::Hash>;
 	type TxExtension = GenericTxExtension<Tip, T::Index>;
@@ -23,6 +23,7 @@ pub use extrinsic_params::{
 };
 #[allow(deprecated)]
 pub use extrinsic_v4::deprecated;
+pub use mortality::Mortality;
 pub use signer::{ExtrinsicSigner, SignExtrinsic};
 pub use sp_runtime::generic::{Preamble, UncheckedExtrinsic};
 
@@ -32,4 +33,5 @@ pub type CallIndex = [u8; 2];
 pub mod extrinsic_params;
 pub mod extrinsic_params_without_hash_check;
 mod extrinsic_v4;
+pub mod mortality;
 pub mod signer;
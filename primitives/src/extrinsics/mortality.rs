@@ -0,0 +1,93 @@
+/*
+   Copyright 2019 Supercomputing Systems AG
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+	   http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+
+*/
+
+//! Mortality of extrinsics, i.e. the range of blocks they can be included in.
+
+use codec::{Decode, Encode};
+use sp_runtime::generic::Era;
+
+/// Mortal [`Era`] of an extrinsic, together with the block it is anchored to.
+///
+/// An extrinsic with a mortal era can only be included in the blocks from its birth block
+/// up to, but not including, its death block. Afterwards, it is rejected by the node, so it
+/// can not be replayed. Keep the mortality of an extrinsic to tell whether it has expired.
+#[derive(Decode, Encode, Copy, Clone, Eq, PartialEq, Debug)]
+pub struct Mortality<Hash> {
+	era: Era,
+	birth_block_number: u64,
+	birth_block_hash: Hash,
+}
+
+impl<Hash: Copy> Mortality<Hash> {
+	/// Create the mortality of an extrinsic with the `era`, whose birth block is the one with
+	/// the given number and hash. The birth block is `era.birth(current)` of the block `current`
+	/// the era has been created for.
+	pub fn new(era: Era, birth_block_number: u64, birth_block_hash: Hash) -> Self {
+		Self { era, birth_block_number, birth_block_hash }
+	}
+
+	/// The era to be set in the additional params.
+	pub fn era(&self) -> Era {
+		self.era
+	}
+
+	/// The hash of the birth block, which is the mortality checkpoint to be set in the
+	/// additional params.
+	pub fn checkpoint(&self) -> Hash {
+		self.birth_block_hash
+	}
+
+	/// The first block the extrinsic can be included in.
+	pub fn birth_block(&self) -> u64 {
+		self.birth_block_number
+	}
+
+	/// The first block the extrinsic can't be included in anymore.
+	pub fn death_block(&self) -> u64 {
+		self.era.death(self.birth_block_number)
+	}
+
+	/// Returns true if the extrinsic can not be included in the block with the given number
+	/// anymore, nor in any later block.
+	pub fn is_expired_at(&self, block_number: u64) -> bool {
+		block_number >= self.death_block()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use primitive_types::H256;
+
+	#[test]
+	fn mortality_expires_after_period() {
+		let era = Era::mortal(64, 1000);
+		let mortality = Mortality::new(era, era.birth(1000), H256::repeat_byte(1));
+
+		assert_eq!(mortality.birth_block(), 1000);
+		assert_eq!(mortality.death_block(), 1064);
+		assert!(!mortality.is_expired_at(1063));
+		assert!(mortality.is_expired_at(1064));
+	}
+
+	#[test]
+	fn immortal_era_never_expires() {
+		let mortality = Mortality::new(Era::Immortal, 0, H256::zero());
+
+		assert!(!mortality.is_expired_at(u64::MAX - 1));
+	}
+}
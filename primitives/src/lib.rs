@@ -22,14 +22,18 @@ extern crate alloc;
 // Re-export everything.
 pub use config::*;
 pub use extrinsics::*;
+pub use maybe_send::*;
 pub use rpc_numbers::*;
 pub use rpc_params::*;
+pub use rpc_spec_v2::*;
 pub use types::*;
 
 pub mod config;
 pub mod extrinsics;
+pub mod maybe_send;
 pub mod rpc_numbers;
 pub mod rpc_params;
+pub mod rpc_spec_v2;
 pub mod types;
 
 // Re-export substrate types for easy import on user side.
@@ -0,0 +1,43 @@
+/*
+   Copyright 2019 Supercomputing Systems AG
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+	   http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+
+*/
+
+//! Marker traits requiring `Send` and `Sync` only if the `send-futures` feature is enabled,
+//! so the futures of the api can be sent between threads.
+
+/// Requires `Send` if the `send-futures` feature is enabled. Implemented by all types otherwise.
+#[cfg(feature = "send-futures")]
+pub trait MaybeSend: Send {}
+#[cfg(feature = "send-futures")]
+impl<T: Send + ?Sized> MaybeSend for T {}
+
+/// Requires `Send` if the `send-futures` feature is enabled. Implemented by all types otherwise.
+#[cfg(not(feature = "send-futures"))]
+pub trait MaybeSend {}
+#[cfg(not(feature = "send-futures"))]
+impl<T: ?Sized> MaybeSend for T {}
+
+/// Requires `Sync` if the `send-futures` feature is enabled. Implemented by all types otherwise.
+#[cfg(feature = "send-futures")]
+pub trait MaybeSync: Sync {}
+#[cfg(feature = "send-futures")]
+impl<T: Sync + ?Sized> MaybeSync for T {}
+
+/// Requires `Sync` if the `send-futures` feature is enabled. Implemented by all types otherwise.
+#[cfg(not(feature = "send-futures"))]
+pub trait MaybeSync {}
+#[cfg(not(feature = "send-futures"))]
+impl<T: ?Sized> MaybeSync for T {}
@@ -32,7 +32,7 @@ use alloc::{string::String, vec, vec::Vec};
 use serde::Serialize;
 use serde_json::{Result, Value};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct RpcParams(ParamsBuilder);
 
 impl RpcParams {
@@ -83,7 +83,7 @@ const PARAM_BYTES_CAPACITY: usize = 128;
 ///   1. Other serialization methods than `serde_json::to_writer` would internally
 ///      have an extra heap allocation for temporarily holding the value in memory.
 ///   2. `io::Write` is not implemented for `String` required for serialization.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub(crate) struct ParamsBuilder {
 	bytes: Vec<u8>,
 	start: char,
@@ -0,0 +1,170 @@
+/*
+   Copyright 2019 Supercomputing Systems AG
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+	   http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+
+*/
+
+//! Types of the `archive_v1` functions.
+// https://paritytech.github.io/json-rpc-interface-spec/api/archive.html
+
+use crate::rpc_spec_v2::{StorageQuery, StorageQueryType, StorageResultItem};
+use alloc::string::String;
+use codec::Decode;
+use serde::{Deserialize, Serialize};
+use sp_core::{Bytes, storage::StorageKey};
+
+/// A single item to query from the storage of an archived block.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PaginatedStorageQuery {
+	pub key: StorageKey,
+	#[serde(rename = "type")]
+	pub query_type: StorageQueryType,
+	/// Only return descendants with a key strictly greater than this one. Used to continue the
+	/// `DescendantsValues` and `DescendantsHashes` queries after the last returned key.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub pagination_start_key: Option<StorageKey>,
+}
+
+impl PaginatedStorageQuery {
+	pub fn new(key: StorageKey, query_type: StorageQueryType) -> Self {
+		Self { key, query_type, pagination_start_key: None }
+	}
+
+	/// Continue the query after the `start_key`.
+	pub fn with_pagination_start_key(mut self, start_key: StorageKey) -> Self {
+		self.pagination_start_key = Some(start_key);
+		self
+	}
+}
+
+impl From<StorageQuery> for PaginatedStorageQuery {
+	fn from(query: StorageQuery) -> Self {
+		Self::new(query.key, query.query_type)
+	}
+}
+
+/// Event of an `archive_v1_storage` subscription.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "event")]
+pub enum ArchiveStorageEvent {
+	/// A storage item has been fetched.
+	Storage(ArchiveStorageResult),
+	/// The query failed. No further events are reported.
+	StorageError(ArchiveStorageError),
+	/// All items have been fetched.
+	StorageDone,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchiveStorageResult {
+	#[serde(flatten)]
+	pub item: StorageResultItem,
+	/// Set if the item has been queried within a child trie.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub child_trie_key: Option<StorageKey>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchiveStorageError {
+	pub error: String,
+}
+
+/// Response of `archive_v1_call`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchiveCallResult {
+	pub success: bool,
+	/// The scale encoded output of the runtime call, set upon success.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub value: Option<Bytes>,
+	/// Set if the runtime call failed.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub error: Option<String>,
+}
+
+impl ArchiveCallResult {
+	/// Returns the output of a successful call, or the error message otherwise.
+	pub fn into_result(self) -> Result<Bytes, String> {
+		match (self.success, self.value) {
+			(true, Some(value)) => Ok(value),
+			(true, None) => Err("Successful call without value".into()),
+			(false, _) => Err(self.error.unwrap_or_default()),
+		}
+	}
+
+	/// Decode the output of a successful call. Returns `None` if the call failed.
+	pub fn decode_value<V: Decode>(&self) -> Option<Result<V, codec::Error>> {
+		let value = self.value.as_ref().filter(|_| self.success)?;
+		Some(V::decode(&mut value.0.as_slice()))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use serde_json::json;
+
+	#[test]
+	fn storage_events_deserialize() {
+		let storage = json!({ "event": "storage", "key": "0x01", "value": "0x2a000000" });
+		let error = json!({ "event": "storageError", "error": "Block not found" });
+
+		let ArchiveStorageEvent::Storage(storage) = serde_json::from_value(storage).unwrap() else {
+			panic!("Expected storage event")
+		};
+		let error: ArchiveStorageEvent = serde_json::from_value(error).unwrap();
+		let done: ArchiveStorageEvent =
+			serde_json::from_value(json!({ "event": "storageDone" })).unwrap();
+
+		assert_eq!(storage.item.key, StorageKey(vec![1]));
+		assert_eq!(storage.item.decode_value::<u32>().unwrap(), Some(42));
+		assert_eq!(storage.child_trie_key, None);
+		assert_eq!(
+			error,
+			ArchiveStorageEvent::StorageError(ArchiveStorageError {
+				error: "Block not found".into()
+			})
+		);
+		assert_eq!(done, ArchiveStorageEvent::StorageDone);
+	}
+
+	#[test]
+	fn paginated_query_serializes_start_key() {
+		let query =
+			PaginatedStorageQuery::new(StorageKey(vec![1]), StorageQueryType::DescendantsValues)
+				.with_pagination_start_key(StorageKey(vec![1, 2]));
+
+		let value = serde_json::to_value(query).unwrap();
+
+		assert_eq!(
+			value,
+			json!({ "key": "0x01", "type": "descendantsValues", "paginationStartKey": "0x0102" })
+		);
+	}
+
+	#[test]
+	fn call_result_returns_value_or_error() {
+		let success: ArchiveCallResult =
+			serde_json::from_value(json!({ "success": true, "value": "0x2a000000" })).unwrap();
+		let failure: ArchiveCallResult =
+			serde_json::from_value(json!({ "success": false, "error": "Wasm trap" })).unwrap();
+
+		assert_eq!(success.decode_value::<u32>().unwrap().unwrap(), 42);
+		assert!(failure.decode_value::<u32>().is_none());
+		assert_eq!(failure.into_result(), Err("Wasm trap".into()));
+	}
+}
@@ -0,0 +1,379 @@
+/*
+   Copyright 2019 Supercomputing Systems AG
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+	   http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+
+*/
+
+//! Types of the `chainHead_v1` functions.
+// https://paritytech.github.io/json-rpc-interface-spec/api/chainHead.html
+
+use alloc::{collections::BTreeMap, string::String, vec::Vec};
+use codec::Decode;
+use serde::{Deserialize, Serialize};
+use sp_core::{Bytes, storage::StorageKey};
+
+/// Event of a `chainHead_v1_follow` subscription.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "event")]
+pub enum FollowEvent<Hash> {
+	/// First event of the subscription, containing the current finalized blocks.
+	Initialized(Initialized<Hash>),
+	/// A new non-finalized block has been added.
+	NewBlock(NewBlock<Hash>),
+	/// The best block of the chain has changed.
+	BestBlockChanged(BestBlockChanged<Hash>),
+	/// Blocks have been finalized, and others have been pruned.
+	Finalized(Finalized<Hash>),
+	/// The body requested by `chainHead_v1_body` has been fetched.
+	OperationBodyDone(OperationBodyDone),
+	/// The runtime call requested by `chainHead_v1_call` has been executed.
+	OperationCallDone(OperationCallDone),
+	/// Items requested by `chainHead_v1_storage` have been fetched.
+	OperationStorageItems(OperationStorageItems),
+	/// The storage operation waits for `chainHead_v1_continue` before fetching more items.
+	OperationWaitingForContinue(OperationId),
+	/// All items requested by `chainHead_v1_storage` have been fetched.
+	OperationStorageDone(OperationId),
+	/// The node could not fetch the data of the operation. Retrying might succeed.
+	OperationInaccessible(OperationId),
+	/// The operation failed and will not succeed upon a retry.
+	OperationError(OperationError),
+	/// The subscription has been ended by the node. All pinned blocks are unpinned and the
+	/// subscription id is no longer valid.
+	Stop,
+}
+
+/// Runtime of a block, as reported if the subscription has been started `with_runtime`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum BlockRuntime {
+	Valid(ValidRuntime),
+	/// The runtime could not be read, e.g. because the code is invalid.
+	Invalid(InvalidRuntime),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ValidRuntime {
+	pub spec: RuntimeSpec,
+}
+
+/// Runtime version, as reported by the new json-rpc functions.
+///
+/// Unlike the `RuntimeVersion`, the apis are reported as map of the hex encoded api id
+/// to the version of the api.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RuntimeSpec {
+	pub spec_name: String,
+	pub impl_name: String,
+	pub spec_version: u32,
+	pub impl_version: u32,
+	pub transaction_version: Option<u32>,
+	pub apis: BTreeMap<String, u32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InvalidRuntime {
+	pub error: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Initialized<Hash> {
+	/// The current finalized block and some of its ancestors, ordered by increasing block number.
+	/// All of them are pinned.
+	pub finalized_block_hashes: Vec<Hash>,
+	/// Runtime of the last finalized block. Only set if the subscription has been started
+	/// `with_runtime`.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub finalized_block_runtime: Option<BlockRuntime>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NewBlock<Hash> {
+	/// The new block, which is pinned.
+	pub block_hash: Hash,
+	pub parent_block_hash: Hash,
+	/// Set if the runtime of the block differs from the one of its parent. Only reported if the
+	/// subscription has been started `with_runtime`.
+	#[serde(default)]
+	pub new_runtime: Option<BlockRuntime>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BestBlockChanged<Hash> {
+	pub best_block_hash: Hash,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Finalized<Hash> {
+	/// The newly finalized blocks, ordered by increasing block number.
+	pub finalized_block_hashes: Vec<Hash>,
+	/// Blocks which are not part of the finalized chain and will never be reported again.
+	/// They stay pinned until they are unpinned.
+	pub pruned_block_hashes: Vec<Hash>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OperationId {
+	pub operation_id: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OperationBodyDone {
+	pub operation_id: String,
+	/// The scale encoded extrinsics of the block.
+	pub value: Vec<Bytes>,
+}
+
+impl OperationBodyDone {
+	/// Decode the extrinsics of the block, e.g. into the `Extrinsic` type of the `Config::Block`.
+	pub fn decode_extrinsics<Extrinsic: Decode>(&self) -> Result<Vec<Extrinsic>, codec::Error> {
+		self.value
+			.iter()
+			.map(|extrinsic| Extrinsic::decode(&mut extrinsic.0.as_slice()))
+			.collect()
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OperationCallDone {
+	pub operation_id: String,
+	/// The scale encoded output of the runtime call.
+	pub output: Bytes,
+}
+
+impl OperationCallDone {
+	/// Decode the output of the runtime call.
+	pub fn decode_output<V: Decode>(&self) -> Result<V, codec::Error> {
+		V::decode(&mut self.output.0.as_slice())
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OperationStorageItems {
+	pub operation_id: String,
+	pub items: Vec<StorageResultItem>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OperationError {
+	pub operation_id: String,
+	pub error: String,
+}
+
+/// Response of the functions starting an operation, whose result is reported via the
+/// follow subscription.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "result")]
+pub enum MethodResponse {
+	/// The operation has been started.
+	Started(MethodResponseStarted),
+	/// The operation has not been started, because the node limits the number of operations
+	/// per subscription.
+	LimitReached,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MethodResponseStarted {
+	/// Identifies the operation within the events of the follow subscription.
+	pub operation_id: String,
+	/// Number of storage items at the end of the request, which will not be fetched.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub discarded_items: Option<usize>,
+}
+
+/// A single item to query from the storage.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StorageQuery {
+	/// The storage key, e.g. created with the `Metadata` of the runtime.
+	pub key: StorageKey,
+	#[serde(rename = "type")]
+	pub query_type: StorageQueryType,
+}
+
+impl StorageQuery {
+	pub fn new(key: StorageKey, query_type: StorageQueryType) -> Self {
+		Self { key, query_type }
+	}
+
+	/// Query the value stored at `key`.
+	pub fn value(key: StorageKey) -> Self {
+		Self::new(key, StorageQueryType::Value)
+	}
+
+	/// Query the hash of the value stored at `key`.
+	pub fn hash(key: StorageKey) -> Self {
+		Self::new(key, StorageQueryType::Hash)
+	}
+
+	/// Query the values of all keys starting with `key`.
+	pub fn descendants_values(key: StorageKey) -> Self {
+		Self::new(key, StorageQueryType::DescendantsValues)
+	}
+
+	/// Query the value hashes of all keys starting with `key`.
+	pub fn descendants_hashes(key: StorageKey) -> Self {
+		Self::new(key, StorageQueryType::DescendantsHashes)
+	}
+
+	/// Query the merkle value of the closest descendant of `key` within the trie, which changes
+	/// whenever any of the descendants change.
+	pub fn closest_descendant_merkle_value(key: StorageKey) -> Self {
+		Self::new(key, StorageQueryType::ClosestDescendantMerkleValue)
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum StorageQueryType {
+	Value,
+	Hash,
+	ClosestDescendantMerkleValue,
+	DescendantsValues,
+	DescendantsHashes,
+}
+
+/// A single storage item returned by a storage query. Depending on the query type,
+/// only one of `value`, `hash` and `closest_descendant_merkle_value` is set.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StorageResultItem {
+	pub key: StorageKey,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub value: Option<Bytes>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub hash: Option<Bytes>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub closest_descendant_merkle_value: Option<Bytes>,
+}
+
+impl StorageResultItem {
+	/// Decode the value of the item. Returns `None` if no value has been returned.
+	pub fn decode_value<V: Decode>(&self) -> Result<Option<V>, codec::Error> {
+		self.value.as_ref().map(|value| V::decode(&mut value.0.as_slice())).transpose()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use serde_json::json;
+	use sp_core::H256;
+
+	#[test]
+	fn follow_events_deserialize() {
+		let hash = H256::repeat_byte(1);
+		let initialized = json!({
+			"event": "initialized",
+			"finalizedBlockHashes": [hash],
+			"finalizedBlockRuntime": {
+				"type": "valid",
+				"spec": {
+					"specName": "node",
+					"implName": "node",
+					"specVersion": 1,
+					"implVersion": 2,
+					"transactionVersion": 3,
+					"apis": { "0xdf6acb689907609b": 5 },
+				},
+			},
+		});
+		let finalized = json!({
+			"event": "finalized",
+			"finalizedBlockHashes": [hash],
+			"prunedBlockHashes": [],
+		});
+
+		let FollowEvent::Initialized(initialized) = serde_json::from_value(initialized).unwrap()
+		else {
+			panic!("Expected initialized event")
+		};
+		let finalized: FollowEvent<H256> = serde_json::from_value(finalized).unwrap();
+		let stop: FollowEvent<H256> = serde_json::from_value(json!({ "event": "stop" })).unwrap();
+
+		assert_eq!(initialized.finalized_block_hashes, vec![hash]);
+		let Some(BlockRuntime::Valid(runtime)) = initialized.finalized_block_runtime else {
+			panic!("Expected valid runtime")
+		};
+		assert_eq!(runtime.spec.spec_version, 1);
+		assert_eq!(runtime.spec.apis["0xdf6acb689907609b"], 5);
+		assert_eq!(
+			finalized,
+			FollowEvent::Finalized(Finalized {
+				finalized_block_hashes: vec![hash],
+				pruned_block_hashes: vec![]
+			})
+		);
+		assert_eq!(stop, FollowEvent::Stop);
+	}
+
+	#[test]
+	fn storage_query_serializes_type() {
+		let query = StorageQuery::descendants_values(StorageKey(vec![1, 2]));
+
+		let value = serde_json::to_value(query).unwrap();
+
+		assert_eq!(value, json!({ "key": "0x0102", "type": "descendantsValues" }));
+	}
+
+	#[test]
+	fn storage_items_decode_value() {
+		let event = json!({
+			"event": "operationStorageItems",
+			"operationId": "1",
+			"items": [{ "key": "0x01", "value": "0x2a000000" }, { "key": "0x02", "hash": "0x03" }],
+		});
+
+		let FollowEvent::<H256>::OperationStorageItems(storage) =
+			serde_json::from_value(event).unwrap()
+		else {
+			panic!("Expected storage items")
+		};
+
+		assert_eq!(storage.items[0].decode_value::<u32>().unwrap(), Some(42));
+		assert_eq!(storage.items[1].decode_value::<u32>().unwrap(), None);
+		assert_eq!(storage.items[1].hash, Some(Bytes(vec![3])));
+	}
+
+	#[test]
+	fn method_response_deserializes() {
+		let started = json!({ "result": "started", "operationId": "7" });
+		let limit_reached = json!({ "result": "limitReached" });
+
+		assert_eq!(
+			serde_json::from_value::<MethodResponse>(started).unwrap(),
+			MethodResponse::Started(MethodResponseStarted {
+				operation_id: "7".into(),
+				discarded_items: None
+			})
+		);
+		assert_eq!(
+			serde_json::from_value::<MethodResponse>(limit_reached).unwrap(),
+			MethodResponse::LimitReached
+		);
+	}
+}
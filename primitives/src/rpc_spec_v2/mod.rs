@@ -0,0 +1,27 @@
+/*
+   Copyright 2019 Supercomputing Systems AG
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+	   http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+
+*/
+
+//! Types of the new json-rpc interface specification:
+//! https://paritytech.github.io/json-rpc-interface-spec/
+
+pub use archive::*;
+pub use chain_head::*;
+pub use transaction::*;
+
+pub mod archive;
+pub mod chain_head;
+pub mod transaction;
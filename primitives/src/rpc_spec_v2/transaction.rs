@@ -0,0 +1,146 @@
+/*
+   Copyright 2019 Supercomputing Systems AG
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+	   http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+
+*/
+
+//! Types of the `transactionWatch_v1` and `transaction_v1` functions.
+// https://paritytech.github.io/json-rpc-interface-spec/api/transactionWatch.html
+
+use alloc::string::String;
+use serde::{Deserialize, Serialize};
+
+/// Event of a `transactionWatch_v1_submitAndWatch` subscription.
+///
+/// The events `Finalized`, `Error`, `Invalid` and `Dropped` are final,
+/// the subscription ends afterwards.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "event")]
+pub enum TransactionEvent<Hash> {
+	/// The transaction has been validated and is part of the pool.
+	Validated,
+	/// The transaction has been broadcasted to other nodes.
+	Broadcasted(TransactionBroadcasted),
+	/// The transaction has been included in a block of the best chain, or has been removed from
+	/// it again if `block` is `None`.
+	BestChainBlockIncluded(TransactionBlockIncluded<Hash>),
+	/// The transaction has been included in a finalized block.
+	Finalized(TransactionFinalized<Hash>),
+	/// The node failed to process the transaction. It might still end up in a block.
+	Error(TransactionError),
+	/// The transaction is invalid.
+	Invalid(TransactionError),
+	/// The node does not track the transaction anymore, e.g. because the pool is full.
+	Dropped(TransactionError),
+}
+
+impl<Hash> TransactionEvent<Hash> {
+	/// Returns true if the subscription ends after this event.
+	pub fn is_final(&self) -> bool {
+		matches!(self, Self::Finalized(_) | Self::Error(_) | Self::Invalid(_) | Self::Dropped(_))
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransactionBroadcasted {
+	/// Number of peers the transaction has been broadcasted to.
+	pub num_peers: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransactionBlockIncluded<Hash> {
+	pub block: Option<TransactionBlock<Hash>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransactionFinalized<Hash> {
+	pub block: TransactionBlock<Hash>,
+}
+
+/// Block a transaction has been included in.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransactionBlock<Hash> {
+	pub hash: Hash,
+	/// Index of the transaction within the body of the block.
+	#[serde(with = "index_as_string")]
+	pub index: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransactionError {
+	pub error: String,
+}
+
+/// The index is transmitted as string, to support numbers beyond the range of json numbers.
+mod index_as_string {
+	use alloc::string::{String, ToString};
+	use serde::{Deserialize, Deserializer, Serializer, de::Error};
+
+	pub fn serialize<S: Serializer>(index: &usize, serializer: S) -> Result<S::Ok, S::Error> {
+		serializer.serialize_str(&index.to_string())
+	}
+
+	pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<usize, D::Error> {
+		String::deserialize(deserializer)?.parse().map_err(D::Error::custom)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use serde_json::json;
+	use sp_core::H256;
+
+	#[test]
+	fn transaction_events_deserialize() {
+		let hash = H256::repeat_byte(1);
+		let included = json!({
+			"event": "bestChainBlockIncluded",
+			"block": { "hash": hash, "index": "3" },
+		});
+		let retracted = json!({ "event": "bestChainBlockIncluded", "block": null });
+		let invalid = json!({ "event": "invalid", "error": "Transaction is outdated" });
+
+		assert_eq!(
+			serde_json::from_value::<TransactionEvent<H256>>(included).unwrap(),
+			TransactionEvent::BestChainBlockIncluded(TransactionBlockIncluded {
+				block: Some(TransactionBlock { hash, index: 3 })
+			})
+		);
+		assert_eq!(
+			serde_json::from_value::<TransactionEvent<H256>>(retracted).unwrap(),
+			TransactionEvent::BestChainBlockIncluded(TransactionBlockIncluded { block: None })
+		);
+		let invalid = serde_json::from_value::<TransactionEvent<H256>>(invalid).unwrap();
+		assert!(invalid.is_final());
+		assert_eq!(
+			invalid,
+			TransactionEvent::Invalid(TransactionError { error: "Transaction is outdated".into() })
+		);
+	}
+
+	#[test]
+	fn block_index_serializes_as_string() {
+		let block = TransactionBlock { hash: H256::zero(), index: 7 };
+
+		let value = serde_json::to_value(block).unwrap();
+
+		assert_eq!(value["index"], json!("7"));
+	}
+}
@@ -22,8 +22,11 @@ use jsonrpsee::{
 	core::client::{Client, ClientBuilder},
 	server::{RpcModule, Server},
 };
-use substrate_api_client::rpc::JsonrpseeClient;
-use tokio::{sync::oneshot, task, time, time::Duration};
+use substrate_api_client::rpc::{
+	JsonrpseeClient, JsonrpseeClientBuilder,
+	jsonrpsee_client::{HeaderName, HeaderValue},
+};
+use tokio::{io::AsyncReadExt, net::TcpListener, sync::oneshot, task, time, time::Duration};
 
 #[tokio::main]
 async fn main() {
@@ -85,4 +88,36 @@ async fn main() {
 	}
 
 	client_handle.await.unwrap();
+
+	test_handshake_headers().await;
+}
+
+/// The headers of the builder are sent with the websocket handshake.
+async fn test_handshake_headers() {
+	// Invalid header names are rejected instead of panicking.
+	let builder = JsonrpseeClientBuilder::new("ws://127.0.0.1:9944");
+	assert!(builder.header("in valid", HeaderValue::from_static("value")).is_err());
+
+	let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+	let url = format!("ws://{}", listener.local_addr().unwrap());
+	let builder = JsonrpseeClientBuilder::new(&url)
+		.header("Authorization", HeaderValue::from_static("Bearer token"))
+		.unwrap()
+		.header(HeaderName::from_static("x-api-key"), HeaderValue::from_static("key"))
+		.unwrap();
+	// The listener never completes the handshake, it only reads the http request.
+	let connection = task::spawn(builder.build());
+
+	let (mut stream, _) = listener.accept().await.unwrap();
+	let mut request = Vec::new();
+	let mut buffer = [0u8; 1024];
+	while !request.ends_with(b"\r\n\r\n") {
+		let len = stream.read(&mut buffer).await.unwrap();
+		assert!(len > 0, "Connection closed during handshake");
+		request.extend_from_slice(&buffer[..len]);
+	}
+	let request = String::from_utf8(request).unwrap().to_lowercase();
+	assert!(request.contains("\r\nauthorization: bearer token\r\n"));
+	assert!(request.contains("\r\nx-api-key: key\r\n"));
+	connection.abort();
 }
@@ -0,0 +1,174 @@
+/*
+	Copyright 2024 Supercomputing Systems AG
+	Licensed under the Apache License, Version 2.0 (the "License");
+	you may not use this file except in compliance with the License.
+	You may obtain a copy of the License at
+
+		http://www.apache.org/licenses/LICENSE-2.0
+
+	Unless required by applicable law or agreed to in writing, software
+	distributed under the License is distributed on an "AS IS" BASIS,
+	WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+	See the License for the specific language governing permissions and
+	limitations under the License.
+*/
+
+//! Tests for the ReconnectingJsonrpseeClient. A local jsonrpsee server is used as node, which is
+//! connected via proxy. Restarting the proxy closes the connection of the client.
+
+use jsonrpsee::{
+	core::to_json_raw_value,
+	server::{RpcModule, Server},
+	types::ErrorObjectOwned,
+};
+use std::{
+	net::SocketAddr,
+	sync::{
+		Arc,
+		atomic::{AtomicUsize, Ordering},
+	},
+};
+use substrate_api_client::{
+	ac_compose_macros::rpc_params,
+	rpc::{Error, HandleSubscription, ReconnectingJsonrpseeClient, Request, Subscribe},
+};
+use tokio::{
+	io::copy_bidirectional,
+	net::{TcpListener, TcpStream},
+	task::{JoinHandle, JoinSet},
+	time::{Duration, sleep},
+};
+
+/// Time the server needs to answer, so there is enough time to close the connection meanwhile.
+const RESPONSE_DELAY: Duration = Duration::from_millis(500);
+
+#[derive(Default)]
+struct Calls {
+	system_name: AtomicUsize,
+	submit_extrinsic: AtomicUsize,
+}
+
+#[tokio::main]
+async fn main() {
+	let calls = Arc::new(Calls::default());
+	let server = start_server(calls.clone()).await;
+	let mut proxy = Proxy::start(server).await;
+	let client = ReconnectingJsonrpseeClient::new(&proxy.url()).await.unwrap();
+
+	// An idempotent request is resent after reconnecting.
+	let request = tokio::spawn({
+		let client = client.clone();
+		async move { client.request::<String>("system_name", rpc_params![]).await }
+	});
+	sleep(RESPONSE_DELAY / 5).await;
+	proxy.restart().await;
+	assert_eq!(request.await.unwrap().unwrap(), "node");
+	assert_eq!(calls.system_name.load(Ordering::Relaxed), 2);
+	assert_eq!(client.reconnections(), 1);
+
+	// An extrinsic is never submitted twice.
+	let request = tokio::spawn({
+		let client = client.clone();
+		async move { client.request::<String>("author_submitExtrinsic", rpc_params![]).await }
+	});
+	sleep(RESPONSE_DELAY / 5).await;
+	proxy.restart().await;
+	assert!(matches!(request.await.unwrap(), Err(Error::ConnectionClosed)));
+	assert_eq!(calls.submit_extrinsic.load(Ordering::Relaxed), 1);
+	// The next request reconnects.
+	assert_eq!(client.request::<String>("system_name", rpc_params![]).await.unwrap(), "node");
+	assert_eq!(client.reconnections(), 2);
+
+	// A subscription is renewed and signals that notifications might have been missed.
+	let mut subscription = client
+		.subscribe::<u32>("subscribe_numbers", rpc_params![], "unsubscribe_numbers")
+		.await
+		.unwrap();
+	let subscription_id = subscription.subscription_id();
+	assert_eq!(subscription.next().await.unwrap().unwrap(), 1);
+	proxy.restart().await;
+	assert!(matches!(subscription.next().await, Some(Err(Error::SubscriptionRenewed))));
+	assert_eq!(subscription.next().await.unwrap().unwrap(), 1);
+	assert_ne!(subscription.subscription_id(), subscription_id);
+	assert_eq!(client.reconnections(), 3);
+	subscription.unsubscribe().await.unwrap();
+}
+
+/// Starts a server that answers `system_name` and `author_submitExtrinsic` with a delay and
+/// sends a single notification to every `subscribe_numbers` subscription.
+async fn start_server(calls: Arc<Calls>) -> SocketAddr {
+	let mut module = RpcModule::new(calls);
+	module
+		.register_async_method("system_name", |_, calls, _| async move {
+			calls.system_name.fetch_add(1, Ordering::Relaxed);
+			sleep(RESPONSE_DELAY).await;
+			Ok::<_, ErrorObjectOwned>("node")
+		})
+		.unwrap();
+	module
+		.register_async_method("author_submitExtrinsic", |_, calls, _| async move {
+			calls.submit_extrinsic.fetch_add(1, Ordering::Relaxed);
+			sleep(RESPONSE_DELAY).await;
+			Ok::<_, ErrorObjectOwned>("0x00")
+		})
+		.unwrap();
+	module
+		.register_subscription(
+			"subscribe_numbers",
+			"numbers",
+			"unsubscribe_numbers",
+			|_, pending, _, _| async move {
+				let Ok(sink) = pending.accept().await else { return };
+				let _ = sink.send(to_json_raw_value(&1u32).unwrap().into()).await;
+				sink.closed().await;
+			},
+		)
+		.unwrap();
+
+	let server = Server::builder().build("127.0.0.1:0").await.unwrap();
+	let addr = server.local_addr().unwrap();
+	// The server keeps running until the end of the test.
+	let handle = server.start(module);
+	tokio::spawn(handle.stopped());
+	addr
+}
+
+/// Forwards all connections to the server. Restarting it closes all forwarded connections.
+struct Proxy {
+	addr: SocketAddr,
+	server: SocketAddr,
+	task: JoinHandle<()>,
+}
+
+impl Proxy {
+	async fn start(server: SocketAddr) -> Self {
+		let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+		let addr = listener.local_addr().unwrap();
+		Self { addr, server, task: forward(listener, server) }
+	}
+
+	fn url(&self) -> String {
+		format!("ws://{}", self.addr)
+	}
+
+	async fn restart(&mut self) {
+		self.task.abort();
+		let _ = (&mut self.task).await;
+		let listener = TcpListener::bind(self.addr).await.unwrap();
+		self.task = forward(listener, self.server);
+	}
+}
+
+fn forward(listener: TcpListener, server: SocketAddr) -> JoinHandle<()> {
+	tokio::spawn(async move {
+		// Aborting the task drops the set, which aborts all forwarding tasks.
+		let mut connections = JoinSet::new();
+		loop {
+			let (mut inbound, _) = listener.accept().await.unwrap();
+			connections.spawn(async move {
+				let mut outbound = TcpStream::connect(server).await.unwrap();
+				let _ = copy_bidirectional(&mut inbound, &mut outbound).await;
+			});
+		}
+	})
+}
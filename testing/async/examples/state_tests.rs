@@ -21,7 +21,7 @@ use pallet_society::Vote;
 use sp_core::{crypto::Ss58Codec, sr25519};
 use sp_keyring::Sr25519Keyring;
 use substrate_api_client::{
-	Api, GetChainInfo, GetStorage,
+	Api, GetChainInfo, GetStorage, GetStorageBatch,
 	ac_primitives::{Config, RococoRuntimeConfig},
 	rpc::JsonrpseeClient,
 };
@@ -91,6 +91,16 @@ async fn main() {
 		.await
 		.unwrap()
 		.unwrap();
+	let bob_storage_key = api
+		.metadata()
+		.storage_map_key("System", "Account", Sr25519Keyring::Bob.to_account_id())
+		.unwrap();
+	let account_data_batch: Vec<Option<AccountData>> = api
+		.get_storage_by_keys(vec![storage_key.clone(), bob_storage_key], Some(block_hash))
+		.await
+		.unwrap();
+	assert_eq!(account_data_batch.len(), 2);
+	assert!(account_data_batch.iter().all(|account_data| account_data.is_some()));
 	let _keys = api.get_keys(storage_key, None).await.unwrap().unwrap();
 	let _constants: Balance = api.get_constant("Balances", "ExistentialDeposit").await.unwrap();
 
@@ -0,0 +1,92 @@
+/*
+	Copyright 2024 Supercomputing Systems AG
+	Licensed under the Apache License, Version 2.0 (the "License");
+	you may not use this file except in compliance with the License.
+	You may obtain a copy of the License at
+
+		http://www.apache.org/licenses/LICENSE-2.0
+
+	Unless required by applicable law or agreed to in writing, software
+	distributed under the License is distributed on an "AS IS" BASIS,
+	WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+	See the License for the specific language governing permissions and
+	limitations under the License.
+*/
+
+//! Tests for the SubmissionQueue. A local jsonrpsee server is used as node, which delays the
+//! status of the first extrinsic it watches.
+
+use futures_util::StreamExt;
+use jsonrpsee::{
+	core::to_json_raw_value,
+	server::{RpcModule, Server},
+	types::ErrorObjectOwned,
+};
+use sp_keyring::Sr25519Keyring;
+use std::{
+	net::SocketAddr,
+	sync::atomic::{AtomicUsize, Ordering},
+};
+use substrate_api_client::{
+	Api, RpcCapabilities, XtStatus, ac_primitives::RococoRuntimeConfig, rpc::JsonrpseeClient,
+};
+use tokio::time::{Duration, sleep};
+
+/// Time the server waits until it reports the first extrinsic as ready.
+const READY_DELAY: Duration = Duration::from_millis(500);
+
+#[tokio::main]
+async fn main() {
+	let server = start_server().await;
+	let client = JsonrpseeClient::new(&format!("ws://{server}")).await.unwrap();
+	let api = Api::<RococoRuntimeConfig, _>::builder(client)
+		.metadata_from_bytes(include_bytes!("../../../ksm_metadata_v14.bin"))
+		.unwrap()
+		.genesis_hash(Default::default())
+		.runtime_version(Default::default())
+		.rpc_capabilities(RpcCapabilities::unknown())
+		.signer(Sr25519Keyring::Alice.pair().into())
+		.build()
+		.await
+		.unwrap();
+
+	let mut queue = api.submission_queue().unwrap().watch_until(XtStatus::Ready);
+	queue.push(0u8);
+	queue.push(1u8);
+	let outcomes: Vec<_> = queue.into_outcomes().collect().await;
+
+	// The second extrinsic is ready while the first one is still being watched.
+	assert_eq!(outcomes.iter().map(|(index, _)| *index).collect::<Vec<_>>(), vec![1, 0]);
+	assert!(outcomes.iter().all(|(_, report)| report.is_ok()));
+}
+
+/// Starts a server that hands out the nonces and reports every watched extrinsic as ready,
+/// the first one only after `READY_DELAY`.
+async fn start_server() -> SocketAddr {
+	let mut module = RpcModule::new(AtomicUsize::new(0));
+	module
+		.register_method("system_accountNextIndex", |_, _, _| Ok::<_, ErrorObjectOwned>(0u32))
+		.unwrap();
+	module
+		.register_subscription(
+			"author_submitAndWatchExtrinsic",
+			"author_extrinsicUpdate",
+			"author_unwatchExtrinsic",
+			|_, pending, watched, _| async move {
+				let Ok(sink) = pending.accept().await else { return };
+				if watched.fetch_add(1, Ordering::Relaxed) == 0 {
+					sleep(READY_DELAY).await;
+				}
+				let _ = sink.send(to_json_raw_value(&"ready").unwrap().into()).await;
+				sink.closed().await;
+			},
+		)
+		.unwrap();
+
+	let server = Server::builder().build("127.0.0.1:0").await.unwrap();
+	let addr = server.local_addr().unwrap();
+	// The server keeps running until the end of the test.
+	let handle = server.start(module);
+	tokio::spawn(handle.stopped());
+	addr
+}
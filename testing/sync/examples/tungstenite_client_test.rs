@@ -19,8 +19,10 @@ use sp_core::{
 };
 use sp_runtime::MultiAddress;
 use substrate_api_client::{
-	Api, GetAccountInformation, SubmitAndWatch, XtStatus, ac_primitives::RococoRuntimeConfig,
-	extrinsic::BalancesExtrinsics, rpc::TungsteniteRpcClient,
+	Api, Error, GetAccountInformation, SubmitAndWatch, SubscribeChain, XtStatus,
+	ac_primitives::RococoRuntimeConfig,
+	extrinsic::BalancesExtrinsics,
+	rpc::{HandleSubscription, TungsteniteRpcClient},
 };
 
 fn main() {
@@ -38,6 +40,14 @@ fn main() {
 		.unwrap();
 	let bob_balance = api.get_account_data(&bob.into()).unwrap().unwrap_or_default().free;
 
+	// Check that a subscription can be ended while the connection is still used for requests.
+	let mut subscription = api.subscribe_finalized_heads().unwrap();
+	let _header = subscription.next().unwrap().unwrap();
+	// The id is looked up in the subscription table of the connection.
+	assert!(subscription.subscription_id().is_some());
+	subscription.unsubscribe().unwrap();
+	api.get_account_data(&bob.into()).unwrap();
+
 	// Check for failed extrinsic failed onchain
 	let xt = api
 		.balance_transfer_allow_death(MultiAddress::Id(bob.into()), bob_balance + 1)
@@ -47,8 +57,8 @@ fn main() {
 
 	// Check directly failed extrinsic (before actually submitted to a block)
 	let result = api.submit_and_watch_extrinsic_until(xt, XtStatus::InBlock);
-	assert!(result.is_err());
-	assert!(format!("{result:?}").contains("ExtrinsicFailed"));
+	// The transaction pool rejects it, because it has already been included.
+	assert!(matches!(result, Err(Error::RpcClient(e)) if e.pool_error().is_some()));
 
 	// Check for successful extrinisc
 	let xt = api